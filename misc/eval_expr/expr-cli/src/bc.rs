@@ -0,0 +1,259 @@
+use expr_core::{
+    split_assignment, tokenize, Context, EvalOptions, Expr, ExprError, PrecedenceTable, Value,
+};
+
+use crate::Failure;
+
+// What `.` and `last` are rewritten to; bc names are lowercase letters, so
+// no program can use it for anything else.
+const LAST: &str = "_";
+
+/// Runs `lines` as a `bc` program would be, printing the value of each
+/// expression statement and reporting errors as bc does, with the line
+/// number, without stopping. Fails as the first statement that failed did.
+pub fn run(lines: impl Iterator<Item = Result<String, String>>) -> Result<(), Failure> {
+    let mut bc = Bc::new();
+    let mut failure = None;
+    let mut statements = Statements::default();
+
+    for (i, line) in lines.enumerate() {
+        for stmt in statements.push(&line?) {
+            if stmt == "quit" {
+                return failure.map_or(Ok(()), Err);
+            }
+            match bc.exec(&stmt) {
+                Ok(Some(value)) => println!("{}", value),
+                Ok(None) => {}
+                Err(err) => {
+                    eprintln!("(standard_in) {}: {}", i + 1, err);
+                    failure.get_or_insert(Failure::of(&err));
+                }
+            }
+        }
+    }
+
+    failure.map_or(Ok(()), Err)
+}
+
+/// The state of a bc session: its variables, and the last value printed
+/// under `LAST`. As in bc, every variable, `last` included, is 0 until
+/// assigned, and unary minus binds tighter than `^`, so `-2 ^ 2` is 4.
+///
+/// Values are integers, so `scale`, the number of decimal places bc keeps,
+/// is always 0; that is also bc's default, under which it truncates
+/// quotients as this crate does.
+struct Bc {
+    context: Context,
+    options: EvalOptions,
+}
+
+impl Bc {
+    fn new() -> Self {
+        let mut context = Context::new();
+        context.set(LAST, 0);
+        let precedence = PrecedenceTable::default().prefix("-", 8);
+        Self {
+            context,
+            options: EvalOptions::default().precedence(precedence),
+        }
+    }
+
+    /// Runs one statement, returning the value to print, if any: an
+    /// expression's, but not an assignment's, as in bc.
+    fn exec(&mut self, stmt: &str) -> Result<Option<Value>, ExprError> {
+        let (name, src) = match split_compound(stmt) {
+            Some((name, src)) => (Some(name), src),
+            None => (None, stmt.to_string()),
+        };
+
+        if name == Some("scale") {
+            return match self.eval(&src)? {
+                Value::Int(0) => Ok(None),
+                scale => Err(ExprError::Unsupported(format!(
+                    "scale={}, as values are integers",
+                    scale
+                ))),
+            };
+        }
+        if src.trim() == "scale" && name.is_none() {
+            return Ok(Some(Value::Int(0)));
+        }
+
+        let value = self.eval(&src)?;
+        match name {
+            Some(name) => {
+                self.context.set(name, value);
+                Ok(None)
+            }
+            None => {
+                self.context.set(LAST, value);
+                Ok(Some(value))
+            }
+        }
+    }
+
+    fn eval(&mut self, src: &str) -> Result<Value, ExprError> {
+        let src = with_last(src);
+        let tokens = tokenize(&src);
+        for (i, (span, kind)) in tokens.iter().enumerate() {
+            let name = &src[span.start..span.end];
+            let is_call = tokens.get(i + 1).is_some_and(|(_, next)| *next == "(");
+            if *kind == "identifier" && !is_call && self.context.get(name).is_none() {
+                self.context.set(name, 0);
+            }
+        }
+        Expr::with_options(&src, self.options.clone())
+            .with_context(&self.context)
+            .eval()
+    }
+}
+
+// Splits an assignment, turning `x op= e` into `x` and `x op (e)`.
+fn split_compound(stmt: &str) -> Option<(&str, String)> {
+    if let Some((name, src)) = split_assignment(stmt) {
+        return Some((name, src.to_string()));
+    }
+    let (lhs, rhs) = stmt.split_once('=')?;
+    let op = lhs.trim_end().chars().last()?;
+    let name = lhs.trim_end().strip_suffix(op)?.trim();
+    let is_name = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    (is_name && "+-*/%^".contains(op)).then(|| (name, format!("{} {} ({})", name, op, rhs)))
+}
+
+// `src` with `.` and `last`, bc's names for the last value printed,
+// replaced by `LAST`. A `.` next to a digit is left for the tokenizer to
+// reject, as a decimal point.
+fn with_last(src: &str) -> String {
+    let mut out = String::new();
+    let mut end = 0;
+    for (span, kind) in tokenize(src) {
+        let text = &src[span.start..span.end];
+        let is_digit = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit());
+        let last = match (kind, text) {
+            ("identifier", "last") => true,
+            ("unknown", ".") => {
+                !is_digit(src[..span.start].chars().last())
+                    && !is_digit(src[span.end..].chars().next())
+            }
+            _ => false,
+        };
+        if last {
+            out.push_str(&src[end..span.start]);
+            out.push_str(LAST);
+            end = span.end;
+        }
+    }
+    out.push_str(&src[end..]);
+    out
+}
+
+/// Splits a program into statements, separated by `;` or newlines, without
+/// `#` and `/* */` comments or blank statements. A `/* */` comment may span
+/// lines, and the statement it is in goes on after it.
+#[derive(Default)]
+struct Statements {
+    // Whether the next line starts inside a `/* */` comment.
+    comment: bool,
+    // The statement the comment interrupted.
+    pending: String,
+}
+
+impl Statements {
+    /// The statements `line` ends.
+    fn push(&mut self, line: &str) -> Vec<String> {
+        let mut code = std::mem::take(&mut self.pending);
+        let mut rest = line;
+        loop {
+            if self.comment {
+                let Some(end) = rest.find("*/") else {
+                    break;
+                };
+                rest = &rest[end + 2..];
+                self.comment = false;
+                code.push(' ');
+            }
+            let Some(start) = [rest.find('#'), rest.find("/*")]
+                .into_iter()
+                .flatten()
+                .min()
+            else {
+                code.push_str(rest);
+                break;
+            };
+            code.push_str(&rest[..start]);
+            if rest[start..].starts_with('#') {
+                break;
+            }
+            rest = &rest[start + 2..];
+            self.comment = true;
+        }
+        if self.comment {
+            let open = code.rfind(';').map_or(0, |end| end + 1);
+            self.pending = code.split_off(open);
+        }
+        code.split(';')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(src: &str) -> Vec<String> {
+        let mut bc = Bc::new();
+        let mut statements = Statements::default();
+        src.lines()
+            .flat_map(|line| statements.push(line))
+            .filter_map(|stmt| match bc.exec(&stmt) {
+                Ok(value) => value.map(|value| value.to_string()),
+                Err(err) => Some(format!("error: {}", err)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bc() {
+        assert_eq!(
+            session("x = 7; y=2\nx / y; x % y  /* truncated */\n2 ^ 10\n. + 1\nlast * 2 # doubled"),
+            ["3", "1", "1024", "1025", "2050"]
+        );
+        assert_eq!(
+            session("n = 5\nn += 2\nn *= 3\nn\nn ^= 2; n"),
+            ["21", "441"]
+        );
+        assert_eq!(
+            session("scale\nscale = 0\nscale=2\n1.5"),
+            [
+                "0".to_string(),
+                "error: Unsupported operation: scale=2, as values are integers".into(),
+                format!("error: {}", expr_core::eval("1.5").unwrap_err()),
+            ]
+        );
+        assert_eq!(
+            Statements::default().push("1; /* a; b */ 2 ;; 3 / 1 /* open"),
+            ["1", "2"]
+        );
+    }
+
+    #[test]
+    fn test_bc_defaults() {
+        assert_eq!(session("last * 2\n. + 1"), ["0", "1"]);
+        assert_eq!(
+            session("x += 2; x\ny * 3 + z\nf(1)"),
+            [
+                "2".to_string(),
+                "0".into(),
+                "error: Unknown function: f".into(),
+            ]
+        );
+        assert_eq!(session("-2 ^ 2\n-3 ^ 3\n-2 ^ 2 * 3"), ["4", "-27", "12"]);
+        assert_eq!(
+            session("1 /* a comment\nover ; lines # 2\n*/ + 2; 5 # /*\n4"),
+            ["3", "5", "4"]
+        );
+    }
+}
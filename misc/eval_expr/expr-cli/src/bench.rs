@@ -0,0 +1,186 @@
+use std::{
+    collections::BTreeMap,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use expr_core::{parse, Context, EvalOptions, Expr};
+
+/// How long each workload is timed for, after a warm-up of the same length.
+const SAMPLE_TIME: Duration = Duration::from_millis(200);
+
+/// Runs `eval_expr bench`, timing each stage (`parse`, `tree` for
+/// [`Ast::eval_with`](expr_core::Ast::eval_with), `vm` for
+/// [`CompiledExpr::eval`](expr_core::CompiledExpr::eval) and `fused` for
+/// [`Expr::eval`]) on each built-in input and printing nanoseconds per run.
+/// With `--baseline PATH` each time is compared with one saved earlier by
+/// `--save PATH`, and the command fails if any is more than `threshold`
+/// percent slower.
+///
+/// The tokenizer is internal to the library, so lexing is timed as part of
+/// the `parse` stage.
+pub fn run(baseline: Option<&String>, save: Option<&String>, threshold: f64) -> Result<(), String> {
+    let baseline = match baseline {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            from_json(&text).map_err(|err| format!("{}: {}", path, err))?
+        }
+        None => BTreeMap::new(),
+    };
+
+    let mut context = Context::new();
+    context.set("x", 3);
+    context.set("y", -7);
+    let options = EvalOptions::default();
+
+    let mut results = BTreeMap::new();
+    let mut regressed = 0;
+    for (input, src) in inputs() {
+        let ast = parse(&src).map_err(|err| err.to_string())?;
+        let compiled = ast.compile(&options);
+        let stages: [(&str, &dyn Fn()); 4] = [
+            ("parse", &|| {
+                black_box(parse(black_box(&src)).ok());
+            }),
+            ("tree", &|| {
+                black_box(ast.eval_with(&options, &context).ok());
+            }),
+            ("vm", &|| {
+                black_box(compiled.eval(&context).ok());
+            }),
+            ("fused", &|| {
+                black_box(
+                    Expr::new(black_box(&src))
+                        .with_context(&context)
+                        .eval()
+                        .ok(),
+                );
+            }),
+        ];
+
+        for (stage, workload) in stages {
+            let name = format!("{}/{}", stage, input);
+            let nanos = time(workload);
+            let (line, slower) = compare(&name, nanos, baseline.get(&name), threshold);
+            println!("{}", line);
+            regressed += slower as usize;
+            results.insert(name, nanos);
+        }
+    }
+
+    if let Some(path) = save {
+        std::fs::write(path, to_json(&results)).map_err(|err| format!("{}: {}", path, err))?;
+    }
+
+    if regressed > 0 {
+        return Err(format!(
+            "{} of {} workloads regressed",
+            regressed,
+            results.len()
+        ));
+    }
+    Ok(())
+}
+
+// The inputs each stage is timed on, by name.
+fn inputs() -> [(&'static str, String); 3] {
+    let wide = (1..=50)
+        .map(|i| format!("(x * {} - y) % {}", i, i + 1))
+        .collect::<Vec<_>>()
+        .join(" + ");
+    let deep = format!("{}x{}", "(1 + ".repeat(100), ")".repeat(100));
+    [
+        ("small", "1 + 2 * 3 - 4 / 2".to_string()),
+        ("wide", wide),
+        ("deep", deep),
+    ]
+}
+
+// Nanoseconds per call of `workload`, averaged over `SAMPLE_TIME`.
+fn time(workload: &dyn Fn()) -> f64 {
+    let sample = || {
+        let start = Instant::now();
+        let mut runs = 0u64;
+        while start.elapsed() < SAMPLE_TIME {
+            // Look at the clock every few runs, not after each one.
+            for _ in 0..16 {
+                workload();
+            }
+            runs += 16;
+        }
+        start.elapsed().as_nanos() as f64 / runs as f64
+    };
+    sample();
+    sample()
+}
+
+// The report line for one workload, and whether it regressed against
+// `baseline`.
+fn compare(name: &str, nanos: f64, baseline: Option<&f64>, threshold: f64) -> (String, bool) {
+    let Some(&baseline) = baseline else {
+        return (format!("{:<12} {:>12.1} ns", name, nanos), false);
+    };
+    let change = (nanos / baseline - 1.0) * 100.0;
+    let regressed = change > threshold;
+    let line = format!(
+        "{:<12} {:>12.1} ns {:>+8.1}%{}",
+        name,
+        nanos,
+        change,
+        if regressed { "  REGRESSED" } else { "" }
+    );
+    (line, regressed)
+}
+
+// Saved results are a flat JSON object from workload name to nanoseconds.
+fn to_json(results: &BTreeMap<String, f64>) -> String {
+    let fields = results
+        .iter()
+        .map(|(name, nanos)| format!("  \"{}\": {:.1}", name, nanos))
+        .collect::<Vec<_>>();
+    format!("{{\n{}\n}}\n", fields.join(",\n"))
+}
+
+fn from_json(text: &str) -> Result<BTreeMap<String, f64>, String> {
+    let invalid = || "not a saved benchmark result".to_string();
+    let body = text
+        .trim()
+        .strip_prefix('{')
+        .and_then(|text| text.strip_suffix('}'))
+        .ok_or_else(invalid)?;
+
+    let mut results = BTreeMap::new();
+    for field in body.split(',').filter(|field| !field.trim().is_empty()) {
+        let (name, nanos) = field.split_once(':').ok_or_else(invalid)?;
+        let name = name
+            .trim()
+            .strip_prefix('"')
+            .and_then(|name| name.strip_suffix('"'))
+            .ok_or_else(invalid)?;
+        let nanos = nanos.trim().parse().map_err(|_| invalid())?;
+        results.insert(name.to_string(), nanos);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_results() {
+        let results = BTreeMap::from([
+            ("parse/small".to_string(), 120.5),
+            ("vm/deep".to_string(), 3.0),
+        ]);
+        assert_eq!(from_json(&to_json(&results)), Ok(results));
+        assert_eq!(from_json("{}"), Ok(BTreeMap::new()));
+        assert!(from_json("[1, 2]").is_err());
+
+        assert!(!compare("vm/deep", 105.0, Some(&100.0), 10.0).1);
+        let (line, regressed) = compare("vm/deep", 120.0, Some(&100.0), 10.0);
+        assert!(regressed);
+        assert!(line.ends_with("+20.0%  REGRESSED"), "{}", line);
+        assert!(!compare("vm/deep", 1e9, None, 10.0).1);
+    }
+}
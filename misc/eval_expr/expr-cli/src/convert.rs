@@ -0,0 +1,68 @@
+use std::io::{self, BufRead};
+
+use clap::ValueEnum;
+use expr_core::{parse, Ast};
+
+/// A notation `convert` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Source {
+    Infix,
+    Rpn,
+    Prefix,
+    Latex,
+}
+
+/// A notation `convert` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Target {
+    Rpn,
+    Prefix,
+}
+
+/// Runs `eval_expr convert`, printing each of `lines`, written in `from`,
+/// in the notation `to`. `eval_expr rpn` is the same with `to` RPN.
+pub fn run(from: Source, to: Target, lines: &[String]) -> Result<(), String> {
+    let from = match from {
+        Source::Infix => parse as fn(&str) -> expr_core::Result<Ast>,
+        Source::Rpn => Ast::from_rpn,
+        Source::Prefix => Ast::from_prefix,
+        Source::Latex => Ast::from_latex,
+    };
+    let to = match to {
+        Target::Rpn => Ast::to_rpn as fn(&Ast) -> String,
+        Target::Prefix => Ast::to_prefix,
+    };
+
+    let mut failed = false;
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        match from(line) {
+            Ok(ast) => println!("{}", to(&ast)),
+            Err(err) => {
+                eprintln!("{}: {}", line.trim(), err);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return Err("some expressions could not be converted".into());
+    }
+    Ok(())
+}
+
+/// The expressions a subcommand works on: `exprs` joined into one if given,
+/// else the lines of the file at `path`, else the lines of stdin.
+pub fn read_inputs(exprs: &[&str], path: Option<&String>) -> Result<Vec<String>, String> {
+    if !exprs.is_empty() {
+        Ok(vec![exprs.join(" ")])
+    } else if let Some(path) = path {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        Ok(text.lines().map(String::from).collect())
+    } else {
+        io::stdin()
+            .lock()
+            .lines()
+            .collect::<io::Result<_>>()
+            .map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,526 @@
+use std::{cmp::Ordering, collections::HashMap, fmt};
+
+/// A spreadsheet value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Cell::Number(n) => write!(f, "{}", n),
+            Cell::Text(text) => write!(f, "{}", text),
+            Cell::Bool(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+        }
+    }
+}
+
+/// Why a formula failed: it did not parse, or it evaluated to one of the
+/// spreadsheet error values such as `#DIV/0!`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Syntax(String),
+    Value(&'static str, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax(message) => write!(f, "Syntax error: {}", message),
+            Error::Value(code, message) => write!(f, "{} {}", code, message),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+fn syntax<T>(message: impl Into<String>) -> Result<T> {
+    Err(Error::Syntax(message.into()))
+}
+
+fn value_error<T>(message: impl Into<String>) -> Result<T> {
+    Err(Error::Value("#VALUE!", message.into()))
+}
+
+/// Evaluates a spreadsheet formula such as `=IF(A1<>0, ROUND(B2/A1, 2), 0)`,
+/// with names such as cell references looked up in `vars`.
+///
+/// The leading `=` is optional. Numbers are floating point; text is
+/// written in double quotes, with `""` for a quote, and joined with `&`.
+/// The operators, from lowest to highest precedence, are the comparisons
+/// `=`, `<>`, `<`, `<=`, `>` and `>=`, then `&`, `+` and `-`, `*` and `/`,
+/// `^`, a prefix `-`, and a postfix `%`, so that `-2^2` is 4 as in Excel.
+/// Function names are case-insensitive: `SUM`, `AVERAGE`, `MIN`, `MAX`,
+/// `ABS`, `ROUND`, `IF`, `AND`, `OR` and `NOT`, with `,` or `;` between
+/// arguments; `TRUE` and `FALSE` are the booleans.
+pub fn eval(formula: &str, vars: &HashMap<String, f64>) -> Result<Cell> {
+    let src = formula.trim();
+    let src = src.strip_prefix('=').unwrap_or(src);
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+    };
+    let node = parser.comparison()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        return syntax(format!("unexpected {}", token));
+    }
+    node.eval(vars)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Name(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "'{}'", n),
+            Token::Text(text) => write!(f, "\"{}\"", text),
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Op(op) => write!(f, "'{}'", op),
+        }
+    }
+}
+
+// Longest first, so that `<>` is not read as `<` and `>`.
+const OPS: &[&str] = &[
+    "<>", "<=", ">=", "=", "<", ">", "&", "+", "-", "*", "/", "^", "%", "(", ")", ",", ";",
+];
+
+fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = src.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() || c == '.' {
+            let len = number_len(rest);
+            let n = rest[..len]
+                .parse()
+                .or_else(|_| syntax(format!("invalid number '{}'", &rest[..len])))?;
+            tokens.push(Token::Number(n));
+            len
+        } else if c == '"' {
+            let mut text = String::new();
+            let mut chars = rest.char_indices().skip(1).peekable();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) if chars.peek().map(|(_, c)| *c) != Some('"') => break i + 1,
+                    Some((_, '"')) => {
+                        chars.next();
+                        text.push('"');
+                    }
+                    Some((_, c)) => text.push(c),
+                    None => return syntax("unclosed text"),
+                }
+            };
+            tokens.push(Token::Text(text));
+            end
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '$' | '.')))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..len].to_string()));
+            len
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else {
+            return syntax(format!("unexpected '{}'", c));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+// The length of the number at the start of `src`: digits with an optional
+// fraction and exponent.
+fn number_len(src: &str) -> usize {
+    let bytes = src.as_bytes();
+    let digits = |mut i: usize| {
+        while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+        }
+        i
+    };
+    let mut len = digits(0);
+    if bytes.get(len) == Some(&b'.') {
+        len = digits(len + 1);
+    }
+    if matches!(bytes.get(len), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(len + 1), Some(b'+' | b'-')));
+        if bytes.get(len + 1 + sign).is_some_and(u8::is_ascii_digit) {
+            len = digits(len + 1 + sign);
+        }
+    }
+    len
+}
+
+#[derive(Debug)]
+enum Node {
+    Value(Cell),
+    Name(String),
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+    Call(String, Vec<Node>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    // The next token if it is one of the operators `ops`, consuming it.
+    fn op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<()> {
+        match self.op(&[op]) {
+            Some(_) => Ok(()),
+            None => match self.tokens.get(self.pos) {
+                Some(token) => syntax(format!("expected '{}', found {}", op, token)),
+                None => syntax(format!("expected '{}'", op)),
+            },
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[&'static str],
+        operand: fn(&mut Self) -> Result<Node>,
+    ) -> Result<Node> {
+        let mut lhs = operand(self)?;
+        while let Some(op) = self.op(ops) {
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(operand(self)?));
+        }
+        Ok(lhs)
+    }
+
+    fn comparison(&mut self) -> Result<Node> {
+        self.binary(&["=", "<>", "<", "<=", ">", ">="], Self::concat)
+    }
+
+    fn concat(&mut self) -> Result<Node> {
+        self.binary(&["&"], Self::sum)
+    }
+
+    fn sum(&mut self) -> Result<Node> {
+        self.binary(&["+", "-"], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Node> {
+        self.binary(&["*", "/"], Self::power)
+    }
+
+    fn power(&mut self) -> Result<Node> {
+        self.binary(&["^"], Self::negation)
+    }
+
+    fn negation(&mut self) -> Result<Node> {
+        match self.op(&["-", "+"]) {
+            Some(op) => Ok(Node::Unary(op, Box::new(self.negation()?))),
+            None => self.percent(),
+        }
+    }
+
+    fn percent(&mut self) -> Result<Node> {
+        let mut node = self.primary()?;
+        while self.op(&["%"]).is_some() {
+            node = Node::Unary("%", Box::new(node));
+        }
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return syntax("unexpected end of formula");
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Node::Value(Cell::Number(n))),
+            Token::Text(text) => Ok(Node::Value(Cell::Text(text))),
+            Token::Name(name) if self.op(&["("]).is_some() => {
+                let mut args = Vec::new();
+                if self.op(&[")"]).is_none() {
+                    loop {
+                        args.push(self.comparison()?);
+                        if self.op(&[",", ";"]).is_none() {
+                            break;
+                        }
+                    }
+                    self.expect(")")?;
+                }
+                Ok(Node::Call(name.to_ascii_uppercase(), args))
+            }
+            Token::Name(name) => match name.to_ascii_uppercase().as_str() {
+                "TRUE" => Ok(Node::Value(Cell::Bool(true))),
+                "FALSE" => Ok(Node::Value(Cell::Bool(false))),
+                _ => Ok(Node::Name(name)),
+            },
+            Token::Op("(") => {
+                let node = self.comparison()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            token => syntax(format!("unexpected {}", token)),
+        }
+    }
+}
+
+impl Node {
+    // Numbers out of range, however they arise, are `#NUM!` as in Excel:
+    // the literal `1e309`, `SUM(1e308, 1e308)` or `2^1024`.
+    fn eval(&self, vars: &HashMap<String, f64>) -> Result<Cell> {
+        let cell = match self {
+            Node::Value(value) => Ok(value.clone()),
+            Node::Name(name) => vars
+                .iter()
+                .find(|(var, _)| var.eq_ignore_ascii_case(name))
+                .map(|(_, value)| Cell::Number(*value))
+                .ok_or_else(|| Error::Value("#NAME?", format!("unknown name '{}'", name))),
+            Node::Unary(op, operand) => {
+                let n = number(&operand.eval(vars)?)?;
+                Ok(Cell::Number(match *op {
+                    "-" => -n,
+                    "%" => n / 100.0,
+                    _ => n,
+                }))
+            }
+            Node::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(vars)?, rhs.eval(vars)?);
+                binary(op, &lhs, &rhs)
+            }
+            Node::Call(name, args) => call(name, args, vars),
+        }?;
+        match cell {
+            Cell::Number(n) if !n.is_finite() => {
+                Err(Error::Value("#NUM!", "result out of range".into()))
+            }
+            cell => Ok(cell),
+        }
+    }
+}
+
+fn binary(op: &str, lhs: &Cell, rhs: &Cell) -> Result<Cell> {
+    if op == "&" {
+        return Ok(Cell::Text(format!("{}{}", lhs, rhs)));
+    }
+    if let Some(holds) = compare(op, lhs, rhs) {
+        return Ok(Cell::Bool(holds));
+    }
+
+    let (l, r) = (number(lhs)?, number(rhs)?);
+    Ok(Cell::Number(match op {
+        "+" => l + r,
+        "-" => l - r,
+        "*" => l * r,
+        "/" if r == 0.0 => return Err(Error::Value("#DIV/0!", "division by zero".into())),
+        "/" => l / r,
+        _ => l.powf(r),
+    }))
+}
+
+// The result of the comparison `op`, or `None` if `op` is not one. As in
+// spreadsheets, numbers sort before text and text before booleans, and
+// text compares case-insensitively.
+fn compare(op: &str, lhs: &Cell, rhs: &Cell) -> Option<bool> {
+    let rank = |cell: &Cell| match cell {
+        Cell::Number(_) => 0,
+        Cell::Text(_) => 1,
+        Cell::Bool(_) => 2,
+    };
+    let ordering = || match (lhs, rhs) {
+        (Cell::Number(l), Cell::Number(r)) => l.partial_cmp(r).unwrap_or(Ordering::Equal),
+        (Cell::Text(l), Cell::Text(r)) => l.to_lowercase().cmp(&r.to_lowercase()),
+        (Cell::Bool(l), Cell::Bool(r)) => l.cmp(r),
+        _ => rank(lhs).cmp(&rank(rhs)),
+    };
+    Some(match op {
+        "=" => ordering() == Ordering::Equal,
+        "<>" => ordering() != Ordering::Equal,
+        "<" => ordering() == Ordering::Less,
+        "<=" => ordering() != Ordering::Greater,
+        ">" => ordering() == Ordering::Greater,
+        ">=" => ordering() != Ordering::Less,
+        _ => return None,
+    })
+}
+
+// `cell` as a number: booleans are 1 and 0, and text must spell a number.
+fn number(cell: &Cell) -> Result<f64> {
+    match cell {
+        Cell::Number(n) => Ok(*n),
+        Cell::Bool(b) => Ok(f64::from(u8::from(*b))),
+        Cell::Text(text) => text
+            .trim()
+            .parse()
+            .or_else(|_| value_error(format!("\"{}\" is not a number", text))),
+    }
+}
+
+fn truthy(cell: &Cell) -> Result<bool> {
+    match cell {
+        Cell::Bool(b) => Ok(*b),
+        cell => number(cell).map(|n| n != 0.0),
+    }
+}
+
+fn call(name: &str, args: &[Node], vars: &HashMap<String, f64>) -> Result<Cell> {
+    let arity = |min: usize, max: usize| {
+        if (min..=max).contains(&args.len()) {
+            Ok(())
+        } else {
+            value_error(format!("wrong number of arguments to {}", name))
+        }
+    };
+    let numbers = || -> Result<Vec<f64>> {
+        args.iter()
+            .map(|arg| arg.eval(vars).and_then(|value| number(&value)))
+            .collect()
+    };
+
+    match name {
+        "IF" => {
+            arity(2, 3)?;
+            if truthy(&args[0].eval(vars)?)? {
+                args[1].eval(vars)
+            } else {
+                args.get(2)
+                    .map_or(Ok(Cell::Bool(false)), |arg| arg.eval(vars))
+            }
+        }
+        "SUM" => Ok(Cell::Number(numbers()?.iter().sum())),
+        "AVERAGE" => {
+            arity(1, usize::MAX)?;
+            let numbers = numbers()?;
+            Ok(Cell::Number(
+                numbers.iter().sum::<f64>() / numbers.len() as f64,
+            ))
+        }
+        "MIN" => Ok(Cell::Number(
+            numbers()?.into_iter().reduce(f64::min).unwrap_or(0.0),
+        )),
+        "MAX" => Ok(Cell::Number(
+            numbers()?.into_iter().reduce(f64::max).unwrap_or(0.0),
+        )),
+        "ABS" => {
+            arity(1, 1)?;
+            Ok(Cell::Number(numbers()?[0].abs()))
+        }
+        "ROUND" => {
+            arity(2, 2)?;
+            let numbers = numbers()?;
+            Ok(Cell::Number(round(numbers[0], numbers[1].trunc() as i32)))
+        }
+        "AND" | "OR" => {
+            arity(1, usize::MAX)?;
+            let values = args
+                .iter()
+                .map(|arg| arg.eval(vars).and_then(|value| truthy(&value)))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Cell::Bool(if name == "AND" {
+                values.iter().all(|b| *b)
+            } else {
+                values.iter().any(|b| *b)
+            }))
+        }
+        "NOT" => {
+            arity(1, 1)?;
+            Ok(Cell::Bool(!truthy(&args[0].eval(vars)?)?))
+        }
+        _ => Err(Error::Value(
+            "#NAME?",
+            format!("unknown function '{}'", name),
+        )),
+    }
+}
+
+// `n` rounded to `digits` decimal places, or to tens, hundreds and so on
+// for negative `digits`, with halves away from zero as spreadsheets do.
+// Halves are judged on the decimal `n` stands for, not its binary value:
+// `1.005` is stored as 1.00499999..., but rounds to 1.01 as in Excel,
+// which keeps 15 significant digits.
+fn round(n: f64, digits: i32) -> f64 {
+    let scale = 10f64.powi(digits.clamp(-308, 308).abs());
+    let scaled = if digits < 0 { n / scale } else { n * scale };
+    if scaled.abs() >= 1e15 {
+        // Past 15 significant digits, `n` has no decimal places to drop.
+        return n;
+    }
+    let scaled = format!("{:.14e}", scaled)
+        .parse::<f64>()
+        .unwrap_or(scaled)
+        .round();
+    if digits < 0 {
+        scaled * scale
+    } else {
+        scaled / scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excel(formula: &str) -> String {
+        let vars = HashMap::from([("A1".to_string(), 4.0), ("rate".to_string(), 0.08)]);
+        match eval(formula, &vars) {
+            Ok(value) => value.to_string(),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(excel("=1 + 2 * 3"), "7");
+        assert_eq!(excel("=-2^2"), "4");
+        assert_eq!(excel("2^3^2"), "64");
+        assert_eq!(excel("=50% * a1"), "2");
+        assert_eq!(excel("=sum(1, 2; A1) / 2"), "3.5");
+        assert_eq!(excel("=Round(10 / 3, 2)"), "3.33");
+        assert_eq!(excel("=ROUND(2.5, 0) + ROUND(-2.5, 0)"), "0");
+        assert_eq!(excel("=ROUND(1234, -2)"), "1200");
+        assert_eq!(
+            excel("=ROUND(1.005, 2) & \" \" & ROUND(-2.675, 2)"),
+            "1.01 -2.68"
+        );
+        assert_eq!(excel("=ROUND(0.1 + 0.2, 15) = 0.3"), "TRUE");
+        assert_eq!(excel("=ROUND(1.5, 400) + ROUND(1.5, -400)"), "1.5");
+        assert_eq!(excel("=IF(A1 <> 0, 100 / A1, 1 / 0)"), "25");
+        assert_eq!(excel("=if(a1 > 5, \"big\", \"small\")"), "small");
+        assert_eq!(excel("=\"a\"\"b\" & 1 + 1 = \"A\"\"B2\""), "TRUE");
+        assert_eq!(excel("=AND(TRUE, NOT(FALSE), 1 < rate)"), "FALSE");
+        assert_eq!(excel("=MAX(1, 2.5e1, -3) + MIN(4) + ABS(-1)"), "30");
+        assert_eq!(excel("=AVERAGE(1, 2)"), "1.5");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(excel("=1/0"), "#DIV/0! division by zero");
+        assert_eq!(excel("=1e309"), "#NUM! result out of range");
+        assert_eq!(excel("=SUM(1e308, 1e308) * 0"), "#NUM! result out of range");
+        assert_eq!(excel("=2^1024"), "#NUM! result out of range");
+        assert_eq!(excel("=B2 + 1"), "#NAME? unknown name 'B2'");
+        assert_eq!(excel("=VLOOKUP(1)"), "#NAME? unknown function 'VLOOKUP'");
+        assert_eq!(excel("=\"x\" * 2"), "#VALUE! \"x\" is not a number");
+        assert_eq!(excel("=IF(1)"), "#VALUE! wrong number of arguments to IF");
+        assert_eq!(excel("=(1 + 2"), "Syntax error: expected ')'");
+        assert_eq!(excel("=1 + 2)"), "Syntax error: unexpected ')'");
+        assert_eq!(excel("=1 +"), "Syntax error: unexpected end of formula");
+        assert_eq!(excel("=1 ! 2"), "Syntax error: unexpected '!'");
+    }
+}
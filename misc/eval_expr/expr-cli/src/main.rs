@@ -0,0 +1,773 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, IsTerminal},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use expr_core::{
+    format_programmer, format_value, parse, split_assignment, tokenize, Ast, Context, EvalOptions,
+    ExprError, Notation, NumberFormat, Precision, Value, ValueFormat, WarningKind, PRECISION_LIMIT,
+};
+
+mod bc;
+mod bench;
+mod convert;
+mod excel;
+mod posix;
+mod repl;
+mod roundtrip;
+mod run;
+mod simplify;
+mod terminal;
+mod tree;
+
+/// Evaluates integer arithmetic expressions such as `2 * (3 + 4)`.
+///
+/// With no subcommand the expression given is evaluated. With none given
+/// either, an interactive prompt starts if stdin is a terminal, and
+/// otherwise each line of stdin is evaluated as it arrives, e.g.
+/// `echo '2 * 3' | eval_expr`.
+#[derive(Parser)]
+#[command(name = "eval_expr", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    eval: EvalArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Evaluate expressions and print their values
+    Eval(EvalArgs),
+    /// Print the tokens of expressions, one per line with their spans
+    Tokenize(Input),
+    /// Draw the syntax trees of expressions without evaluating them
+    Parse {
+        /// Draw branches with ASCII instead of box-drawing characters
+        #[arg(long)]
+        ascii: bool,
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Print expressions with normalized spacing and parentheses
+    Fmt {
+        /// Break lines longer than this many columns
+        #[arg(long)]
+        width: Option<usize>,
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Start the interactive prompt
+    Repl {
+        /// Keep the history of this session only, without reading or
+        /// writing the history file
+        #[arg(long)]
+        no_history: bool,
+    },
+    /// Time each evaluation stage and compare against a saved baseline
+    Bench {
+        /// Compare each time with the one saved in PATH by `--save`
+        #[arg(long, value_name = "PATH")]
+        baseline: Option<String>,
+        /// Save the times to PATH as JSON
+        #[arg(long, value_name = "PATH")]
+        save: Option<String>,
+        /// Fail if any time is more than PERCENT slower than the baseline
+        #[arg(long, value_name = "PERCENT", default_value_t = 10.0)]
+        threshold: f64,
+    },
+    /// Rewrite expressions in another notation
+    Convert {
+        /// The notation the input is written in
+        #[arg(long, value_enum, default_value_t = convert::Source::Infix)]
+        from: convert::Source,
+        /// The notation to print
+        #[arg(long, value_enum)]
+        to: convert::Target,
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Print expressions in postfix notation, as `convert --to rpn` does
+    Rpn {
+        /// The notation the input is written in
+        #[arg(long, value_enum, default_value_t = convert::Source::Infix)]
+        from: convert::Source,
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Check that every line of a file re-parses to the same tree and value
+    Roundtrip {
+        /// The file of expressions, one per line
+        #[arg(value_name = "FILE")]
+        path: String,
+    },
+    /// Run a script of assignments, functions, conditionals and loops, or
+    /// with --batch evaluate each line of a file on its own
+    Run {
+        /// Evaluate each line on its own, reporting every failure without
+        /// stopping
+        #[arg(long)]
+        batch: bool,
+        /// How to print values and errors; with json, each is a JSON object
+        /// on its own line of stdout
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// The script, or with --batch the file of expressions
+        #[arg(value_name = "FILE")]
+        path: String,
+    },
+    /// Print expressions after applying algebraic identities
+    Simplify(Input),
+}
+
+/// Where the expressions to work on come from: the command line, `-e`, a
+/// file with one per line, or else stdin with one per line.
+#[derive(Args)]
+struct Input {
+    /// The expression; its words are joined with spaces. Options may follow
+    /// it; words after `--` are all part of it, e.g. `-- -x + 1`
+    #[arg(value_name = "EXPR", allow_negative_numbers = true)]
+    words: Vec<String>,
+
+    /// The expression, as a single argument
+    #[arg(short, long = "expr", value_name = "EXPR", conflicts_with = "words")]
+    expr: Option<String>,
+
+    /// Read one expression per line from PATH
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["words", "expr"])]
+    file: Option<String>,
+}
+
+impl Input {
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.expr.is_none() && self.file.is_none()
+    }
+
+    /// The input lines, read from stdin one at a time as they arrive, so
+    /// results can be printed as the lines are, e.g. from `tail -f`.
+    fn stream(&self) -> Result<Box<dyn Iterator<Item = Result<String, String>>>, String> {
+        if self.is_empty() {
+            let lines = io::stdin().lock().lines();
+            return Ok(Box::new(
+                lines.map(|line| line.map_err(|err| err.to_string())),
+            ));
+        }
+        Ok(Box::new(self.lines()?.into_iter().map(Ok)))
+    }
+
+    fn lines(&self) -> Result<Vec<String>, String> {
+        match &self.expr {
+            Some(expr) => Ok(vec![expr.clone()]),
+            None => {
+                let words = self.words.iter().map(String::as_str).collect::<Vec<_>>();
+                convert::read_inputs(&words, self.file.as_ref())
+            }
+        }
+    }
+}
+
+#[derive(Args)]
+struct EvalArgs {
+    #[command(flatten)]
+    input: Input,
+
+    /// How to print errors
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+
+    /// How to print results; with json, each value or error is a JSON
+    /// object on its own line of stdout
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, conflicts_with = "latex")]
+    format: OutputFormat,
+
+    /// Read the expression in postfix notation, e.g. `3 4 + 2 *`
+    #[arg(long, conflicts_with = "latex_input")]
+    rpn: bool,
+
+    /// Read the expression as LaTeX, e.g. `\frac{1}{2}`
+    #[arg(long)]
+    latex_input: bool,
+
+    /// Print the expression as LaTeX along with its value, or without it if
+    /// a `/` discarded a remainder, which `\frac` would not show
+    #[arg(long)]
+    latex: bool,
+
+    /// Evaluate the arguments as POSIX expr(1) does, each one an operand or
+    /// operator, e.g. `--posix 3 '*' 4`, with its exit statuses: 1 for a
+    /// null or 0 result and 2 for an invalid expression
+    #[arg(long, conflicts_with_all = ["expr", "file", "rpn", "latex_input", "latex", "format", "vars"])]
+    posix: bool,
+
+    /// Run the input as a bc program: statements separated by newlines or
+    /// `;`, assignments that print nothing, `.` or `last` for the last
+    /// value printed, and `scale`, which must stay 0
+    #[arg(long, conflicts_with_all = ["posix", "rpn", "latex_input", "latex", "format", "vars"])]
+    bc: bool,
+
+    /// Evaluate each input as a spreadsheet formula: a leading `=`, `^` for
+    /// powers, `<>` and the other comparisons, `&` to join text, and `SUM`,
+    /// `IF`, `ROUND` and similar functions in any case
+    #[arg(long, conflicts_with_all = ["posix", "bc", "rpn", "latex_input", "latex", "format"])]
+    excel: bool,
+
+    /// Bind NAME to VALUE, a number or a constant expression such as
+    /// `2^10`; may be repeated
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var)]
+    vars: Vec<(String, Value)>,
+
+    /// Read numbers as written in LOCALE, e.g. `de` for `1.234.567` or `fr`
+    /// for `1 234 567`
+    #[arg(long, value_name = "LOCALE", value_parser = parse_locale,
+          conflicts_with_all = ["posix", "bc", "excel", "rpn", "latex_input"])]
+    locale: Option<NumberFormat>,
+
+    /// Separate the digits of results into groups of three with SEP, `,`
+    /// if none is given, e.g. `109,876,463`, or `109 876 463` with `--group=" "`
+    #[arg(long, value_name = "SEP", num_args = 0..=1, require_equals = true, default_missing_value = ",",
+          conflicts_with_all = ["posix", "bc", "excel", "latex"])]
+    group: Option<char>,
+
+    /// Print results in BASE, 2, 8, 10 or 16, e.g. `0x100`; calling `hex`,
+    /// `bin` or `oct` does the same for one expression, e.g. `hex(255) + 1`
+    #[arg(long, value_name = "BASE", value_parser = parse_base,
+          conflicts_with_all = ["posix", "bc", "excel", "latex"])]
+    output_base: Option<u32>,
+
+    /// Print results in NOTATION: `fixed`, `sci` for `1.23456789e8` or
+    /// `eng` for `123.456789e6`, with an exponent that is a multiple of 3
+    #[arg(long, value_name = "NOTATION", value_parser = parse_notation,
+          conflicts_with_all = ["posix", "bc", "excel", "latex"])]
+    notation: Option<Notation>,
+
+    /// Print results with N decimal places, up to 64, e.g. `1234.00` or,
+    /// with `--notation sci`, `1.23e3`
+    #[arg(long, value_name = "N", value_parser = parse_decimals,
+          conflicts_with_all = ["posix", "bc", "excel", "latex"])]
+    precision: Option<usize>,
+
+    /// Print results rounded to N significant figures, from 1 to 64, e.g.
+    /// `123000` for 123456 with 3, or `1.23e5` with `--notation sci`
+    #[arg(long, value_name = "N", value_parser = parse_significant,
+          conflicts_with_all = ["posix", "bc", "excel", "latex", "precision"])]
+    significant: Option<usize>,
+
+    /// Print each result in decimal, hex, binary and octal, as the two's
+    /// complement bit pattern of a BITS-bit word: 8, 16 or 32 if none is given
+    #[arg(long, value_name = "BITS", value_parser = parse_word_size, num_args = 0..=1,
+          require_equals = true, default_missing_value = "32",
+          conflicts_with_all = ["posix", "bc", "excel", "latex", "format", "group", "output_base",
+                                "notation", "precision", "significant"])]
+    programmer: Option<u32>,
+}
+
+fn parse_word_size(bits: &str) -> Result<u32, String> {
+    match bits.parse() {
+        Ok(bits @ (8 | 16 | 32)) => Ok(bits),
+        _ => Err("expected 8, 16 or 32".into()),
+    }
+}
+
+fn parse_decimals(n: &str) -> Result<usize, String> {
+    parse_digits(n, 0)
+}
+
+fn parse_significant(n: &str) -> Result<usize, String> {
+    parse_digits(n, 1)
+}
+
+// Parses a count of digits from `min` to `PRECISION_LIMIT`.
+fn parse_digits(n: &str, min: usize) -> Result<usize, String> {
+    match n.parse() {
+        Ok(n) if (min..=PRECISION_LIMIT).contains(&n) => Ok(n),
+        _ => Err(format!("expected {} to {}", min, PRECISION_LIMIT)),
+    }
+}
+
+fn parse_notation(notation: &str) -> Result<Notation, String> {
+    match notation {
+        "fixed" => Ok(Notation::Fixed),
+        "sci" | "scientific" => Ok(Notation::Scientific),
+        "eng" | "engineering" => Ok(Notation::Engineering),
+        _ => Err("expected fixed, sci or eng".into()),
+    }
+}
+
+fn parse_base(base: &str) -> Result<u32, String> {
+    match base.parse() {
+        Ok(base @ (2 | 8 | 10 | 16)) => Ok(base),
+        _ => Err("expected 2, 8, 10 or 16".into()),
+    }
+}
+
+fn parse_locale(tag: &str) -> Result<NumberFormat, String> {
+    NumberFormat::locale(tag).ok_or_else(|| format!("unknown locale '{}'", tag))
+}
+
+// Parses a `--var` binding, `NAME=VALUE`.
+fn parse_var(binding: &str) -> Result<(String, Value), String> {
+    let (name, value) = split_assignment(binding)
+        .ok_or_else(|| format!("expected NAME=VALUE, found '{}'", binding))?;
+    let value = expr_core::eval(value).map_err(|err| format!("{}: {}", name, err))?;
+    Ok((name.to_string(), value))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Why a command failed, which decides the exit status: 2 if an expression
+/// did not parse, 3 if one failed to evaluate, as on division by zero, 64
+/// for bad arguments, and 1 otherwise, e.g. for an unreadable file.
+#[derive(Debug, PartialEq, Eq)]
+enum Failure {
+    /// An expression did not parse; the error has been reported.
+    Parse,
+    /// An expression failed to evaluate; the error has been reported.
+    Eval,
+    Usage(String),
+    Other(String),
+    /// A status the command chose, e.g. `expr`'s under `--posix`, after
+    /// reporting any error itself.
+    Status(i32),
+}
+
+impl Failure {
+    fn of(err: &ExprError) -> Failure {
+        if err.is_parse() {
+            Failure::Parse
+        } else {
+            Failure::Eval
+        }
+    }
+
+    fn status(&self) -> i32 {
+        match self {
+            Failure::Parse => 2,
+            Failure::Eval => 3,
+            Failure::Usage(_) => 64,
+            Failure::Other(_) => 1,
+            Failure::Status(status) => *status,
+        }
+    }
+}
+
+/// A subcommand's error message, e.g. for an unreadable file; clap has
+/// already rejected bad arguments.
+impl From<String> for Failure {
+    fn from(message: String) -> Failure {
+        Failure::Other(message)
+    }
+}
+
+fn main() {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // `--help` and `--version` are not errors.
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => {
+            let _ = err.print();
+            std::process::exit(Failure::Usage(String::new()).status());
+        }
+    };
+    let result = match cli.command {
+        None if cli.eval.input.is_empty()
+            && !cli.eval.posix
+            && !cli.eval.bc
+            && !cli.eval.excel
+            && io::stdin().is_terminal() =>
+        {
+            start_repl(true)
+        }
+        None => eval(&cli.eval),
+        Some(Command::Eval(args)) => eval(&args),
+        Some(Command::Tokenize(input)) => tokens(&input),
+        Some(Command::Parse { ascii, input }) => input
+            .lines()
+            .and_then(|lines| tree::run(ascii, &lines))
+            .map_err(Failure::from),
+        Some(Command::Fmt { width, input }) => fmt(width, &input),
+        Some(Command::Repl { no_history }) => start_repl(!no_history),
+        Some(Command::Bench {
+            baseline,
+            save,
+            threshold,
+        }) => bench::run(baseline.as_ref(), save.as_ref(), threshold).map_err(Failure::from),
+        Some(Command::Convert { from, to, input }) => input
+            .lines()
+            .and_then(|lines| convert::run(from, to, &lines))
+            .map_err(Failure::from),
+        Some(Command::Rpn { from, input }) => input
+            .lines()
+            .and_then(|lines| convert::run(from, convert::Target::Rpn, &lines))
+            .map_err(Failure::from),
+        Some(Command::Roundtrip { path }) => roundtrip::run(&path).map_err(Failure::from),
+        Some(Command::Run {
+            batch,
+            format,
+            path,
+        }) => run::run(&path, batch, format == OutputFormat::Json),
+        Some(Command::Simplify(input)) => input
+            .lines()
+            .and_then(|lines| simplify::run(&lines))
+            .map_err(Failure::from),
+    };
+    if let Err(failure) = result {
+        if let Failure::Usage(message) | Failure::Other(message) = &failure {
+            eprintln!("{}", message);
+        }
+        std::process::exit(failure.status());
+    }
+}
+
+fn start_repl(history: bool) -> Result<(), Failure> {
+    let stdin = io::stdin();
+    let result = if stdin.is_terminal() {
+        let history = history.then(terminal::history_path).flatten();
+        terminal::Terminal::new(history).and_then(|terminal| repl::run(terminal, io::stdout()))
+    } else {
+        repl::run(repl::Piped(stdin.lock()), io::stdout())
+    };
+    result.map_err(|err| Failure::Other(err.to_string()))
+}
+
+// Evaluates each input expression, printing its value or error, and fails
+// as the first expression that failed did.
+fn eval(args: &EvalArgs) -> Result<(), Failure> {
+    if args.posix {
+        return posix(&args.input.words);
+    }
+    if args.bc {
+        return bc::run(args.input.stream()?);
+    }
+    if args.excel {
+        return excel(args);
+    }
+
+    let read = if args.rpn {
+        Some(Ast::from_rpn as fn(&str) -> expr_core::Result<Ast>)
+    } else if args.latex_input {
+        Some(Ast::from_latex as fn(&str) -> expr_core::Result<Ast>)
+    } else {
+        None
+    };
+
+    let mut format = ValueFormat::default();
+    if let Some(separator) = args.group {
+        format = format.grouping(separator);
+    }
+    if let Some(radix) = args.output_base {
+        format = format.radix(radix);
+    }
+    if let Some(notation) = args.notation {
+        format = format.notation(notation);
+    }
+    if let Some(decimals) = args.precision {
+        format = format.precision(Precision::Decimals(decimals));
+    }
+    if let Some(significant) = args.significant {
+        format = format.precision(Precision::Significant(significant));
+    }
+    let mut options = EvalOptions::default();
+    if let Some(format) = args.locale {
+        options = options.number_format(format);
+    }
+    let mut context = Context::new();
+    for (name, value) in &args.vars {
+        context.set(name.as_str(), *value);
+    }
+
+    let mut failure = None;
+    for src in args.input.stream()? {
+        let src = src?;
+        if src.trim().is_empty() {
+            continue;
+        }
+        let mut format = format.clone();
+        let Evaluated {
+            src,
+            result,
+            radix,
+            truncated,
+        } = evaluate(src, read, &options, &context);
+        if let Some(radix) = radix {
+            format = format.radix(radix);
+        }
+        match result {
+            // `\frac` reads as exact division, so the equation would be
+            // false with the value of a truncating one.
+            Ok(value) if args.latex => match parse(&src) {
+                Ok(ast) if truncated => println!("{}", ast.to_latex()),
+                Ok(ast) => println!("{} = {}", ast.to_latex(), value),
+                Err(_) => println!("{}", value),
+            },
+            Ok(value) if args.format == OutputFormat::Json => println!("{}", value.to_json()),
+            Ok(value) => match (value, args.programmer) {
+                (Value::Int(n), Some(bits)) => println!("{}", format_programmer(n, bits)),
+                (value, _) => println!("{}", format_value(&value, &format)),
+            },
+            Err(err) => {
+                match args.error_format {
+                    _ if args.format == OutputFormat::Json => println!("{}", err.to_json()),
+                    ErrorFormat::Json => eprintln!("{}", err.to_json()),
+                    ErrorFormat::Human => eprintln!("{}", err.render(&src)),
+                }
+                failure.get_or_insert(Failure::of(&err));
+            }
+        }
+    }
+
+    failure.map_or(Ok(()), Err)
+}
+
+/// An input line evaluated by [`evaluate`].
+#[derive(Debug, PartialEq)]
+struct Evaluated {
+    /// The infix evaluated, or the input if it could not be read.
+    src: String,
+    result: expr_core::Result<Value>,
+    /// The base a `hex`, `bin` or `oct` call asked for.
+    radix: Option<u32>,
+    /// Whether a `/` discarded a remainder.
+    truncated: bool,
+}
+
+/// Evaluates `src`, read with `read` if given, printing any warnings.
+///
+/// Postfix and LaTeX input is evaluated as the infix it reads as, so that
+/// `options`, warnings and the builtins apply to it as to any other; errors
+/// then point into that infix, other than those reading `src`.
+fn evaluate(
+    src: String,
+    read: Option<fn(&str) -> expr_core::Result<Ast>>,
+    options: &EvalOptions,
+    context: &Context,
+) -> Evaluated {
+    let infix = match read.map(|read| read(&src)) {
+        Some(Ok(ast)) => ast.to_string(),
+        Some(Err(err)) => {
+            return Evaluated {
+                src,
+                result: Err(err),
+                radix: None,
+                truncated: false,
+            }
+        }
+        None => src,
+    };
+    let mut expr = expr_core::Expr::with_options(&infix, options.clone()).with_context(context);
+    let result = expr.eval();
+    for warning in expr.warnings() {
+        eprintln!("{}", warning.render(&infix));
+    }
+    let radix = expr.radix();
+    let truncated = expr
+        .warnings()
+        .iter()
+        .any(|warning| warning.kind() == WarningKind::InexactDivision);
+    Evaluated {
+        src: infix,
+        result,
+        radix,
+        truncated,
+    }
+}
+
+// Prints the value of `expr` arguments as `expr` does, failing with its
+// exit statuses.
+fn posix(args: &[String]) -> Result<(), Failure> {
+    match posix::eval(args) {
+        Ok(value) => {
+            println!("{}", value);
+            if posix::is_null(&value) {
+                return Err(Failure::Status(1));
+            }
+            Ok(())
+        }
+        Err(err) => {
+            eprintln!("eval_expr: {}", err);
+            Err(Failure::Status(2))
+        }
+    }
+}
+
+// Prints the value of each input formula, or its error, and fails as the
+// first formula that failed did.
+fn excel(args: &EvalArgs) -> Result<(), Failure> {
+    let mut vars = HashMap::new();
+    for (name, value) in &args.vars {
+        match value {
+            Value::Int(n) => vars.insert(name.clone(), f64::from(*n)),
+            _ => return Err(Failure::Usage(format!("{}: formulas take numbers", name))),
+        };
+    }
+
+    let mut failure = None;
+    for src in args.input.stream()? {
+        let src = src?;
+        if src.trim().is_empty() {
+            continue;
+        }
+        match excel::eval(&src, &vars) {
+            Ok(value) => println!("{}", value),
+            Err(err) => {
+                eprintln!("{}", err);
+                failure.get_or_insert(match err {
+                    excel::Error::Syntax(_) => Failure::Parse,
+                    excel::Error::Value(..) => Failure::Eval,
+                });
+            }
+        }
+    }
+    failure.map_or(Ok(()), Err)
+}
+
+// Prints each token of each input as `START..END KIND TEXT`.
+fn tokens(input: &Input) -> Result<(), Failure> {
+    for src in input.lines()? {
+        for (span, kind) in tokenize(&src) {
+            println!(
+                "{}..{}\t{}\t{}",
+                span.start,
+                span.end,
+                kind,
+                &src[span.start..span.end]
+            );
+        }
+    }
+    Ok(())
+}
+
+// Prints each input as `Ast`'s `Display` does, wrapped to `width` if given.
+fn fmt(width: Option<usize>, input: &Input) -> Result<(), Failure> {
+    let mut failed = false;
+    for src in input.lines()?.iter().filter(|src| !src.trim().is_empty()) {
+        match parse(src) {
+            Ok(ast) => match width {
+                Some(width) => println!("{}", ast.to_wrapped_string(width)),
+                None => println!("{}", ast),
+            },
+            Err(err) => {
+                eprintln!("{}", err.render(src));
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return Err(Failure::Parse);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_status() {
+        let status = |src| Failure::of(&expr_core::eval(src).unwrap_err()).status();
+        assert_eq!(status("2 * (3"), 2);
+        assert_eq!(status("1 / 0"), 3);
+        assert_eq!(status("2147483647 + 1"), 3);
+        assert_eq!(Failure::from("x.txt: not found".to_string()).status(), 1);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let context = Context::new();
+        let options = EvalOptions::default().modulus(7);
+        let rpn = Some(Ast::from_rpn as fn(&str) -> expr_core::Result<Ast>);
+        assert_eq!(
+            evaluate("3 4 * hex/1".into(), rpn, &options, &context),
+            Evaluated {
+                src: "hex(3 * 4)".into(),
+                result: Ok(Value::Int(5)),
+                radix: Some(16),
+                truncated: false,
+            }
+        );
+        let latex = Some(Ast::from_latex as fn(&str) -> expr_core::Result<Ast>);
+        assert_eq!(
+            evaluate("\\frac{3}{2}".into(), latex, &options, &context),
+            evaluate("3 / 2".into(), None, &options, &context)
+        );
+        let evaluated = evaluate("3 +".into(), rpn, &options, &context);
+        assert_eq!(evaluated.src, "3 +");
+        assert!(evaluated.result.unwrap_err().is_parse());
+
+        let options = EvalOptions::default();
+        assert!(evaluate("1 / 2 + 3 ^ 2".into(), None, &options, &context).truncated);
+        assert!(!evaluate("4 / 2 + 7 // 2".into(), None, &options, &context).truncated);
+    }
+
+    #[test]
+    fn test_parse_var() {
+        assert_eq!(parse_var("rate=8"), Ok(("rate".into(), Value::Int(8))));
+        assert_eq!(parse_var(" x = 2^10 "), Ok(("x".into(), Value::Int(1024))));
+        assert_eq!(parse_var("n=-3"), Ok(("n".into(), Value::Int(-3))));
+        assert_eq!(
+            parse_var("rate"),
+            Err("expected NAME=VALUE, found 'rate'".into())
+        );
+        assert_eq!(parse_var("y=x"), Err("y: Unknown variable: x".into()));
+        assert_eq!(parse_decimals("0"), Ok(0));
+        assert_eq!(
+            parse_decimals("100000000000"),
+            Err("expected 0 to 64".into())
+        );
+        assert_eq!(parse_significant("64"), Ok(64));
+        assert_eq!(parse_significant("0"), Err("expected 1 to 64".into()));
+    }
+
+    #[test]
+    fn test_options_after_expr() {
+        let cli =
+            Cli::try_parse_from(["eval_expr", "x * 2", "--var", "x=3", "--output-base", "16"]);
+        let EvalArgs {
+            input,
+            vars,
+            output_base,
+            ..
+        } = cli.unwrap().eval;
+        assert_eq!(input.words, ["x * 2"]);
+        assert_eq!(vars, [("x".into(), Value::Int(3))]);
+        assert_eq!(output_base, Some(16));
+
+        let cli = Cli::try_parse_from(["eval_expr", "tokenize", "x", "--file", "in.txt"]);
+        assert!(cli.is_err(), "EXPR conflicts with --file");
+        let cli = Cli::try_parse_from(["eval_expr", "-3", "*", "2", "--", "-x"]).unwrap();
+        assert_eq!(cli.eval.input.words, ["-3", "*", "2", "-x"]);
+
+        let cli = Cli::try_parse_from(["eval_expr", "convert", "1 + 2", "--to", "prefix"]);
+        let Some(Command::Convert { from, to, input }) = cli.unwrap().command else {
+            panic!("expected convert");
+        };
+        assert_eq!(
+            (from, to),
+            (convert::Source::Infix, convert::Target::Prefix)
+        );
+        assert_eq!(input.words, ["1 + 2"]);
+        assert!(
+            Cli::try_parse_from(["eval_expr", "convert", "1"]).is_err(),
+            "--to is required"
+        );
+        assert!(Cli::try_parse_from(["eval_expr", "bench", "--threshold", "x"]).is_err());
+        assert!(Cli::try_parse_from(["eval_expr", "run", "--format", "xml", "a.calc"]).is_err());
+        let cli = Cli::try_parse_from(["eval_expr", "run", "a.calc", "--batch"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Run { batch: true, .. })
+        ));
+    }
+}
@@ -0,0 +1,324 @@
+use std::cmp::Ordering;
+
+use regex::Regex;
+
+/// Evaluates `args` as POSIX `expr(1)` does, each argument being one
+/// operand or operator, and returns the result as `expr` would print it.
+///
+/// From lowest to highest precedence, all left-associative:
+///
+/// ```text
+/// a | b     a if it is neither null nor 0, else b if it is not null, else 0
+/// a & b     a if neither is null nor 0, else 0
+/// a = b     1 if true, else 0, also for != < <= > >=; integers compare
+///           as numbers and anything else as strings
+/// a + b     integer arithmetic, also for -, and for * / % a level up
+/// a : re    anchored match against a basic regular expression: the text
+///           of the first \( \) group, or the length of the match
+/// ( a )     grouping
+/// ```
+///
+/// Values are strings; an integer is an optional `-` followed by digits.
+pub fn eval(args: &[String]) -> Result<String, String> {
+    let mut parser = Parser { args, pos: 0 };
+    let value = parser.or()?;
+    match args.get(parser.pos) {
+        Some(arg) => Err(format!("syntax error: unexpected argument '{}'", arg)),
+        None => Ok(value),
+    }
+}
+
+/// Whether `value` is null or zero, which `expr` reports with exit status 1.
+pub fn is_null(value: &str) -> bool {
+    value.is_empty() || integer(value) == Some(0)
+}
+
+struct Parser<'a> {
+    args: &'a [String],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    // The next argument if it is one of `ops`, consuming it.
+    fn op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        let arg = self.args.get(self.pos)?;
+        let op = ops.iter().find(|op| **op == arg)?;
+        self.pos += 1;
+        Some(op)
+    }
+
+    fn or(&mut self) -> Result<String, String> {
+        let mut lhs = self.and()?;
+        while self.op(&["|"]).is_some() {
+            let rhs = self.and()?;
+            lhs = if !is_null(&lhs) {
+                lhs
+            } else if !rhs.is_empty() {
+                rhs
+            } else {
+                "0".into()
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<String, String> {
+        let mut lhs = self.comparison()?;
+        while self.op(&["&"]).is_some() {
+            let rhs = self.comparison()?;
+            if is_null(&lhs) || is_null(&rhs) {
+                lhs = "0".into();
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn comparison(&mut self) -> Result<String, String> {
+        let mut lhs = self.sum()?;
+        while let Some(op) = self.op(&["=", "!=", "<", "<=", ">", ">="]) {
+            let rhs = self.sum()?;
+            let ordering = match (integer(&lhs), integer(&rhs)) {
+                (Some(l), Some(r)) => l.cmp(&r),
+                _ => lhs.cmp(&rhs),
+            };
+            let holds = match op {
+                "=" => ordering == Ordering::Equal,
+                "!=" => ordering != Ordering::Equal,
+                "<" => ordering == Ordering::Less,
+                "<=" => ordering != Ordering::Greater,
+                ">" => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            };
+            lhs = (holds as i64).to_string();
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> Result<String, String> {
+        let mut lhs = self.product()?;
+        while let Some(op) = self.op(&["+", "-"]) {
+            let rhs = self.product()?;
+            lhs = arithmetic(&lhs, op, &rhs)?;
+        }
+        Ok(lhs)
+    }
+
+    fn product(&mut self) -> Result<String, String> {
+        let mut lhs = self.matching()?;
+        while let Some(op) = self.op(&["*", "/", "%"]) {
+            let rhs = self.matching()?;
+            lhs = arithmetic(&lhs, op, &rhs)?;
+        }
+        Ok(lhs)
+    }
+
+    fn matching(&mut self) -> Result<String, String> {
+        let mut lhs = self.primary()?;
+        while self.op(&[":"]).is_some() {
+            let pattern = self.primary()?;
+            lhs = match_bre(&lhs, &pattern)?;
+        }
+        Ok(lhs)
+    }
+
+    fn primary(&mut self) -> Result<String, String> {
+        let Some(arg) = self.args.get(self.pos) else {
+            return Err("syntax error: missing argument".into());
+        };
+        self.pos += 1;
+        if arg != "(" {
+            return Ok(arg.clone());
+        }
+        let value = self.or()?;
+        match self.op(&[")"]) {
+            Some(_) => Ok(value),
+            None => Err("syntax error: expected ')'".into()),
+        }
+    }
+}
+
+// `value` as an integer, if it is one: digits after an optional `-`.
+fn integer(value: &str) -> Option<i64> {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+fn arithmetic(lhs: &str, op: &str, rhs: &str) -> Result<String, String> {
+    let (Some(l), Some(r)) = (integer(lhs), integer(rhs)) else {
+        return Err("non-integer argument".into());
+    };
+    let result = match op {
+        "+" => l.checked_add(r),
+        "-" => l.checked_sub(r),
+        "*" => l.checked_mul(r),
+        _ if r == 0 => return Err("division by zero".into()),
+        "/" => l.checked_div(r),
+        _ => l.checked_rem(r),
+    };
+    result
+        .map(|n| n.to_string())
+        .ok_or_else(|| "integer overflow".into())
+}
+
+// `text : pattern`, the pattern being anchored at the start of `text`.
+fn match_bre(text: &str, pattern: &str) -> Result<String, String> {
+    let regex = Regex::new(&format!("^(?:{})", bre_to_regex(pattern)?))
+        .map_err(|err| format!("invalid regular expression '{}': {}", pattern, err))?;
+    let captures = regex.captures(text);
+    if regex.captures_len() > 1 {
+        let group = captures.and_then(|captures| captures.get(1));
+        return Ok(group.map_or("", |group| group.as_str()).to_string());
+    }
+    let len = captures.map_or(0, |captures| captures[0].chars().count());
+    Ok(len.to_string())
+}
+
+/// Rewrites a POSIX basic regular expression in the `regex` crate's syntax:
+/// `\(`, `\)`, `\{` and `\}` lose their backslashes, and the characters
+/// those give meaning to without one are escaped instead. A leading `*`,
+/// and a `^` or `$` other than at the start or end, are literal.
+fn bre_to_regex(bre: &str) -> Result<String, String> {
+    let chars = bre.chars().collect::<Vec<_>>();
+    let mut out = String::new();
+    let mut i = 0;
+    // Whether a `*` here would have nothing to repeat.
+    let mut at_start = true;
+
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            '\\' => {
+                let Some(&next) = chars.get(i) else {
+                    return Err("trailing backslash in regular expression".into());
+                };
+                i += 1;
+                match next {
+                    '(' | ')' | '{' | '}' => out.push(next),
+                    '1'..='9' => return Err("back-references are not supported".into()),
+                    _ => out.push_str(&regex::escape(&next.to_string())),
+                }
+                at_start = next == '(';
+                continue;
+            }
+            '[' => {
+                let end = bracket_end(&chars, i)
+                    .ok_or_else(|| "unclosed '[' in regular expression".to_string())?;
+                out.push('[');
+                let mut j = i;
+                if chars.get(j) == Some(&'^') {
+                    out.push('^');
+                    j += 1;
+                }
+                while j < end {
+                    match chars[j] {
+                        '[' if chars.get(j + 1) == Some(&':') => {
+                            let close = (j + 2..end)
+                                .find(|&k| chars[k] == ':' && chars.get(k + 1) == Some(&']'))
+                                .unwrap_or(end - 1);
+                            out.extend(&chars[j..close + 2]);
+                            j = close + 2;
+                            continue;
+                        }
+                        c @ ('\\' | '[' | ']' | '&' | '~') => {
+                            out.push('\\');
+                            out.push(c);
+                        }
+                        c => out.push(c),
+                    }
+                    j += 1;
+                }
+                out.push(']');
+                i = end + 1;
+            }
+            '*' if at_start => out.push_str("\\*"),
+            '^' if out.is_empty() => out.push('^'),
+            '$' if i == chars.len() => out.push('$'),
+            '.' | '*' => out.push(c),
+            _ => out.push_str(&regex::escape(&c.to_string())),
+        }
+        at_start = false;
+    }
+
+    Ok(out)
+}
+
+// The index of the `]` closing a bracket expression whose contents start at
+// `start`, where a `]` first, or after `^`, is part of the set.
+fn bracket_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start;
+    if chars.get(i) == Some(&'^') {
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+    while i < chars.len() {
+        match chars[i] {
+            ']' => return Some(i),
+            '[' if chars.get(i + 1) == Some(&':') => {
+                i = (i + 2..chars.len().saturating_sub(1))
+                    .find(|&k| chars[k] == ':' && chars[k + 1] == ']')?
+                    + 2;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(args: &str) -> Result<String, String> {
+        eval(&args.split(' ').map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(expr("1 + 2 * 3"), Ok("7".into()));
+        assert_eq!(expr("( 1 + 2 ) * 3"), Ok("9".into()));
+        assert_eq!(expr("-7 / 2"), Ok("-3".into()));
+        assert_eq!(expr("-7 % 2"), Ok("-1".into()));
+        assert_eq!(expr("10 < 9"), Ok("0".into()));
+        assert_eq!(expr("abc < b"), Ok("1".into()));
+        assert_eq!(expr("007 = 7"), Ok("1".into()));
+        assert_eq!(expr("007"), Ok("007".into()));
+        assert_eq!(expr("0 | "), Ok("0".into()));
+        assert_eq!(expr("0 | x"), Ok("x".into()));
+        assert_eq!(expr("y | x"), Ok("y".into()));
+        assert_eq!(expr("3 & 0"), Ok("0".into()));
+        assert_eq!(expr("3 & 4"), Ok("3".into()));
+        assert_eq!(expr("abc + 1"), Err("non-integer argument".into()));
+        assert_eq!(expr("1 / 0"), Err("division by zero".into()));
+        assert_eq!(expr("1 +"), Err("syntax error: missing argument".into()));
+        assert_eq!(
+            expr("1 2"),
+            Err("syntax error: unexpected argument '2'".into())
+        );
+        assert_eq!(expr("( 1"), Err("syntax error: expected ')'".into()));
+        assert!(is_null("") && is_null("-00") && !is_null("x") && !is_null("1"));
+    }
+
+    #[test]
+    fn test_match() {
+        assert_eq!(expr("abcdef : abc"), Ok("3".into()));
+        assert_eq!(expr("abcdef : bc"), Ok("0".into()));
+        assert_eq!(expr("abcdef : a.*e"), Ok("5".into()));
+        assert_eq!(expr("file.tar.gz : \\(.*\\)\\.gz"), Ok("file.tar".into()));
+        assert_eq!(expr("file.txt : \\(.*\\)\\.gz"), Ok("".into()));
+        assert_eq!(expr("a+b : a+"), Ok("2".into()));
+        assert_eq!(expr("aaa : a\\{2\\}"), Ok("2".into()));
+        assert_eq!(expr("x1y : [[:alpha:]][0-9]"), Ok("2".into()));
+        assert_eq!(expr("*ab : *a"), Ok("2".into()));
+        assert_eq!(expr("]x : []]"), Ok("1".into()));
+        assert_eq!(
+            expr("aa : \\(a\\)\\1"),
+            Err("back-references are not supported".into())
+        );
+    }
+}
@@ -0,0 +1,853 @@
+use std::{
+    io::{self, BufRead, Write},
+    time::Instant,
+};
+
+use expr_core::{
+    format_fraction, format_value, is_complete, parse, split_assignment, tokenize, Context,
+    EvalOptions, Expr, ExprError, Function, Value, ValueFormat, WarningKind,
+};
+
+const PROMPT: &str = ">> ";
+const PASTE_PROMPT: &str = ".. ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+/// Reads statements line by line from `lines` and writes each result to
+/// `output`. Errors go to stderr so a bad line never ends the session.
+///
+/// A statement is an expression, an assignment `name = expr`, or a function
+/// definition `name(x, y) = expr`. Assigned variables and defined functions
+/// persist for the rest of the session, and `:undo`/`:redo` step back and
+/// forth through the assignments and definitions made so far.
+///
+/// Every result is kept: `ans` is the latest, and `_1`, `_2`, … the first,
+/// second and so on, e.g. `ans * 2` or `_1 + _3`. A variable of the same
+/// name is hidden by these.
+///
+/// `:paste` collects lines until `:end` (or end of input) and evaluates them
+/// as one block: either every line succeeds and all results are printed, or
+/// the first failure is reported and nothing is printed or assigned.
+///
+/// `:time` re-evaluates the last expression and reports how long it took;
+/// `:profile` also lists how many times each operator was applied.
+/// `:trace` re-evaluates it listing the value of every variable read and
+/// function called along the way. `:frac` toggles printing results as
+/// exact fractions, `7 / 3` as `7/3`, and `:frac mixed` as mixed numbers,
+/// `2 1/3`. The results kept for `ans` are still the integers, so the exact
+/// value is labelled with the one kept: `7/3 (exact, kept as 2)`.
+/// `:help` lists these and the other commands, such as `:vars` and `:quit`.
+pub fn run(mut lines: impl Lines, mut output: impl Write) -> io::Result<()> {
+    let interactive = lines.interactive();
+    let mut fractions: Option<ValueFormat> = None;
+    let mut context = Context::new();
+    let mut journal = Journal::default();
+    let mut last: Option<String> = None;
+    let mut results = Vec::new();
+
+    loop {
+        let scope = with_results(&context, &results);
+        lines.update(&scope);
+        let Some(line) = lines.read_line(PROMPT)? else {
+            break;
+        };
+        let mut src = line.trim().to_string();
+        if src.is_empty() {
+            continue;
+        }
+        if !src.starts_with(':') {
+            if interactive {
+                continue_statement(&mut src, &mut lines)?;
+            }
+            let src = src.as_str();
+            if let Some((name, params, body)) = split_definition(src) {
+                let defined = Function::new(&params, body)
+                    .and_then(|function| journal.define(&mut context, name, function));
+                match defined {
+                    Ok(()) => {
+                        let function = context.function(name).expect("just defined");
+                        writeln!(output, "{}", definition(name, function))?;
+                    }
+                    Err(err) => lines.report(&err.render(body)),
+                }
+                continue;
+            }
+            let expr = split_assignment(src).map_or(src, |(_, expr)| expr);
+            match exec(src, &scope, fractions.as_ref()) {
+                Ok(outcome) => {
+                    outcome.apply(&mut context, &mut journal);
+                    results.push(outcome.value());
+                    writeln!(output, "{}", outcome)?;
+                }
+                // Spans are relative to the expression, not the assignment.
+                Err(err) => lines.report(&err.render(expr)),
+            }
+            last = Some(expr.to_string());
+            continue;
+        }
+
+        let command = match Command::parse(&src) {
+            Ok(command) => command,
+            Err(err) => {
+                lines.report(&err.to_string());
+                continue;
+            }
+        };
+        match command {
+            Command::Help => write!(output, "{}", HELP)?,
+            Command::Quit => break,
+            Command::Paste => {
+                if interactive {
+                    writeln!(output, "// Paste mode, finish with :end or Ctrl-D")?;
+                }
+
+                let mut block = Vec::new();
+                while let Some(line) = lines.read_line(PASTE_PROMPT)? {
+                    if line.trim() == ":end" {
+                        break;
+                    }
+                    block.push(line);
+                }
+
+                match exec_block(&block, &context, &results, fractions.as_ref()) {
+                    Ok(statements) => {
+                        for statement in statements {
+                            match statement {
+                                Statement::Eval(outcome) => {
+                                    outcome.apply(&mut context, &mut journal);
+                                    results.push(outcome.value());
+                                    writeln!(output, "{}", outcome)?;
+                                }
+                                Statement::Define(name, function) => {
+                                    writeln!(output, "{}", definition(&name, &function))?;
+                                    journal
+                                        .define(&mut context, &name, function)
+                                        .expect("defined in the block");
+                                }
+                            }
+                        }
+                    }
+                    Err((line, err)) => lines.report(&format!("line {}: {}", line, err)),
+                }
+            }
+            Command::Vars => {
+                let mut vars = context.vars().collect::<Vec<_>>();
+                vars.sort_by_key(|(name, _)| *name);
+                for (name, value) in vars {
+                    writeln!(output, "{} = {}", name, value)?;
+                }
+            }
+            Command::Funcs => {
+                let mut functions = context.functions().collect::<Vec<_>>();
+                functions.sort_by_key(|(name, _)| *name);
+                for (name, function) in functions {
+                    writeln!(output, "{}", definition(name, function))?;
+                }
+            }
+            Command::Clear => {
+                context = Context::new();
+                journal = Journal::default();
+                last = None;
+                results.clear();
+            }
+            Command::Frac(mixed) => {
+                fractions = match (&fractions, mixed) {
+                    (Some(_), None) => None,
+                    (_, Some(true)) => Some(ValueFormat::default().mixed_fractions()),
+                    _ => Some(ValueFormat::default()),
+                };
+                let state = match (&fractions, mixed) {
+                    (None, _) => "off",
+                    (Some(_), Some(true)) => "mixed",
+                    (Some(_), _) => "on",
+                };
+                writeln!(output, "// Fractions {}", state)?;
+            }
+            Command::Mode(None) => writeln!(output, "int")?,
+            Command::Mode(Some(Mode::Int)) => {}
+            Command::Mode(Some(Mode::Float)) => {
+                lines.report("Float mode is not available: values are 32-bit integers")
+            }
+            Command::Undo | Command::Redo => {
+                let (change, verb) = if command == Command::Undo {
+                    (journal.undo(&mut context), "undo")
+                } else {
+                    (journal.redo(&mut context), "redo")
+                };
+                match change {
+                    Some(Change::Var { name, .. }) => match context.get(name) {
+                        Some(value) => writeln!(output, "{} = {}", name, value)?,
+                        None => writeln!(output, "{} is unset", name)?,
+                    },
+                    Some(Change::Function { name, .. }) => match context.function(name) {
+                        Some(function) => writeln!(output, "{}", definition(name, function))?,
+                        None => writeln!(output, "{}() is undefined", name)?,
+                    },
+                    None => lines.report(&format!("Nothing to {}", verb)),
+                }
+            }
+            Command::Time | Command::Profile => match &last {
+                Some(src) => profile(src, &scope, command == Command::Profile, &mut output)?,
+                None => lines.report("No expression to time"),
+            },
+            Command::Trace => match &last {
+                Some(src) => trace(src, &scope, &mut output)?,
+                None => lines.report("No expression to trace"),
+            },
+        }
+    }
+
+    Ok(())
+}
+
+const HELP: &str = "\
+Enter an expression to evaluate it, `name = expr` to assign a variable, or
+`name(x, y) = expr` to define a function.
+
+:help              show this message
+:vars              list the variables assigned
+:funcs             list the functions defined
+:clear             forget every variable and function
+:mode [int|float]  show or set the number mode
+:frac [mixed]      toggle printing results as fractions, e.g. 7/3 or 2 1/3
+:undo, :redo       step back and forth through assignments and definitions
+:paste             evaluate the lines up to :end as one block
+:time, :profile    re-evaluate the last expression and time it
+:trace             re-evaluate the last expression listing what it read
+:quit              end the session, as Ctrl-D does
+";
+
+/// The names of the commands, for completion.
+pub const COMMANDS: &[&str] = &[
+    ":help", ":vars", ":funcs", ":clear", ":mode", ":frac", ":undo", ":redo", ":paste", ":end",
+    ":time", ":profile", ":trace", ":quit",
+];
+
+/// A line starting with `:`, which controls the session rather than being
+/// evaluated.
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Help,
+    Vars,
+    Funcs,
+    Clear,
+    Mode(Option<Mode>),
+    // Whether to print mixed numbers, if given.
+    Frac(Option<bool>),
+    Undo,
+    Redo,
+    Paste,
+    Time,
+    Profile,
+    Trace,
+    Quit,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Mode {
+    Int,
+    Float,
+}
+
+impl Command {
+    /// Parses `line`, a trimmed line starting with `:`, as a command name
+    /// and its arguments separated by whitespace.
+    fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<_>>();
+
+        let command = match name {
+            ":help" | ":h" | ":?" => Command::Help,
+            ":vars" => Command::Vars,
+            ":funcs" => Command::Funcs,
+            ":clear" => Command::Clear,
+            ":mode" => {
+                return match args[..] {
+                    [] => Ok(Command::Mode(None)),
+                    ["int"] => Ok(Command::Mode(Some(Mode::Int))),
+                    ["float"] => Ok(Command::Mode(Some(Mode::Float))),
+                    _ => Err("Usage: :mode [int|float]".into()),
+                }
+            }
+            ":frac" => {
+                return match args[..] {
+                    [] => Ok(Command::Frac(None)),
+                    ["mixed"] => Ok(Command::Frac(Some(true))),
+                    ["improper"] => Ok(Command::Frac(Some(false))),
+                    _ => Err("Usage: :frac [mixed|improper]".into()),
+                }
+            }
+            ":undo" => Command::Undo,
+            ":redo" => Command::Redo,
+            ":paste" => Command::Paste,
+            ":time" => Command::Time,
+            ":profile" => Command::Profile,
+            ":trace" => Command::Trace,
+            ":quit" | ":q" | ":exit" => Command::Quit,
+            _ => return Err(format!("Unknown command: {} (try :help)", name)),
+        };
+        match args.first() {
+            Some(arg) => Err(format!("{} takes no arguments, found '{}'", name, arg)),
+            None => Ok(command),
+        }
+    }
+}
+
+/// Reads more lines onto `src` while it is an unfinished expression, such
+/// as one ending with an operator or an open parenthesis. A blank line, or
+/// the end of input, stops early and leaves the error to be reported.
+fn continue_statement(src: &mut String, lines: &mut impl Lines) -> io::Result<()> {
+    let expr = |src: &str| match split_definition(src) {
+        Some((_, _, body)) => body.len(),
+        None => split_assignment(src).map_or(src.len(), |(_, expr)| expr.len()),
+    };
+    while !is_complete(&src[src.len() - expr(src)..]) {
+        match lines.read_line(CONTINUATION_PROMPT)? {
+            Some(line) if !line.trim().is_empty() => {
+                src.push(' ');
+                src.push_str(line.trim());
+            }
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+/// Splits a function definition `name(params) = body` into its parts, or
+/// returns `None` if `src` is not one.
+fn split_definition(src: &str) -> Option<(&str, Vec<&str>, &str)> {
+    let mut tokens = tokenize(src).into_iter().map(|(span, kind)| (kind, span));
+    let (("identifier", name), ("(", _)) = (tokens.next()?, tokens.next()?) else {
+        return None;
+    };
+    let mut params = Vec::new();
+    let mut next = tokens.next()?;
+    if next.0 != ")" {
+        loop {
+            let ("identifier", param) = next else {
+                return None;
+            };
+            params.push(&src[param.start..param.end]);
+            match tokens.next()? {
+                (",", _) => next = tokens.next()?,
+                (")", _) => break,
+                _ => return None,
+            }
+        }
+    }
+    let ("=", assign) = tokens.next()? else {
+        return None;
+    };
+    Some((&src[name.start..name.end], params, &src[assign.end..]))
+}
+
+/// `name(params) = body`, as `:funcs` lists a function.
+fn definition(name: &str, function: &Function) -> String {
+    format!(
+        "{}({}) = {}",
+        name,
+        function.params().join(", "),
+        function.body()
+    )
+}
+
+struct Outcome {
+    // The variable the statement assigns, if it is an assignment.
+    name: Option<String>,
+    value: Value,
+    // The base a `hex`, `bin` or `oct` call asked for the value in.
+    radix: Option<u32>,
+    // The exact value of the statement as written, when fractions are on
+    // and it is not `value`.
+    exact: Option<String>,
+}
+
+impl Outcome {
+    fn value(&self) -> Value {
+        self.value
+    }
+
+    fn apply(&self, context: &mut Context, journal: &mut Journal) {
+        if let Some(name) = &self.name {
+            journal.assign(context, name, self.value);
+        }
+    }
+}
+
+/// `x = 7`, or `x = 7/3 (exact, kept as 2)` when the exact value differs
+/// from the integer bound.
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "{} = ", name)?;
+        }
+        let value = match self.radix {
+            Some(radix) => format_value(&self.value, &ValueFormat::default().radix(radix)),
+            None => self.value.to_string(),
+        };
+        match &self.exact {
+            Some(exact) => write!(f, "{} (exact, kept as {})", exact, value),
+            None => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Runs the statement `src` against `context`. With `fractions`, the outcome
+/// also has the exact value, written that way, if there is one and it is not
+/// the integer result; a statement with no exact value, such as one on
+/// intervals, has only the integer one.
+fn exec(
+    src: &str,
+    context: &Context,
+    fractions: Option<&ValueFormat>,
+) -> Result<Outcome, ExprError> {
+    let (name, src) = match split_assignment(src) {
+        Some((name, expr)) => (Some(name), expr),
+        None => (None, src),
+    };
+    let mut expr = Expr::new(src).with_context(context);
+    let result = expr.eval();
+    let exact = fractions
+        .zip(result.as_ref().ok())
+        .and_then(|(format, value)| {
+            let fraction = parse(src).and_then(|ast| ast.eval_fraction(context)).ok()?;
+            let integer = fraction
+                .is_integer()
+                .then(|| i32::try_from(fraction.numer()).ok());
+            (integer.flatten().map(Value::Int) != Some(*value))
+                .then(|| format_fraction(&fraction, format))
+        });
+    for warning in expr.warnings() {
+        // The exact value shows what a division discarded.
+        if exact.is_none() || warning.kind() != WarningKind::InexactDivision {
+            eprintln!("{}", warning.render(src));
+        }
+    }
+    Ok(Outcome {
+        name: name.map(str::to_string),
+        value: result?,
+        radix: expr.radix(),
+        exact,
+    })
+}
+
+/// A statement of a `:paste` block that ran.
+enum Statement {
+    Eval(Outcome),
+    Define(String, Function),
+}
+
+/// Runs every non-blank line against a scratch copy of `context`, failing
+/// with the 1-based line number of the first error. Each result is bound
+/// after the earlier `results`, and each function defined, for the lines
+/// that follow. Outcomes carry exact values as [`exec`] gives them with
+/// `fractions`.
+fn exec_block(
+    lines: &[String],
+    context: &Context,
+    results: &[Value],
+    fractions: Option<&ValueFormat>,
+) -> Result<Vec<Statement>, (usize, ExprError)> {
+    let mut scratch = context.clone();
+    let mut results = results.to_vec();
+    let mut statements = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let at = |err| (i + 1, err);
+        if let Some((name, params, body)) = split_definition(line) {
+            let function = Function::new(&params, body).map_err(at)?;
+            scratch.set_function(name, function.clone()).map_err(at)?;
+            statements.push(Statement::Define(name.to_string(), function));
+            continue;
+        }
+        let scope = with_results(&scratch, &results);
+        let outcome = exec(line, &scope, fractions).map_err(at)?;
+        if let Some(name) = &outcome.name {
+            scratch.set(name.as_str(), outcome.value);
+        }
+        results.push(outcome.value());
+        statements.push(Statement::Eval(outcome));
+    }
+
+    Ok(statements)
+}
+
+/// `context` with `ans` bound to the latest of `results`, and `_1`, `_2`, …
+/// to each of them in turn.
+fn with_results(context: &Context, results: &[Value]) -> Context {
+    let mut scope = context.clone();
+    for (i, value) in results.iter().enumerate() {
+        scope.set(format!("_{}", i + 1), *value);
+    }
+    if let Some(value) = results.last() {
+        scope.set("ans", *value);
+    }
+    scope
+}
+
+/// A variable binding before and after an assignment, or a function before
+/// and after a definition.
+enum Change {
+    Var {
+        name: String,
+        before: Option<Value>,
+        after: Value,
+    },
+    Function {
+        name: String,
+        before: Option<Function>,
+        after: Function,
+    },
+}
+
+impl Change {
+    /// Puts the binding back the way it was before the change, or with
+    /// `after`, as it was after.
+    fn restore(&self, context: &mut Context, after: bool) {
+        match self {
+            Change::Var {
+                name,
+                before,
+                after: value,
+            } => {
+                match if after { Some(*value) } else { *before } {
+                    Some(value) => context.set(name.as_str(), value),
+                    None => context.remove(name),
+                };
+            }
+            Change::Function {
+                name,
+                before,
+                after: function,
+            } => {
+                match if after {
+                    Some(function)
+                } else {
+                    before.as_ref()
+                } {
+                    // Undo and redo only ever return to definitions that
+                    // were accepted, so none can be recursive.
+                    Some(function) => context
+                        .set_function(name, function.clone())
+                        .expect("an earlier definition"),
+                    None => context.remove_function(name),
+                };
+            }
+        }
+    }
+}
+
+/// Context mutations in the order they were made, so they can be undone.
+#[derive(Default)]
+struct Journal {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl Journal {
+    fn assign(&mut self, context: &mut Context, name: &str, value: Value) {
+        let before = context.set(name, value);
+        self.undo.push(Change::Var {
+            name: name.to_string(),
+            before,
+            after: value,
+        });
+        self.redo.clear();
+    }
+
+    /// Defines `name` as `function`, failing if it would be recursive.
+    fn define(
+        &mut self,
+        context: &mut Context,
+        name: &str,
+        function: Function,
+    ) -> Result<(), ExprError> {
+        let before = context.set_function(name, function.clone())?;
+        self.undo.push(Change::Function {
+            name: name.to_string(),
+            before,
+            after: function,
+        });
+        self.redo.clear();
+        Ok(())
+    }
+
+    /// Reverts the latest change, returning it.
+    fn undo(&mut self, context: &mut Context) -> Option<&Change> {
+        let change = self.undo.pop()?;
+        change.restore(context, false);
+        self.redo.push(change);
+        self.redo.last()
+    }
+
+    /// Reapplies the latest undone change, returning it.
+    fn redo(&mut self, context: &mut Context) -> Option<&Change> {
+        let change = self.redo.pop()?;
+        change.restore(context, true);
+        self.undo.push(change);
+        self.undo.last()
+    }
+}
+
+fn profile(
+    src: &str,
+    context: &Context,
+    op_counts: bool,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let mut expr = Expr::new(src).with_context(context);
+    let start = Instant::now();
+    let result = expr.eval();
+    let elapsed = start.elapsed();
+
+    match result {
+        Ok(value) => writeln!(output, "{}", value)?,
+        Err(err) => eprintln!("{}", err),
+    }
+    writeln!(output, "time: {:?}", elapsed)?;
+
+    if op_counts {
+        for (op, count) in expr.op_counts() {
+            writeln!(output, "  {:<4} {}", op, count)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn trace(src: &str, context: &Context, output: &mut impl Write) -> io::Result<()> {
+    let options = EvalOptions::default().trace_vars(true);
+    let mut expr = Expr::with_options(src, options).with_context(context);
+
+    match expr.eval() {
+        Ok(value) => writeln!(output, "{}", value)?,
+        Err(err) => eprintln!("{}", err),
+    }
+    for (name, value) in expr.trace() {
+        writeln!(output, "  {} = {}", name, value)?;
+    }
+
+    Ok(())
+}
+
+/// Where the statements of a session come from.
+pub trait Lines {
+    /// The next line, after showing `prompt` if there is someone to see it,
+    /// or `None` once the input has ended.
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>>;
+
+    /// Whether a person is typing, as opposed to a pipe or a file.
+    fn interactive(&self) -> bool {
+        false
+    }
+
+    /// Called with the session's context before each statement is read,
+    /// e.g. so that its names can be completed.
+    fn update(&mut self, _context: &Context) {}
+
+    /// Shows an error or other message that is not a result.
+    fn report(&mut self, message: &str) {
+        eprintln!("{}", message);
+    }
+}
+
+/// Lines read as they are, without prompts, e.g. from a pipe.
+pub struct Piped<R>(pub R);
+
+impl<R: BufRead> Lines for Piped<R> {
+    fn read_line(&mut self, _prompt: &str) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.0.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(input: &str) -> String {
+        let mut output = Vec::new();
+        run(Piped(input.as_bytes()), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Lines as if typed at a terminal, without its prompts.
+    struct Typed<'a>(std::str::Lines<'a>);
+
+    impl Lines for Typed<'_> {
+        fn read_line(&mut self, _prompt: &str) -> io::Result<Option<String>> {
+            Ok(self.0.next().map(String::from))
+        }
+
+        fn interactive(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_continuation() {
+        let typed = |input: &str| {
+            let mut output = Vec::new();
+            run(Typed(input.lines()), &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(typed("2 * (3 +\n4\n)\n5\n"), "14\n5\n");
+        assert_eq!(typed("x = 1 +\n  2\nx\n"), "x = 3\n3\n");
+        assert_eq!(typed("1 +\n\n2\n"), "2\n");
+        assert_eq!(typed("1 + * 2\n3\n"), "3\n");
+        // Piped input keeps to one statement per line.
+        assert_eq!(session("1 +\n2\n"), "2\n");
+    }
+
+    #[test]
+    fn test_results() {
+        assert_eq!(
+            session("2 + 3\nans * 2\nx = ans - 1\n_1 + _2 + _3\nans\n"),
+            "5\n10\nx = 9\n24\n24\n"
+        );
+        assert_eq!(session(":paste\n4\nans + 1\n:end\nans + _1\n"), "4\n5\n9\n");
+        assert_eq!(session("ans\n1\n:clear\n_1\n"), "1\n");
+        assert_eq!(session("ans = 3\nans\n_1\n"), "ans = 3\n3\n3\n");
+        assert_eq!(
+            session("hex(255) + 1\nans\nx = bin(5)\n"),
+            "0x100\n256\nx = 0b101\n"
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        assert_eq!(session("1 + 2\n\n2 * 3\n"), "3\n6\n");
+    }
+
+    #[test]
+    fn test_paste_mode() {
+        assert_eq!(session(":paste\n1 + 2\n\n2 * 3\n:end\n4\n"), "3\n6\n4\n");
+        assert_eq!(session(":paste\n1 + 2\n2 *\n:end\n4\n"), "4\n");
+        assert_eq!(session(":paste\n1 + 2\n2 * 3"), "3\n6\n");
+        assert_eq!(
+            session(":paste\nx = 2\ny = x * 3\n:end\ny + 1\n"),
+            "x = 2\ny = 6\n7\n"
+        );
+        assert_eq!(session(":paste\nx = 2\ny = x *\n:end\nx\n"), "");
+    }
+
+    #[test]
+    fn test_profile() {
+        let out = session("1 + 2 * 3 - -4 * 5\n:profile\n");
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..2], ["27", "27"]);
+        assert!(lines[2].starts_with("time: "));
+        assert_eq!(lines[3..], ["  *    2", "  +    1", "  -    1", "  neg  1"]);
+
+        let out = session("2 ^ 10\n:time\n");
+        assert!(out.starts_with("1024\n1024\ntime: "));
+        assert_eq!(session(":time\n"), "");
+    }
+
+    #[test]
+    fn test_trace() {
+        assert_eq!(
+            session("rate = 7\nbase = 1000\nbase * rate / 100 + base\n:trace\n"),
+            "rate = 7\nbase = 1000\n1070\n1070\n  base = 1000\n  rate = 7\n"
+        );
+        assert_eq!(session(":trace\n"), "");
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(Command::parse(":vars"), Ok(Command::Vars));
+        assert_eq!(
+            Command::parse(":mode   float"),
+            Ok(Command::Mode(Some(Mode::Float)))
+        );
+        assert_eq!(Command::parse(":mode"), Ok(Command::Mode(None)));
+        assert_eq!(
+            Command::parse(":mode hex"),
+            Err("Usage: :mode [int|float]".into())
+        );
+        assert_eq!(
+            Command::parse(":quit now"),
+            Err(":quit takes no arguments, found 'now'".into())
+        );
+        assert_eq!(
+            Command::parse(":vras"),
+            Err("Unknown command: :vras (try :help)".into())
+        );
+
+        assert_eq!(
+            session("b = 2\na = 1\n:vars\n"),
+            "b = 2\na = 1\na = 1\nb = 2\n"
+        );
+        assert_eq!(
+            session("a = 1\n:clear\n:vars\n:undo\n:mode\n"),
+            "a = 1\nint\n"
+        );
+        assert_eq!(session("1\n:quit\n2\n"), "1\n");
+        assert!(session(":help\n").contains(":vars"));
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(
+            session("sq(x) = x*x\narea(w, h) = w * h\none() = 1\nsq(3) + area(2, 4) * one()\n:funcs\n"),
+            "sq(x) = x * x\narea(w, h) = w * h\none() = 1\n17\narea(w, h) = w * h\none() = 1\nsq(x) = x * x\n"
+        );
+        assert_eq!(
+            session("f(x) = x + 1\nf(x) = f(x)\nf(2)\n:clear\n:funcs\nf(x, 1) = 2\n"),
+            "f(x) = x + 1\n3\n"
+        );
+        assert_eq!(
+            split_definition("f(a, b) = a - b"),
+            Some(("f", vec!["a", "b"], " a - b"))
+        );
+        assert_eq!(
+            session(":paste\nf(x) = x + y\ny = 2\nf(1) * 3\n:end\nf(y)\n:funcs\n"),
+            "f(x) = x + y\ny = 2\n9\n4\nf(x) = x + y\n"
+        );
+        assert_eq!(session(":paste\nf(x) = x\nf(x) = f(x)\n:end\n:funcs\n"), "");
+        assert_eq!(split_definition("f(2) = 1"), None);
+        assert_eq!(split_definition("x = 1"), None);
+    }
+
+    #[test]
+    fn test_fractions() {
+        assert_eq!(
+            session("7 / 3\n:frac\n7 / 3\nx = 1/3 + 1/6\nx * 4\nans * 4\n6 / 3\n3 / 2 * 2\n"),
+            "2\n// Fractions on\n7/3 (exact, kept as 2)\nx = 1/2 (exact, kept as 0)\n0\n0\n2\n3 (exact, kept as 2)\n"
+        );
+        assert_eq!(
+            session(":frac mixed\n-7 / 3\n(1/2) % 2\nhex(31 / 2)\n:frac\n7 / 3\n"),
+            "// Fractions mixed\n-2 1/3 (exact, kept as -2)\n0\n0xf\n// Fractions off\n2\n"
+        );
+        assert_eq!(
+            session(":frac\n:paste\n1 / 4\nans + 1 / 2\n:end\n"),
+            "// Fractions on\n1/4 (exact, kept as 0)\n1/2 (exact, kept as 0)\n"
+        );
+        assert_eq!(
+            Command::parse(":frac thirds"),
+            Err("Usage: :frac [mixed|improper]".into())
+        );
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        assert_eq!(
+            session("x = 2\nx * 3\nx = 5\n:undo\nx\n:redo\nx\n:undo\n:undo\nx\n:redo\nx\n"),
+            "x = 2\n6\nx = 5\nx = 2\n2\nx = 5\n5\nx = 2\nx is unset\nx = 2\n2\n"
+        );
+        assert_eq!(
+            session("x = 1\n:undo\n:redo\nx = 3\n:redo\nx\n"),
+            "x = 1\nx is unset\nx = 1\nx = 3\n3\n"
+        );
+        assert_eq!(
+            session("f(x) = x\nf(x) = 2 * x\n:undo\nf(3)\n:undo\n:funcs\n:redo\n:redo\nf(3)\n"),
+            "f(x) = x\nf(x) = 2 * x\nf(x) = x\n3\nf() is undefined\nf(x) = x\nf(x) = 2 * x\n6\n"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use expr_core::{eval, parse};
+
+/// Runs `eval_expr roundtrip FILE`: every non-blank line of `FILE` is parsed,
+/// printed back as infix and re-parsed, and both the trees (ignoring redundant
+/// parentheses) and the results of evaluating the two spellings must match.
+/// Mismatches are reported per line.
+pub fn run(path: &str) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+    let mut checked = 0;
+    let mut failed = 0;
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        checked += 1;
+        if let Err(err) = check(line) {
+            eprintln!("{}:{}: {}", path, i + 1, err);
+            failed += 1;
+        }
+    }
+
+    println!(
+        "{} of {} expressions round-tripped",
+        checked - failed,
+        checked
+    );
+    if failed > 0 {
+        return Err("some expressions did not round-trip".into());
+    }
+    Ok(())
+}
+
+/// Checks that `src` survives printing and re-parsing unchanged.
+fn check(src: &str) -> Result<(), String> {
+    let ast = parse(src).map_err(|err| err.to_string())?;
+    let printed = ast.to_string();
+    let reparsed =
+        parse(&printed).map_err(|err| format!("'{}' does not parse: {}", printed, err))?;
+
+    if reparsed.strip_parens() != ast.strip_parens() {
+        return Err(format!("'{}' parses to a different tree", printed));
+    }
+
+    let (before, after) = (eval(src), eval(&printed));
+    if before != after {
+        return Err(format!(
+            "'{}' evaluates to {} but '{}' to {}",
+            src.trim(),
+            show(&before),
+            printed,
+            show(&after)
+        ));
+    }
+    Ok(())
+}
+
+fn show(result: &expr_core::Result<expr_core::Value>) -> String {
+    match result {
+        Ok(value) => value.to_string(),
+        Err(err) => format!("an error ({})", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        assert_eq!(check("2*(3+4) - -1"), Ok(()));
+        assert_eq!(check("not x in (1, 2) or 2 ** 3 ** 2"), Ok(()));
+        assert_eq!(check("1 / 0"), Ok(()));
+        assert_eq!(
+            check("1 +"),
+            Err("Parse error: Expected number or parenthesis, found end of input".into())
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::Failure;
+use expr_core::{split_assignment, Context, EvalOptions, Expr, ExprError, Script, Value};
+
+/// Runs `eval_expr run FILE`: the [`Script`] in the file at `path`, such as
+/// a `.calc` file, is run from an empty context and its value, if any,
+/// printed.
+///
+/// With `batch`, for `--batch`, each line of the file is instead evaluated on its own, as
+/// an expression or an assignment, against a context shared by the lines.
+/// Every value is printed, and every failure reported as
+/// `FILE:LINE:COLUMN: error: MESSAGE` without stopping.
+///
+/// With `json`, for `--format json`, values and errors are printed to stdout as JSON
+/// objects, one per line, each with the `line` it came from.
+pub fn run(path: &str, batch: bool, json: bool) -> Result<(), Failure> {
+    let src = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+    if batch {
+        let mut failure = None;
+        for line in run_batch(&src) {
+            if let Err((_, err)) = &line.result {
+                failure.get_or_insert(Failure::of(err));
+            }
+            match (json, &line.result) {
+                (true, _) => println!("{}", line.to_json()),
+                (false, Ok(_)) => println!("{}", line.to_text(path)),
+                (false, Err(_)) => eprintln!("{}", line.to_text(path)),
+            }
+        }
+        return failure.map_or(Ok(()), Err);
+    }
+
+    let result = Script::parse(&src)
+        .and_then(|script| script.run(&EvalOptions::default(), &mut Context::new()));
+    match result {
+        Ok(Some(value)) if json => println!("{}", value.to_json()),
+        Ok(Some(value)) => println!("{}", value),
+        Ok(None) => {}
+        Err(err) => {
+            if json {
+                println!(
+                    "{{\"line\":{},\"error\":{}}}",
+                    err.line(),
+                    err.error().to_json()
+                );
+            } else {
+                eprintln!("{}:{}: error: {}", path, err.line(), err.error());
+            }
+            return Err(Failure::of(err.error()));
+        }
+    }
+    Ok(())
+}
+
+/// The outcome of one line of a batch file.
+struct BatchLine {
+    /// 1-based, like `column`.
+    line: usize,
+    result: Result<(Option<String>, Value), (usize, ExprError)>,
+}
+
+impl BatchLine {
+    // `VALUE` or `NAME = VALUE`, or `PATH:LINE:COLUMN: error: MESSAGE`,
+    // like a compiler's.
+    fn to_text(&self, path: &str) -> String {
+        match &self.result {
+            Ok((Some(name), value)) => format!("{} = {}", name, value),
+            Ok((None, value)) => value.to_string(),
+            Err((column, err)) => format!("{}:{}:{}: error: {}", path, self.line, column, err),
+        }
+    }
+
+    // `Value::to_json` with `line` and any `name` added, or
+    // `{"line":LINE,"column":COLUMN,"error":ERROR}`.
+    fn to_json(&self) -> String {
+        match &self.result {
+            Ok((name, value)) => {
+                // Names are identifiers, which need no escaping.
+                let name = name
+                    .as_ref()
+                    .map_or(String::new(), |name| format!("\"name\":\"{}\",", name));
+                format!("{{\"line\":{},{}{}", self.line, name, &value.to_json()[1..])
+            }
+            Err((column, err)) => format!(
+                "{{\"line\":{},\"column\":{},\"error\":{}}}",
+                self.line,
+                column,
+                err.to_json()
+            ),
+        }
+    }
+}
+
+/// Evaluates each line of `src`, skipping blank lines and `#` comments.
+fn run_batch(src: &str) -> Vec<BatchLine> {
+    let mut context = Context::new();
+    let mut lines = Vec::new();
+
+    for (i, line) in src.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or_default();
+        if code.trim().is_empty() {
+            continue;
+        }
+        let (name, expr) = match split_assignment(code) {
+            Some((name, expr)) => (Some(name), expr),
+            None => (None, code),
+        };
+
+        let mut evaluator = Expr::new(expr).with_context(&context);
+        let result = match evaluator.eval() {
+            Ok(value) => {
+                if let Some(name) = name {
+                    context.set(name, value);
+                }
+                Ok((name.map(String::from), value))
+            }
+            Err(err) => {
+                // The expression is the end of `code`, so its spans are
+                // offset by the assignment before it. An error without a
+                // span points at the start of the expression.
+                let offset = match err.span() {
+                    Some(span) => code.len() - expr.len() + span.start,
+                    None => code.len() - expr.trim_start().len(),
+                };
+                let column = line[..offset.min(line.len())].chars().count() + 1;
+                Err((column, err))
+            }
+        };
+        lines.push(BatchLine {
+            line: i + 1,
+            result,
+        });
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_batch() {
+        let src = "# prices\nrate = 8\n\n100 * rate / 100  # tax\nrate +\nx = 1 / 0\nrate * 2\n";
+        let lines = run_batch(src);
+        assert_eq!(
+            lines
+                .iter()
+                .map(|line| line.to_text("formulas.txt"))
+                .collect::<Vec<_>>(),
+            [
+                "rate = 8",
+                "8",
+                "formulas.txt:5:7: error: Parse error: Expected number or parenthesis, found end of input",
+                "formulas.txt:6:5: error: Division by zero",
+                "16",
+            ]
+        );
+        assert_eq!(
+            lines[0].to_json(),
+            r#"{"line":2,"name":"rate","value":8,"type":"int"}"#
+        );
+        assert_eq!(lines[1].to_json(), r#"{"line":4,"value":8,"type":"int"}"#);
+        assert_eq!(
+            lines[3].to_json(),
+            r#"{"line":6,"column":5,"error":{"code":"E002","message":"Division by zero","span":null,"suggestions":[]}}"#
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use expr_core::parse;
+
+/// Runs `eval_expr simplify`, printing each of `lines` after
+/// [`Ast::simplify`](expr_core::Ast::simplify).
+pub fn run(lines: &[String]) -> Result<(), String> {
+    let mut failed = false;
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        match parse(line) {
+            Ok(ast) => println!("{}", ast.simplify()),
+            Err(err) => {
+                eprintln!("{}: {}", line.trim(), err);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return Err("some expressions could not be simplified".into());
+    }
+    Ok(())
+}
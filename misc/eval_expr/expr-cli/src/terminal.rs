@@ -0,0 +1,415 @@
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    env,
+    ffi::OsString,
+    fmt::Write as _,
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+use expr_core::{tokenize, Context, Span, BUILTINS};
+use rustyline::{
+    completion::Completer, config::ColorMode, error::ReadlineError, highlight::Highlighter,
+    hint::Hinter, history::DefaultHistory, validate::Validator, Config, Editor, Helper,
+};
+
+use crate::repl::{Lines, COMMANDS};
+
+/// Lines typed at a terminal, with editing and history.
+///
+/// Ctrl-C discards the line being typed and prompts again; Ctrl-D on an
+/// empty line ends the session.
+///
+/// Unless `NO_COLOR` is set, input is highlighted as it is typed, with the
+/// bracket matching the one at the cursor picked out, and errors are red.
+pub struct Terminal {
+    editor: Editor<LineHelper, DefaultHistory>,
+    history: Option<PathBuf>,
+    color: bool,
+}
+
+impl Terminal {
+    /// A terminal whose history starts from the file at `history`, if
+    /// given, and is written back to it when the session ends.
+    pub fn new(history: Option<PathBuf>) -> io::Result<Terminal> {
+        let color = env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+        let config = Config::builder()
+            .color_mode(if color {
+                ColorMode::Enabled
+            } else {
+                ColorMode::Disabled
+            })
+            .build();
+        let mut editor = Editor::with_config(config).map_err(readline_error)?;
+        editor.set_helper(Some(LineHelper {
+            names: Names::default(),
+            submitted: Cell::new(false),
+        }));
+        if let Some(path) = &history {
+            match editor.load_history(path) {
+                Ok(()) => {}
+                Err(ReadlineError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => eprintln!("Could not read history from {}: {}", path.display(), err),
+            }
+        }
+        Ok(Terminal {
+            editor,
+            history,
+            color: color && io::stderr().is_terminal(),
+        })
+    }
+
+    fn save_history(&mut self, path: &Path) -> Result<(), ReadlineError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.editor.save_history(path)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if let Some(path) = self.history.take() {
+            if let Err(err) = self.save_history(&path) {
+                eprintln!("Could not save history to {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// Where the history of interactive sessions is kept:
+/// `$XDG_CONFIG_HOME/eval_expr/history`, or `~/.config/eval_expr/history`
+/// when that is unset.
+pub fn history_path() -> Option<PathBuf> {
+    config_dir(env::var_os("XDG_CONFIG_HOME"), env::var_os("HOME"))
+        .map(|dir| dir.join("eval_expr").join("history"))
+}
+
+// The XDG config directory given the values of `XDG_CONFIG_HOME` and
+// `HOME`, ignoring either when empty or relative as the spec asks.
+fn config_dir(xdg_config_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    let absolute =
+        |value: Option<OsString>| value.map(PathBuf::from).filter(|path| path.is_absolute());
+    absolute(xdg_config_home).or_else(|| absolute(home).map(|home| home.join(".config")))
+}
+
+impl Lines for Terminal {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    self.editor
+                        .add_history_entry(line.as_str())
+                        .map_err(readline_error)?;
+                }
+                Ok(Some(line))
+            }
+            Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(err) => Err(readline_error(err)),
+        }
+    }
+
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    fn update(&mut self, context: &Context) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.names = Names::of(context);
+        }
+    }
+
+    fn report(&mut self, message: &str) {
+        if self.color {
+            eprintln!("{}{}{}", ERROR, message, RESET);
+        } else {
+            eprintln!("{}", message);
+        }
+    }
+}
+
+fn readline_error(err: ReadlineError) -> io::Error {
+    match err {
+        ReadlineError::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+/// Completes and highlights the line being typed.
+struct LineHelper {
+    names: Names,
+    // Set while the line is drawn for the last time, after Enter.
+    submitted: Cell<bool>,
+}
+
+/// What Tab completes to: the commands, the builtins, and the variables and
+/// functions of the session so far.
+#[derive(Default)]
+struct Names {
+    vars: Vec<String>,
+    functions: Vec<String>,
+}
+
+impl Names {
+    fn of(context: &Context) -> Names {
+        Names {
+            vars: context.vars().map(|(name, _)| name.to_string()).collect(),
+            functions: context
+                .functions()
+                .map(|(name, _)| name)
+                .chain(BUILTINS)
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// The start of the word ending at `pos` in `line`, and the sorted
+    /// candidates it completes to. A command completes only at the start of
+    /// the line, and a function comes with its opening parenthesis.
+    fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        if before.trim_start().starts_with(':') && !before.trim_start().contains(' ') {
+            let start = before.len() - before.trim_start().len();
+            let candidates = COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(&before[start..]))
+                .map(|command| command.to_string())
+                .collect();
+            return (start, candidates);
+        }
+
+        let start = before
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| {
+                i + before[i..].chars().next().map_or(0, char::len_utf8)
+            });
+        let word = &before[start..];
+        if word.is_empty() || word.starts_with(|c: char| c.is_ascii_digit()) {
+            return (start, Vec::new());
+        }
+
+        let mut candidates = self
+            .functions
+            .iter()
+            .filter(|name| name.starts_with(word))
+            .map(|name| format!("{}(", name))
+            .chain(
+                self.vars
+                    .iter()
+                    .filter(|name| name.starts_with(word))
+                    .cloned(),
+            )
+            .collect::<Vec<_>>();
+        candidates.sort();
+        // A function may be defined with the name of a builtin.
+        candidates.dedup();
+        (start, candidates)
+    }
+}
+
+impl Completer for LineHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.names.complete(line, pos))
+    }
+}
+
+impl Hinter for LineHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LineHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if line.trim_start().starts_with(':') {
+            return Cow::Borrowed(line);
+        }
+        let cursor = (!self.submitted.get()).then_some(pos);
+        Cow::Owned(highlight(line, cursor))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, forced: bool) -> bool {
+        // Moving the cursor can change which brackets are picked out.
+        self.submitted.set(forced);
+        true
+    }
+}
+
+impl Validator for LineHelper {}
+
+impl Helper for LineHelper {}
+
+const NUMBER: &str = "\x1b[36m";
+const OPERATOR: &str = "\x1b[35m";
+const MATCHED: &str = "\x1b[1;32m";
+const ERROR: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// `line` with ANSI colors: numbers cyan, operators magenta, characters that
+/// start no token red, and the bracket at or just before `cursor`, if any,
+/// bold green along with its partner, or red if it has none.
+fn highlight(line: &str, cursor: Option<usize>) -> String {
+    let tokens = tokenize(line);
+    let brackets = cursor.and_then(|pos| matching_brackets(&tokens, pos));
+
+    let mut out = String::new();
+    let mut end = 0;
+    for (i, (span, kind)) in tokens.iter().enumerate() {
+        out.push_str(&line[end..span.start]);
+        let color = match brackets {
+            Some((at, partner)) if i == at => Some(if partner.is_some() { MATCHED } else { ERROR }),
+            Some((_, Some(partner))) if i == partner => Some(MATCHED),
+            _ => match *kind {
+                "number" => Some(NUMBER),
+                "unknown" => Some(ERROR),
+                "identifier" | "(" | ")" | "[" | "]" | "," => None,
+                _ => Some(OPERATOR),
+            },
+        };
+        let text = &line[span.start..span.end];
+        match color {
+            Some(color) => {
+                let _ = write!(out, "{}{}{}", color, text, RESET);
+            }
+            None => out.push_str(text),
+        }
+        end = span.end;
+    }
+    out.push_str(&line[end..]);
+    out
+}
+
+/// The index of the bracket token at or just before `pos`, and of the one
+/// that matches it, if there is one.
+fn matching_brackets(tokens: &[(Span, &str)], pos: usize) -> Option<(usize, Option<usize>)> {
+    let is_bracket = |i: usize| matches!(tokens[i].1, "(" | ")" | "[" | "]");
+    let at = (0..tokens.len())
+        .find(|&i| tokens[i].0.start == pos && is_bracket(i))
+        .or_else(|| (0..tokens.len()).find(|&i| tokens[i].0.end == pos && is_bracket(i)))?;
+
+    let (open, close, forward) = match tokens[at].1 {
+        "(" => ("(", ")", true),
+        ")" => ("(", ")", false),
+        "[" => ("[", "]", true),
+        _ => ("[", "]", false),
+    };
+    let mut depth = 0;
+    let order: Box<dyn Iterator<Item = usize>> = if forward {
+        Box::new(at..tokens.len())
+    } else {
+        Box::new((0..=at).rev())
+    };
+    for i in order {
+        let kind = tokens[i].1;
+        if kind == open || kind == close {
+            depth += if (kind == open) == forward { 1 } else { -1 };
+            if depth == 0 {
+                return Some((at, Some(i)));
+            }
+        }
+    }
+    Some((at, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines typed into a session, keeping the names it would complete as a
+    /// terminal does.
+    struct Typed<'a> {
+        lines: std::str::Lines<'a>,
+        names: &'a mut Names,
+    }
+
+    impl Lines for Typed<'_> {
+        fn read_line(&mut self, _prompt: &str) -> io::Result<Option<String>> {
+            Ok(self.lines.next().map(String::from))
+        }
+
+        fn update(&mut self, context: &Context) {
+            *self.names = Names::of(context);
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut names = Names::default();
+        let typed = Typed {
+            lines: "rate = 7\nradius = 2\nsquare(x) = x * x\nrand_int() = 4\nhex(n) = n\n".lines(),
+            names: &mut names,
+        };
+        crate::repl::run(typed, io::sink()).unwrap();
+        let complete = |line: &str| names.complete(line, line.len());
+
+        assert_eq!(complete("sq"), (0, vec!["square(".to_string()]));
+        assert_eq!(
+            complete("2 * (ra"),
+            (
+                5,
+                vec!["radius".to_string(), "rand_int(".into(), "rate".into()]
+            )
+        );
+        assert_eq!(complete("rate + "), (7, vec![]));
+        assert_eq!(complete("1 + he"), (4, vec!["hex(".to_string()]));
+        assert_eq!(complete("b"), (0, vec!["bin(".to_string()]));
+        assert_eq!(complete("12"), (0, vec![]));
+        assert_eq!(
+            complete(":p"),
+            (0, vec![":paste".to_string(), ":profile".into()])
+        );
+        assert_eq!(complete(":mode i"), (6, vec![]));
+        assert_eq!(
+            names.complete("sq + 1", 2),
+            (0, vec!["square(".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(
+            highlight("x + 12", None),
+            "x \x1b[35m+\x1b[0m \x1b[36m12\x1b[0m"
+        );
+        assert_eq!(
+            highlight("(1) $", Some(3)),
+            "\x1b[1;32m(\x1b[0m\x1b[36m1\x1b[0m\x1b[1;32m)\x1b[0m \x1b[31m$\x1b[0m"
+        );
+
+        let tokens = tokenize("f((a), [b]");
+        assert_eq!(matching_brackets(&tokens, 1), Some((1, None)));
+        assert_eq!(matching_brackets(&tokens, 2), Some((2, Some(4))));
+        assert_eq!(matching_brackets(&tokens, 5), Some((4, Some(2))));
+        assert_eq!(matching_brackets(&tokens, 7), Some((6, Some(8))));
+        assert_eq!(matching_brackets(&tokens, 0), None);
+        assert_eq!(matching_brackets(&tokenize("(1"), 0), Some((0, None)));
+    }
+
+    #[test]
+    fn test_config_dir() {
+        let dir = |xdg: Option<&str>, home: Option<&str>| {
+            config_dir(xdg.map(OsString::from), home.map(OsString::from))
+        };
+        assert_eq!(
+            dir(Some("/etc/xdg"), Some("/home/ada")),
+            Some(PathBuf::from("/etc/xdg"))
+        );
+        assert_eq!(
+            dir(None, Some("/home/ada")),
+            Some(PathBuf::from("/home/ada/.config"))
+        );
+        assert_eq!(
+            dir(Some("config"), Some("/home/ada")),
+            Some(PathBuf::from("/home/ada/.config"))
+        );
+        assert_eq!(dir(Some(""), None), None);
+    }
+}
@@ -0,0 +1,45 @@
+use expr_core::parse;
+
+/// Runs `eval_expr parse`, drawing the tree of each of `lines` with
+/// [`Ast::to_tree_string`](expr_core::Ast::to_tree_string) without
+/// evaluating it. `ascii` draws the branches with plain ASCII for
+/// terminals without box-drawing characters.
+pub fn run(ascii: bool, lines: &[String]) -> Result<(), String> {
+    let mut failed = false;
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        match parse(line) {
+            Ok(ast) if ascii => println!("{}", to_ascii(&ast.to_tree_string())),
+            Ok(ast) => println!("{}", ast.to_tree_string()),
+            Err(err) => {
+                eprintln!("{}", err.render(line));
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return Err("some expressions could not be parsed".into());
+    }
+    Ok(())
+}
+
+// `tree` with its box-drawing branches redrawn in ASCII.
+fn to_ascii(tree: &str) -> String {
+    tree.replace("├── ", "|-- ")
+        .replace("└── ", "`-- ")
+        .replace("│   ", "|   ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ascii() {
+        let tree = parse("2 ^ (3 - x)").unwrap().to_tree_string();
+        assert_eq!(
+            to_ascii(&tree),
+            "^\n|-- 2\n`-- ()\n    `-- -\n        |-- 3\n        `-- x"
+        );
+    }
+}
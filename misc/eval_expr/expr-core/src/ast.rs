@@ -0,0 +1,1616 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    iter::Peekable,
+};
+
+use crate::{
+    context::Scope,
+    meter::Meter,
+    rng::Rng,
+    token::{Token, Tokenizer, ASSOC_RIGHT},
+    visit::{fold_children, walk, Folder, Visitor},
+    AstView, Context, EvalOptions, ExprError, Interval, OperatorTable, PrecedenceTable, Result,
+    Span, Value,
+};
+
+/// A parsed expression, as returned by [`parse`](crate::parse).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Ast {
+    Number(i32),
+    Var(String),
+    Interval {
+        lo: Box<Ast>,
+        hi: Box<Ast>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Ast>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Ast>,
+        rhs: Box<Ast>,
+    },
+    In {
+        needle: Box<Ast>,
+        list: Vec<Ast>,
+    },
+    /// A call to a function defined with
+    /// [`Context::define_function`](crate::Context::define_function).
+    Call {
+        name: String,
+        args: Vec<Ast>,
+    },
+    Paren(Box<Ast>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+    Pow,
+    And,
+    Or,
+}
+
+pub(crate) const UNARY_OPS: [UnaryOp; 2] = [UnaryOp::Neg, UnaryOp::Not];
+
+pub(crate) const BINARY_OPS: [BinaryOp; 9] = [
+    BinaryOp::Add,
+    BinaryOp::Sub,
+    BinaryOp::Mul,
+    BinaryOp::Div,
+    BinaryOp::FloorDiv,
+    BinaryOp::Mod,
+    BinaryOp::Pow,
+    BinaryOp::And,
+    BinaryOp::Or,
+];
+
+impl UnaryOp {
+    /// The operator whose [`symbol`](UnaryOp::symbol) is `symbol`.
+    pub(crate) fn from_symbol(symbol: &str) -> Option<Self> {
+        UNARY_OPS.into_iter().find(|op| op.symbol() == symbol)
+    }
+
+    /// The operator's name in RPN output: `neg` or `not`.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "neg",
+            UnaryOp::Not => "not",
+        }
+    }
+}
+
+impl BinaryOp {
+    /// The operator whose canonical [`symbol`](BinaryOp::symbol) is `symbol`.
+    pub(crate) fn from_symbol(symbol: &str) -> Option<Self> {
+        BINARY_OPS.into_iter().find(|op| op.symbol() == symbol)
+    }
+
+    fn from_token(token: &Token) -> Option<Self> {
+        let op = match token {
+            Token::Plus => BinaryOp::Add,
+            Token::Minus => BinaryOp::Sub,
+            Token::Multiply => BinaryOp::Mul,
+            Token::Divide => BinaryOp::Div,
+            Token::FloorDivide => BinaryOp::FloorDiv,
+            Token::Modulo => BinaryOp::Mod,
+            Token::Power => BinaryOp::Pow,
+            Token::And => BinaryOp::And,
+            Token::Or => BinaryOp::Or,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    pub(crate) fn token(&self) -> Token {
+        match self {
+            BinaryOp::Add => Token::Plus,
+            BinaryOp::Sub => Token::Minus,
+            BinaryOp::Mul => Token::Multiply,
+            BinaryOp::Div => Token::Divide,
+            BinaryOp::FloorDiv => Token::FloorDivide,
+            BinaryOp::Mod => Token::Modulo,
+            BinaryOp::Pow => Token::Power,
+            BinaryOp::And => Token::And,
+            BinaryOp::Or => Token::Or,
+        }
+    }
+
+    fn is_commutative(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Add | BinaryOp::Mul | BinaryOp::And | BinaryOp::Or
+        )
+    }
+
+    /// The operator's canonical spelling, e.g. `^` for both `^` and `**`.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::FloorDiv => "//",
+            BinaryOp::Mod => "%",
+            BinaryOp::Pow => "^",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+        }
+    }
+}
+
+impl Ast {
+    /// Evaluates the tree with the default options and no variables, giving
+    /// the same result as [`Expr::eval`](crate::Expr::eval) on its source.
+    pub fn eval(&self) -> Result<Value> {
+        self.eval_with(&EvalOptions::default(), &Context::new())
+    }
+
+    /// Evaluates the tree under `options`, resolving identifiers against
+    /// `context`. The tree can be evaluated any number of times.
+    pub fn eval_with(&self, options: &EvalOptions, context: &Context) -> Result<Value> {
+        self.eval_metered(options, &Scope::new(context), &Meter::new(options))
+    }
+
+    /// Evaluates the tree as part of an evaluation burning fuel from `meter`.
+    pub(crate) fn eval_metered(
+        &self,
+        options: &EvalOptions,
+        scope: &Scope,
+        meter: &Meter,
+    ) -> Result<Value> {
+        if let Some(limit) = options.max_depth {
+            if self.depth() > limit {
+                return Err(ExprError::DepthLimitExceeded { limit });
+            }
+        }
+        self.evaluate(options, scope, meter)
+    }
+
+    fn evaluate(&self, options: &EvalOptions, scope: &Scope, meter: &Meter) -> Result<Value> {
+        match self {
+            Ast::Number(n) => Ok(Value::Int(match options.modulus {
+                Some(p) => n.rem_euclid(p),
+                None => *n,
+            })),
+            Ast::Var(name) => scope
+                .get(name)
+                .ok_or_else(|| ExprError::UnknownVariable(name.clone())),
+            Ast::Interval { .. } if options.modulus.is_some() => Err(ExprError::Unsupported(
+                "interval in modular arithmetic".into(),
+            )),
+            Ast::Interval { lo, hi } if options.intervals => {
+                let lo = lo
+                    .evaluate(options, scope, meter)?
+                    .expect_int("interval bound")?;
+                let hi = hi
+                    .evaluate(options, scope, meter)?
+                    .expect_int("interval bound")?;
+                Interval::new(lo, hi).map(Value::Interval)
+            }
+            // Without interval mode `[` is not an operand, as in `Expr`.
+            Ast::Interval { .. } => Err(ExprError::UnexpectedToken {
+                found: Some("[".into()),
+                expected: "number or parenthesis",
+                span: None,
+            }),
+            Ast::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => {
+                let operand = operand.evaluate(options, scope, meter)?;
+                meter.burn()?;
+                operand.negate(options)
+            }
+            Ast::Unary {
+                op: UnaryOp::Not,
+                operand,
+            } => {
+                let operand = operand.evaluate(options, scope, meter)?.expect_int("not")?;
+                meter.burn()?;
+                Ok(Value::Int((operand == 0) as i32))
+            }
+            Ast::Binary { op, lhs, rhs } => {
+                let lhs = lhs.evaluate(options, scope, meter)?;
+                let rhs = match options.modulus {
+                    // Exponents count repetitions, so they are never reduced.
+                    Some(_) if *op == BinaryOp::Pow => {
+                        let plain = EvalOptions {
+                            modulus: None,
+                            ..options.clone()
+                        };
+                        rhs.evaluate(&plain, scope, meter)?
+                    }
+                    _ => rhs.evaluate(options, scope, meter)?,
+                };
+                meter.burn()?;
+                op.token().apply(lhs, rhs, options)
+            }
+            Ast::In { needle, list } => {
+                let needle = needle.evaluate(options, scope, meter)?.expect_int("in")?;
+                let mut found = false;
+                for item in list {
+                    found |= item.evaluate(options, scope, meter)?.expect_int("in")? == needle;
+                }
+                meter.burn()?;
+                Ok(Value::Int(found as i32))
+            }
+            Ast::Call { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(options, scope, meter))
+                    .collect::<Result<Vec<_>>>()?;
+                meter.burn()?;
+                scope.call(name, args, options, meter)
+            }
+            Ast::Paren(inner) => inner.evaluate(options, scope, meter),
+        }
+    }
+
+    /// Renames variables according to `map`, leaving unlisted ones alone. All
+    /// names are replaced at once, so `{x: y, y: x}` swaps `x` and `y`.
+    ///
+    /// An expression has no binders, so every variable in it is free and
+    /// renaming cannot capture one. Function parameters are binders, but
+    /// they live in a [`Context`] rather than in the tree: passes that put a
+    /// function body into an expression, as [`Ast::inline_functions`] does,
+    /// must substitute the arguments for the parameters before the body
+    /// meets any other names.
+    pub fn rename(&self, map: &HashMap<String, String>) -> Ast {
+        struct Renamer<'a>(&'a HashMap<String, String>);
+
+        impl Folder for Renamer<'_> {
+            fn fold_var(&mut self, name: String) -> Ast {
+                Ast::Var(self.0.get(&name).cloned().unwrap_or(name))
+            }
+        }
+
+        Renamer(map).fold(self.clone())
+    }
+
+    /// Rewrites the expression into a canonical form, so that formulas that
+    /// differ only in spelling compare equal:
+    ///
+    /// - operands of chained `+`, `*`, `and` and `or` are flattened and sorted,
+    ///   numbers first, then variables, then compound terms;
+    /// - the items of an `in` list are sorted and deduplicated;
+    /// - `-0` becomes `0` and a doubly negated literal becomes the literal;
+    /// - parentheses are kept only where precedence requires them.
+    ///
+    /// The canonical form evaluates to the same value as the original unless
+    /// reordering changes which intermediate result overflows.
+    pub fn canonicalize(&self) -> Ast {
+        self.normalize().parenthesize()
+    }
+
+    /// The [`Ast::canonicalize`]d expression as infix text, e.g. `2 * (x + y)`
+    /// for both `(y + x) * 2` and `2 * (x + (y))`.
+    pub fn canonical_string(&self) -> String {
+        self.canonicalize().to_string()
+    }
+
+    /// Whether the two expressions compute the same thing. They are if their
+    /// [`Ast::canonicalize`]d forms match, so `a * (b + c)` is equivalent to
+    /// `(c + b) * a`. Otherwise both are evaluated at
+    /// [`EQUIVALENCE_TRIALS`] pseudo-random assignments of small integers to
+    /// their variables, which catches rewrites canonicalization does not know
+    /// about, such as `2 * x` for `x + x`.
+    ///
+    /// The sampling is deterministic but not a proof: a `true` from it means
+    /// no assignment tried told the two apart. Assignments where both fail are
+    /// skipped, and if every one fails the expressions are not considered
+    /// equivalent, since nothing was compared.
+    pub fn equivalent_to(&self, other: &Ast) -> bool {
+        if self.canonicalize() == other.canonicalize() {
+            return true;
+        }
+
+        let vars = &self.variables() | &other.variables();
+        let options = EvalOptions::default();
+        let mut rng = Rng::new(EQUIVALENCE_TRIALS as u64);
+        let mut compared = false;
+        for _ in 0..EQUIVALENCE_TRIALS {
+            let mut context = Context::new();
+            for var in &vars {
+                context.set(var.as_str(), rng.range(-100, 100));
+            }
+            match (
+                self.eval_with(&options, &context),
+                other.eval_with(&options, &context),
+            ) {
+                (Ok(lhs), Ok(rhs)) if lhs == rhs => compared = true,
+                (Err(_), Err(_)) => {}
+                _ => return false,
+            }
+        }
+        compared
+    }
+
+    /// The names of the variables the expression reads, in sorted order.
+    pub fn variables(&self) -> BTreeSet<String> {
+        struct Vars(BTreeSet<String>);
+
+        impl Visitor for Vars {
+            fn visit_var(&mut self, name: &str) {
+                self.0.insert(name.to_string());
+            }
+        }
+
+        let mut vars = Vars(BTreeSet::new());
+        vars.visit(self);
+        vars.0
+    }
+
+    /// Replaces every occurrence of the variable `name` with `replacement`,
+    /// e.g. to plug one formula into another. Parentheses need no care:
+    /// substituting `a + b` for `x` in `x * 2` prints as `(a + b) * 2`.
+    pub fn substitute(&self, name: &str, replacement: &Ast) -> Ast {
+        self.substitute_all(&HashMap::from([(name, replacement)]))
+    }
+
+    // Replaces all the variables in `bindings` at once.
+    fn substitute_all(&self, bindings: &HashMap<&str, &Ast>) -> Ast {
+        struct Substituter<'a>(&'a HashMap<&'a str, &'a Ast>);
+
+        impl Folder for Substituter<'_> {
+            fn fold_var(&mut self, name: String) -> Ast {
+                match self.0.get(name.as_str()) {
+                    Some(replacement) => (*replacement).clone(),
+                    None => Ast::Var(name),
+                }
+            }
+        }
+
+        Substituter(bindings).fold(self.clone())
+    }
+
+    /// Replaces calls to functions of at most [`INLINE_LIMIT`] nodes with
+    /// their bodies, the arguments substituted for the parameters, so that
+    /// evaluation skips the call and [`Ast::fold_constants`] can see through
+    /// it. Calls to unknown functions or with the wrong number of arguments
+    /// are kept and still fail when evaluated.
+    ///
+    /// The arguments are substituted into a body before the calls it makes
+    /// are inlined, so the parameters of one function never capture the
+    /// variables another function's body reads from the context. Inlining
+    /// still changes what is evaluated: an argument the body never uses is
+    /// no longer evaluated, so its errors vanish, and under a modulus an
+    /// argument used as an exponent is no longer reduced first.
+    pub fn inline_functions(&self, context: &Context) -> Ast {
+        Inliner { context }.fold(self.clone())
+    }
+
+    /// How deeply the expression nests: the most subexpressions open at once
+    /// when reading it left to right, which is what
+    /// [`EvalOptions::max_depth`] limits. A chain such as `1 + 2 + 3` counts
+    /// as one level however long it is; each operand on the right of an
+    /// operator, parenthesized expression, argument or list item adds one.
+    pub(crate) fn depth(&self) -> usize {
+        let deepest = |asts: &[Ast]| asts.iter().map(Ast::depth).max().unwrap_or(0);
+        match self {
+            Ast::Number(_) | Ast::Var(_) => 1,
+            Ast::Interval { lo, hi } => 1 + lo.depth().max(hi.depth()),
+            Ast::Unary { operand, .. } => 1 + operand.depth(),
+            Ast::Binary { lhs, rhs, .. } => lhs.depth().max(1 + rhs.depth()),
+            Ast::In { needle, list } => needle.depth().max(1 + deepest(list)),
+            Ast::Call { args, .. } => 1 + deepest(args),
+            Ast::Paren(inner) => 1 + inner.depth(),
+        }
+    }
+
+    /// The number of nodes in the tree.
+    pub fn size(&self) -> usize {
+        struct Counter(usize);
+
+        impl Visitor for Counter {
+            fn visit(&mut self, ast: &Ast) {
+                self.0 += 1;
+                walk(self, ast);
+            }
+        }
+
+        let mut counter = Counter(0);
+        counter.visit(self);
+        counter.0
+    }
+
+    /// Replaces every constant subtree with its value under `options`, so
+    /// `x * (24 * 60 * 60)` becomes `x * 86400` and is not recomputed on each
+    /// evaluation. The result evaluates exactly like the original under the
+    /// same `options`: subtrees that fail (such as `1 / 0`) or whose value has
+    /// no literal spelling (`i32::MIN`) are left in place.
+    pub fn fold_constants(&self, options: &EvalOptions) -> Ast {
+        ConstantFolder { options }.fold(self.clone())
+    }
+
+    /// Applies algebraic identities until none matches: `x + 0`, `x - 0`,
+    /// `x * 1`, `x / 1`, `x // 1` and `x ^ 1` become `x` (as do `0 + x` and
+    /// `1 * x`), `0 - x` becomes `-x`, `--x` becomes `x` and `x - x` becomes
+    /// `0`.
+    ///
+    /// The rules treat variables as integers, so `x - x` is `0` even though an
+    /// interval minus itself is not, and a removed subtree's errors vanish
+    /// with it: `(1 / 0) - (1 / 0)` simplifies to `0`.
+    pub fn simplify(&self) -> Ast {
+        let mut ast = self.strip_parens();
+        loop {
+            let next = Simplifier.fold(ast.clone());
+            if next == ast {
+                return ast;
+            }
+            ast = next;
+        }
+    }
+
+    /// The tree with every [`Ast::Paren`] removed, so that trees differing
+    /// only in grouping the precedence rules already imply compare equal.
+    pub fn strip_parens(&self) -> Ast {
+        struct Stripper;
+
+        impl Folder for Stripper {
+            fn fold(&mut self, ast: Ast) -> Ast {
+                match ast {
+                    Ast::Paren(inner) => self.fold(*inner),
+                    ast => fold_children(self, ast),
+                }
+            }
+        }
+
+        Stripper.fold(self.clone())
+    }
+
+    // Whether every operand of the node is a literal, making it constant.
+    fn has_literal_operands(&self) -> bool {
+        match self {
+            Ast::Number(_) => true,
+            Ast::Var(_) => false,
+            Ast::Interval { lo, hi } => lo.is_literal() && hi.is_literal(),
+            Ast::Unary { operand, .. } => operand.is_literal(),
+            Ast::Binary { lhs, rhs, .. } => lhs.is_literal() && rhs.is_literal(),
+            Ast::In { needle, list } => needle.is_literal() && list.iter().all(Ast::is_literal),
+            // The folder has no functions to call.
+            Ast::Call { .. } => false,
+            Ast::Paren(inner) => inner.is_literal(),
+        }
+    }
+
+    fn is_literal(&self) -> bool {
+        match self {
+            Ast::Number(_) => true,
+            Ast::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => matches!(**operand, Ast::Number(_)),
+            Ast::Interval { lo, hi } => lo.is_literal() && hi.is_literal(),
+            _ => false,
+        }
+    }
+
+    // The tree spelling `value`, negative numbers as a negated literal.
+    fn literal(value: Value) -> Option<Ast> {
+        let int = |n: i32| match n.checked_neg() {
+            _ if n >= 0 => Some(Ast::Number(n)),
+            Some(n) => Some(Ast::Unary {
+                op: UnaryOp::Neg,
+                operand: Box::new(Ast::Number(n)),
+            }),
+            None => None,
+        };
+
+        match value {
+            Value::Int(n) => int(n),
+            Value::Interval(i) => Some(Ast::Interval {
+                lo: Box::new(int(i.lo())?),
+                hi: Box::new(int(i.hi())?),
+            }),
+        }
+    }
+
+    // Canonicalizes everything but parentheses, which are all dropped.
+    fn normalize(&self) -> Ast {
+        match self {
+            Ast::Number(_) | Ast::Var(_) => self.clone(),
+            Ast::Interval { lo, hi } => Ast::Interval {
+                lo: Box::new(lo.normalize()),
+                hi: Box::new(hi.normalize()),
+            },
+            Ast::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => match operand.normalize() {
+                Ast::Number(0) => Ast::Number(0),
+                Ast::Unary {
+                    op: UnaryOp::Neg,
+                    operand,
+                } if matches!(*operand, Ast::Number(_)) => *operand,
+                operand => Ast::Unary {
+                    op: UnaryOp::Neg,
+                    operand: Box::new(operand),
+                },
+            },
+            Ast::Unary { op, operand } => Ast::Unary {
+                op: *op,
+                operand: Box::new(operand.normalize()),
+            },
+            Ast::Binary { op, .. } if op.is_commutative() => {
+                let mut operands = Vec::new();
+                self.collect_operands(*op, &mut operands);
+                operands.sort_by_cached_key(Ast::sort_key);
+                operands
+                    .into_iter()
+                    .reduce(|lhs, rhs| Ast::Binary {
+                        op: *op,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    })
+                    .expect("a binary operator has operands")
+            }
+            Ast::Binary { op, lhs, rhs } => Ast::Binary {
+                op: *op,
+                lhs: Box::new(lhs.normalize()),
+                rhs: Box::new(rhs.normalize()),
+            },
+            Ast::In { needle, list } => {
+                let mut list = list.iter().map(Ast::normalize).collect::<Vec<_>>();
+                list.sort_by_cached_key(Ast::sort_key);
+                list.dedup();
+                Ast::In {
+                    needle: Box::new(needle.normalize()),
+                    list,
+                }
+            }
+            Ast::Call { name, args } => Ast::Call {
+                name: name.clone(),
+                args: args.iter().map(Ast::normalize).collect(),
+            },
+            Ast::Paren(inner) => inner.normalize(),
+        }
+    }
+
+    // Gathers the normalized operands of a chain of `op`, whatever its grouping.
+    fn collect_operands(&self, op: BinaryOp, out: &mut Vec<Ast>) {
+        match self {
+            Ast::Binary {
+                op: inner,
+                lhs,
+                rhs,
+            } if *inner == op => {
+                lhs.collect_operands(op, out);
+                rhs.collect_operands(op, out);
+            }
+            Ast::Paren(inner) => inner.collect_operands(op, out),
+            _ => out.push(self.normalize()),
+        }
+    }
+
+    fn sort_key(&self) -> (u8, i32, String) {
+        match self {
+            Ast::Number(n) => (0, *n, String::new()),
+            Ast::Var(name) => (1, 0, name.clone()),
+            _ => (2, 0, self.to_string()),
+        }
+    }
+
+    // How tightly the node holds together when it appears as an operand.
+    fn binding(&self) -> i32 {
+        match self {
+            Ast::Binary { op, .. } => Token::precedence(&op.token()),
+            Ast::In { .. } => Token::precedence(&Token::In),
+            Ast::Unary {
+                op: UnaryOp::Not, ..
+            } => Token::precedence(&Token::Not),
+            // `-a ^ b` is `-(a ^ b)`, but `-a * b` is `(-a) * b`.
+            Ast::Unary {
+                op: UnaryOp::Neg, ..
+            } => Token::precedence(&Token::Power) - 1,
+            _ => i32::MAX,
+        }
+    }
+
+    // Wraps operands of a paren-free tree in `Paren` where printing them bare
+    // would parse differently.
+    pub(crate) fn parenthesize(self) -> Ast {
+        let wrap = |ast: Ast, needed: bool| {
+            if needed {
+                Ast::Paren(Box::new(ast))
+            } else {
+                ast
+            }
+        };
+
+        match self {
+            Ast::Number(_) | Ast::Var(_) | Ast::Paren(_) => self,
+            Ast::Interval { lo, hi } => Ast::Interval {
+                lo: Box::new(lo.parenthesize()),
+                hi: Box::new(hi.parenthesize()),
+            },
+            Ast::Unary { op, operand } => {
+                let operand = operand.parenthesize();
+                let needed = match (op, &operand) {
+                    (
+                        UnaryOp::Neg,
+                        Ast::Unary {
+                            op: UnaryOp::Neg, ..
+                        },
+                    ) => false,
+                    (UnaryOp::Neg, operand) => operand.binding() < Token::precedence(&Token::Power),
+                    (UnaryOp::Not, Ast::Unary { .. }) => false,
+                    (UnaryOp::Not, operand) => operand.binding() < Token::precedence(&Token::In),
+                };
+                Ast::Unary {
+                    op,
+                    operand: Box::new(wrap(operand, needed)),
+                }
+            }
+            Ast::Binary { op, lhs, rhs } => {
+                let (lhs, rhs) = (lhs.parenthesize(), rhs.parenthesize());
+                let prec = Token::precedence(&op.token());
+                let right = op.token().assoc() == ASSOC_RIGHT;
+                let lhs_needed = lhs.binding() < prec || (lhs.binding() == prec && right);
+                let rhs_needed = match &rhs {
+                    Ast::Unary {
+                        op: UnaryOp::Neg, ..
+                    } => false,
+                    rhs => rhs.binding() < prec || (rhs.binding() == prec && !right),
+                };
+                Ast::Binary {
+                    op,
+                    lhs: Box::new(wrap(lhs, lhs_needed)),
+                    rhs: Box::new(wrap(rhs, rhs_needed)),
+                }
+            }
+            Ast::In { needle, list } => {
+                let needle = needle.parenthesize();
+                let needed = needle.binding() < Token::precedence(&Token::In);
+                Ast::In {
+                    needle: Box::new(wrap(needle, needed)),
+                    list: list.into_iter().map(Ast::parenthesize).collect(),
+                }
+            }
+            Ast::Call { name, args } => Ast::Call {
+                name,
+                args: args.into_iter().map(Ast::parenthesize).collect(),
+            },
+        }
+    }
+
+    /// Writes the expression in postfix order, e.g. `2 3 4 + *`. Unary minus
+    /// is spelled `neg`, an interval `lo hi interval`, membership `in/N`
+    /// where `N` counts the needle and the list items, and a call `name/N`
+    /// where `N` counts the arguments.
+    pub fn to_rpn(&self) -> String {
+        let mut out = Vec::new();
+        self.write_rpn(&mut out);
+        out.join(" ")
+    }
+
+    fn write_rpn(&self, out: &mut Vec<String>) {
+        match self {
+            Ast::Number(n) => out.push(n.to_string()),
+            Ast::Var(name) => out.push(name.clone()),
+            Ast::Interval { lo, hi } => {
+                lo.write_rpn(out);
+                hi.write_rpn(out);
+                out.push("interval".into());
+            }
+            Ast::Unary { op, operand } => {
+                operand.write_rpn(out);
+                out.push(op.symbol().into());
+            }
+            Ast::Binary { op, lhs, rhs } => {
+                lhs.write_rpn(out);
+                rhs.write_rpn(out);
+                out.push(op.symbol().into());
+            }
+            Ast::In { needle, list } => {
+                needle.write_rpn(out);
+                for item in list {
+                    item.write_rpn(out);
+                }
+                out.push(format!("in/{}", list.len() + 1));
+            }
+            Ast::Call { name, args } => {
+                for arg in args {
+                    arg.write_rpn(out);
+                }
+                out.push(format!("{}/{}", name, args.len()));
+            }
+            Ast::Paren(inner) => inner.write_rpn(out),
+        }
+    }
+}
+
+/// Writes the expression in infix notation with a space around each binary
+/// operator and only the parentheses that precedence and associativity
+/// require, so `((1)+(2*3))` prints as `1 + 2 * 3`. The output parses back to
+/// the same tree up to [`Ast::strip_parens`].
+impl Display for Ast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Infix(&self.strip_parens().parenthesize()))
+    }
+}
+
+struct ConstantFolder<'a> {
+    options: &'a EvalOptions,
+}
+
+impl Folder for ConstantFolder<'_> {
+    fn fold(&mut self, ast: Ast) -> Ast {
+        let ast = match ast {
+            // Exponents are evaluated without the modulus, so fold them so too.
+            Ast::Binary {
+                op: BinaryOp::Pow,
+                lhs,
+                rhs,
+            } if self.options.modulus.is_some() => {
+                let plain = EvalOptions {
+                    modulus: None,
+                    ..self.options.clone()
+                };
+                Ast::Binary {
+                    op: BinaryOp::Pow,
+                    lhs: Box::new(self.fold(*lhs)),
+                    rhs: Box::new(ConstantFolder { options: &plain }.fold(*rhs)),
+                }
+            }
+            ast => fold_children(self, ast),
+        };
+
+        if !ast.has_literal_operands() {
+            return ast;
+        }
+        match ast.eval_with(self.options, &Context::new()) {
+            Ok(value) => Ast::literal(value).unwrap_or(ast),
+            Err(_) => ast,
+        }
+    }
+}
+
+struct Simplifier;
+
+impl Folder for Simplifier {
+    fn fold_unary(&mut self, op: UnaryOp, operand: Ast) -> Ast {
+        match (op, operand) {
+            (
+                UnaryOp::Neg,
+                Ast::Unary {
+                    op: UnaryOp::Neg,
+                    operand,
+                },
+            ) => *operand,
+            (op, operand) => Ast::Unary {
+                op,
+                operand: Box::new(operand),
+            },
+        }
+    }
+
+    fn fold_binary(&mut self, op: BinaryOp, lhs: Ast, rhs: Ast) -> Ast {
+        use BinaryOp::*;
+
+        match (op, lhs, rhs) {
+            (Add | Sub, lhs, Ast::Number(0)) => lhs,
+            (Add, Ast::Number(0), rhs) => rhs,
+            (Sub, Ast::Number(0), rhs) => Ast::Unary {
+                op: UnaryOp::Neg,
+                operand: Box::new(rhs),
+            },
+            (Sub, lhs, rhs) if lhs == rhs => Ast::Number(0),
+            (Mul | Div | FloorDiv | Pow, lhs, Ast::Number(1)) => lhs,
+            (Mul, Ast::Number(1), rhs) => rhs,
+            (op, lhs, rhs) => Ast::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+        }
+    }
+}
+
+/// Functions with bodies of at most this many nodes are inlined by
+/// [`Ast::inline_functions`].
+pub const INLINE_LIMIT: usize = 32;
+
+/// How many variable assignments [`Ast::equivalent_to`] tries when the
+/// canonical forms differ.
+pub const EQUIVALENCE_TRIALS: usize = 64;
+
+/// How tall a syntax tree [`parse`](crate::parse) builds, counting each
+/// operator of a chain such as `1 + 2 + 3` as a level. The `Ast` passes
+/// recurse over the tree, so taller input fails with
+/// [`ExprError::DepthLimitExceeded`] rather than overflowing the stack;
+/// [`Expr::eval`](crate::Expr::eval) takes it whatever its depth.
+pub const TREE_DEPTH_LIMIT: usize = 256;
+
+struct Inliner<'a> {
+    context: &'a Context,
+}
+
+impl Folder for Inliner<'_> {
+    fn fold(&mut self, ast: Ast) -> Ast {
+        let ast = fold_children(self, ast);
+        let Ast::Call { name, args } = &ast else {
+            return ast;
+        };
+        let Some(function) = self.context.function(name) else {
+            return ast;
+        };
+        if args.len() != function.params().len() || function.body().size() > INLINE_LIMIT {
+            return ast;
+        }
+
+        // Substituting first keeps the parameters from capturing the free
+        // variables of the bodies inlined into this one. Definitions cannot
+        // be recursive, so this terminates.
+        let bindings = function
+            .params()
+            .iter()
+            .map(String::as_str)
+            .zip(args)
+            .collect();
+        let body = function.body().substitute_all(&bindings);
+        self.fold(body)
+    }
+}
+
+// Prints a tree exactly as it is, parentheses included.
+pub(crate) struct Infix<'a>(pub(crate) &'a Ast);
+
+impl Display for Infix<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Ast::Number(n) => write!(f, "{}", n),
+            Ast::Var(name) => write!(f, "{}", name),
+            Ast::Interval { lo, hi } => write!(f, "[{}, {}]", Infix(lo), Infix(hi)),
+            Ast::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => write!(f, "-{}", Infix(operand)),
+            Ast::Unary {
+                op: UnaryOp::Not,
+                operand,
+            } => write!(f, "not {}", Infix(operand)),
+            Ast::Binary { op, lhs, rhs } => {
+                write!(f, "{} {} {}", Infix(lhs), op.symbol(), Infix(rhs))
+            }
+            Ast::In { needle, list } => write!(f, "{} in ({})", Infix(needle), List(list)),
+            Ast::Call { name, args } => write!(f, "{}({})", name, List(args)),
+            Ast::Paren(inner) => write!(f, "({})", Infix(inner)),
+        }
+    }
+}
+
+// Prints trees separated by commas.
+struct List<'a>(&'a [Ast]);
+
+impl Display for List<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", Infix(item))?;
+        }
+        Ok(())
+    }
+}
+
+// Syntax trees have no node for operators added to an `OperatorTable`.
+fn custom_unsupported(token: &Token) -> ExprError {
+    ExprError::Unsupported(format!(
+        "custom operator '{}' in a syntax tree",
+        token.symbol()
+    ))
+}
+
+/// Builds an [`Ast`] from source text with the same grammar `Expr` evaluates.
+pub(crate) struct Parser<'a> {
+    src: &'a str,
+    iter: Peekable<Tokenizer<'a>>,
+    depth: usize,
+    max_depth: Option<usize>,
+    // The height of the subtree parsed last, see `Parser::grow`.
+    height: usize,
+    precedence: PrecedenceTable,
+    implicit_mul: bool,
+    // The token consumed last; errors at the end of the input point right
+    // after it.
+    last: Span,
+    // Errors recorded so far when recovering, see `Parser::recover`.
+    errors: Option<Vec<ExprError>>,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(src: &'a str, operators: OperatorTable) -> Self {
+        Self::from_tokens(src, Tokenizer::with_operators(src, operators).peekable())
+    }
+
+    /// Parses from tokens an [`Expr`](crate::Expr) has not consumed yet.
+    pub(crate) fn from_tokens(src: &'a str, iter: Peekable<Tokenizer<'a>>) -> Self {
+        Self {
+            src,
+            iter,
+            depth: 0,
+            max_depth: None,
+            height: 0,
+            precedence: PrecedenceTable::default(),
+            implicit_mul: false,
+            last: Span::default(),
+            errors: None,
+        }
+    }
+
+    /// Reads the input as `options` say: with their precedences and
+    /// implicit multiplication, failing with
+    /// [`ExprError::DepthLimitExceeded`] on input nested deeper than their
+    /// depth limit, see [`Ast::depth`].
+    pub(crate) fn with_options(mut self, options: &EvalOptions) -> Self {
+        self.max_depth = options.max_depth;
+        self.precedence = options.precedence.clone();
+        self.implicit_mul = options.implicit_mul;
+        self
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.iter.peek().map(|token| &token.value)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.iter.next()?;
+        self.last = token.span;
+        Some(token.value)
+    }
+
+    // The source of the token consumed last.
+    fn last_text(&self) -> &'a str {
+        &self.src[self.last.start..self.last.end]
+    }
+
+    // The next token, or right after the last one, where errors point.
+    fn here(&mut self) -> Span {
+        let end = self.last.end;
+        match self.iter.peek() {
+            Some(token) => token.span,
+            None => Span { start: end, end },
+        }
+    }
+
+    // Fails with `err`. When recovering the error is recorded instead, and
+    // the caller carries on as if the input had been what it expected.
+    fn report(&mut self, err: ExprError) -> Result<()> {
+        match &mut self.errors {
+            Some(errors) => {
+                // One mistake often trips several checks at the same spot.
+                if errors.last().and_then(ExprError::span) != err.span() {
+                    errors.push(err);
+                }
+                Ok(())
+            }
+            None => Err(err),
+        }
+    }
+
+    fn unexpected(&mut self, expected: &'static str) -> Result<()> {
+        let err = ExprError::unexpected(self.src, self.here(), expected);
+        self.report(err)
+    }
+
+    // Consumes `token`, or reports that `expected` is not next.
+    fn expect(&mut self, token: Token, expected: &'static str) -> Result<()> {
+        if self.peek() == Some(&token) {
+            self.next_token();
+            return Ok(());
+        }
+        self.unexpected(expected)
+    }
+
+    fn close_paren(&mut self) -> Result<()> {
+        if self.peek() == Some(&Token::RightParen) {
+            self.next_token();
+            return Ok(());
+        }
+        let err = ExprError::unclosed(self.here());
+        self.report(err)
+    }
+
+    // Records that the subtree parsed last is `height` tall, failing once
+    // that is over `TREE_DEPTH_LIMIT`.
+    fn grow(&mut self, height: usize) -> Result<()> {
+        if height > TREE_DEPTH_LIMIT {
+            return Err(ExprError::DepthLimitExceeded {
+                limit: TREE_DEPTH_LIMIT,
+            });
+        }
+        self.height = height;
+        Ok(())
+    }
+
+    pub(crate) fn parse(self) -> Result<Ast> {
+        self.parse_view().map(|view| view.to_ast())
+    }
+
+    pub(crate) fn parse_view(mut self) -> Result<AstView<'a>> {
+        self.parse_all()
+    }
+
+    /// Parses the whole input even if it is malformed, returning every error
+    /// rather than the first. After an error the parser skips or assumes
+    /// tokens to get back in step, leaving `0` in place of missing operands.
+    pub(crate) fn recover(mut self) -> (Ast, Vec<ExprError>) {
+        self.errors = Some(Vec::new());
+        let ast = self.parse_all();
+        let mut errors = self.errors.take().unwrap_or_default();
+        match ast {
+            Ok(ast) => (ast.to_ast(), errors),
+            // Only exceeding the depth limit stops a recovering parse.
+            Err(err) => {
+                errors.push(err);
+                (Ast::Number(0), errors)
+            }
+        }
+    }
+
+    fn parse_all(&mut self) -> Result<AstView<'a>> {
+        let mut ast = self.parse_expr(1)?;
+
+        while self.peek().is_some() {
+            self.unexpected("operator or end of input")?;
+            // Recovering: drop the stray token and go on with any operators
+            // after it.
+            self.next_token();
+            ast = self.parse_operators(ast, 1)?;
+        }
+
+        Ok(ast)
+    }
+
+    fn parse_atom(&mut self) -> Result<AstView<'a>> {
+        if !self.peek().is_some_and(Token::starts_operand) {
+            let (src, span) = (self.src, self.here());
+            let err = Token::operand_error(self.peek(), src, span);
+            self.report(err)?;
+            // Recovering: skip to the operand, unless it is missing altogether.
+            while self
+                .peek()
+                .is_some_and(|token| !token.starts_operand() && !token.ends_operand())
+            {
+                self.next_token();
+            }
+            if !self.peek().is_some_and(Token::starts_operand) {
+                self.grow(1)?;
+                return Ok(AstView::Number { text: "", value: 0 });
+            }
+        }
+
+        let atom = match self.next_token() {
+            Some(Token::Minus) => AstView::Unary {
+                op: UnaryOp::Neg,
+                operand: Box::new(
+                    self.parse_expr(self.precedence.operand_precedence(&Token::Minus))?,
+                ),
+            },
+            Some(Token::Not) => AstView::Unary {
+                op: UnaryOp::Not,
+                operand: Box::new(
+                    self.parse_expr(self.precedence.operand_precedence(&Token::Not))?,
+                ),
+            },
+            Some(token @ Token::Custom { .. }) => return Err(custom_unsupported(&token)),
+            Some(Token::Number(value)) => {
+                self.height = 0;
+                AstView::Number {
+                    text: self.last_text(),
+                    value,
+                }
+            }
+            Some(Token::Ident(_)) if self.peek() == Some(&Token::LeftParen) => {
+                let name = self.last_text();
+                self.next_token(); // consume '('
+                AstView::Call {
+                    name,
+                    args: self.parse_args()?,
+                }
+            }
+            Some(Token::Ident(_)) => {
+                self.height = 0;
+                AstView::Var(self.last_text())
+            }
+            Some(Token::LeftBracket) => {
+                let lo = self.parse_expr(1)?;
+                let lo_height = self.height;
+                self.expect(Token::Comma, "','")?;
+                let hi = self.parse_expr(1)?;
+                self.height = self.height.max(lo_height);
+                self.expect(Token::RightBracket, "']'")?;
+                AstView::Interval {
+                    lo: Box::new(lo),
+                    hi: Box::new(hi),
+                }
+            }
+            // `(`, the only other token that starts an operand.
+            _ => {
+                let inner = self.parse_expr(1)?;
+                self.close_paren()?;
+                AstView::Paren(Box::new(inner))
+            }
+        };
+        // `self.height` is that of the tallest operand, or 0 for a leaf.
+        self.grow(self.height + 1)?;
+        Ok(atom)
+    }
+
+    fn parse_expr(&mut self, min_prec: i32) -> Result<AstView<'a>> {
+        self.depth += 1;
+        if let Some(limit) = self.max_depth.filter(|&limit| self.depth > limit) {
+            return Err(ExprError::DepthLimitExceeded { limit });
+        }
+        // Each level of nesting adds at least one to the height, so this
+        // also bounds the parser's own recursion.
+        if self.depth > TREE_DEPTH_LIMIT {
+            return Err(ExprError::DepthLimitExceeded {
+                limit: TREE_DEPTH_LIMIT,
+            });
+        }
+        let lhs = self.parse_atom()?;
+        let ast = self.parse_operators(lhs, min_prec)?;
+        self.depth -= 1;
+        Ok(ast)
+    }
+
+    // Applies the operators that follow `lhs` and bind at least as tightly
+    // as `min_prec`. `self.height` is that of `lhs` on entry.
+    fn parse_operators(&mut self, mut lhs: AstView<'a>, min_prec: i32) -> Result<AstView<'a>> {
+        while let Some(token) = self.peek().cloned() {
+            let lhs_height = self.height;
+            if self.implicit_mul && token.follows_implicitly() {
+                if self.precedence.precedence(&Token::Multiply) < min_prec {
+                    break;
+                }
+                let rhs = self.parse_expr(self.precedence.rhs_precedence(&Token::Multiply))?;
+                self.grow(self.height.max(lhs_height) + 1)?;
+                lhs = AstView::Binary {
+                    op: BinaryOp::Mul,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                };
+                continue;
+            }
+            if !token.is_operator() || self.precedence.precedence(&token) < min_prec {
+                break;
+            }
+            self.next_token();
+
+            if token == Token::In {
+                let list = self.parse_list()?;
+                self.grow(self.height.max(lhs_height) + 1)?;
+                lhs = AstView::In {
+                    needle: Box::new(lhs),
+                    list,
+                };
+                continue;
+            }
+
+            let op = BinaryOp::from_token(&token).ok_or_else(|| custom_unsupported(&token))?;
+            let rhs = self.parse_expr(self.precedence.rhs_precedence(&token))?;
+            self.grow(self.height.max(lhs_height) + 1)?;
+            lhs = AstView::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    // Parses the `(a, b, ...)` list after `in`
+    fn parse_list(&mut self) -> Result<Vec<AstView<'a>>> {
+        if self.peek() != Some(&Token::LeftParen) {
+            self.unexpected("'(' after 'in'")?;
+            self.height = 0;
+            return Ok(Vec::new());
+        }
+        self.next_token();
+        self.parse_items()
+    }
+
+    // Parses the arguments of a call, after its `(`
+    fn parse_args(&mut self) -> Result<Vec<AstView<'a>>> {
+        if self.peek() == Some(&Token::RightParen) {
+            self.next_token();
+            self.height = 0;
+            return Ok(Vec::new());
+        }
+        self.parse_items()
+    }
+
+    // Parses `a, b, ...)` up to and including the closing parenthesis,
+    // leaving the height of the tallest item in `self.height`.
+    fn parse_items(&mut self) -> Result<Vec<AstView<'a>>> {
+        let mut list = Vec::new();
+        let mut height = 0;
+        loop {
+            list.push(self.parse_expr(1)?);
+            height = height.max(self.height);
+            match self.peek() {
+                Some(Token::Comma) => {
+                    self.next_token();
+                }
+                _ => {
+                    self.close_paren()?;
+                    self.height = height;
+                    return Ok(list);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DivisionMode, OverflowPolicy};
+
+    fn rpn(src: &str) -> String {
+        Parser::new(src, OperatorTable::default())
+            .parse()
+            .unwrap()
+            .to_rpn()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Parser::new("-x ^ 2", OperatorTable::default()).parse(),
+            Ok(Ast::Unary {
+                op: UnaryOp::Neg,
+                operand: Box::new(Ast::Binary {
+                    op: BinaryOp::Pow,
+                    lhs: Box::new(Ast::Var("x".into())),
+                    rhs: Box::new(Ast::Number(2)),
+                }),
+            })
+        );
+        assert!(Parser::new("1 + 2 *", OperatorTable::default())
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_to_rpn() {
+        assert_eq!(rpn("2 * (3 + 4)"), "2 3 4 + *");
+        assert_eq!(rpn("1 + 2 * 3 - 4"), "1 2 3 * + 4 -");
+        assert_eq!(rpn("2 ^ 3 ^ 2"), "2 3 2 ^ ^");
+        assert_eq!(rpn("-x // 2 mod 3"), "x neg 2 // 3 %");
+        assert_eq!(rpn("not x in (1, y)"), "x 1 y in/3 not");
+        assert_eq!(rpn("[1, 2] * 3"), "1 2 interval 3 *");
+    }
+
+    #[test]
+    fn test_eval() {
+        let sources = [
+            "2 * (3 + 4) - -1",
+            "2 ^ 3 ^ 2",
+            "-7 // 2 mod 3",
+            "not 5 in (1, 2) and 1",
+            "x * rate + 1",
+            "x + y",
+            "3 ^ (5 + 5)",
+            "1 / 0",
+            "2147483647 + 1",
+            "[1, 2] * 3",
+        ];
+        let options = [
+            EvalOptions::default(),
+            EvalOptions::default().modulus(7),
+            EvalOptions::default().intervals(true),
+        ];
+        let mut context = Context::new();
+        context.set("x", 3);
+        context.set("rate", 2);
+
+        for src in sources {
+            let ast = Parser::new(src, OperatorTable::default()).parse().unwrap();
+            for options in &options {
+                // The tree keeps no positions to point parse errors at.
+                let expected = crate::Expr::with_options(src, options.clone())
+                    .with_context(&context)
+                    .eval()
+                    .map_err(|err| match err {
+                        ExprError::UnexpectedToken {
+                            found, expected, ..
+                        } => ExprError::UnexpectedToken {
+                            found,
+                            expected,
+                            span: None,
+                        },
+                        err => err,
+                    });
+                assert_eq!(ast.eval_with(options, &context), expected, "{}", src);
+            }
+        }
+
+        let ast = Parser::new("x * x", OperatorTable::default())
+            .parse()
+            .unwrap();
+        for x in 1..4 {
+            context.set("x", x);
+            assert_eq!(
+                ast.eval_with(&EvalOptions::default(), &context),
+                Ok(Value::Int(x * x))
+            );
+        }
+        assert_eq!(ast.eval(), Err(ExprError::UnknownVariable("x".into())));
+    }
+
+    #[test]
+    fn test_rename() {
+        let map = [("x", "y"), ("y", "x"), ("rate", "r")]
+            .into_iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect();
+        let ast = Parser::new("x * rate - (y in (x, z))", OperatorTable::default())
+            .parse()
+            .unwrap();
+        assert_eq!(ast.rename(&map).to_string(), "y * r - (x in (y, z))");
+        assert_eq!(ast.rename(&HashMap::new()), ast);
+    }
+
+    #[test]
+    fn test_substitute() {
+        let parse = |src| Parser::new(src, OperatorTable::default()).parse().unwrap();
+        let substitute =
+            |src, name, replacement| parse(src).substitute(name, &parse(replacement)).to_string();
+
+        assert_eq!(substitute("x * 2", "x", "a + b"), "(a + b) * 2");
+        assert_eq!(substitute("-x ^ 2", "x", "-3"), "-(-3) ^ 2");
+        assert_eq!(substitute("2 ^ x", "x", "y ^ z"), "2 ^ y ^ z");
+        assert_eq!(substitute("1 - x", "x", "y - z"), "1 - (y - z)");
+        assert_eq!(substitute("x + y", "z", "1"), "x + y");
+
+        let mut context = Context::new();
+        context.set("a", 4);
+        context.set("b", 5);
+        let ast = parse("x * x - 1").substitute("x", &parse("a + b"));
+        assert_eq!(
+            ast.eval_with(&EvalOptions::default(), &context),
+            Ok(Value::Int(80))
+        );
+        assert_eq!(parse(&ast.to_string()).strip_parens(), ast.strip_parens());
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let fold = |src, options: &EvalOptions| {
+            Parser::new(src, OperatorTable::default())
+                .parse()
+                .unwrap()
+                .fold_constants(options)
+                .to_string()
+        };
+        let default = EvalOptions::default();
+
+        assert_eq!(fold("x * (24 * 60 * 60)", &default), "x * 86400");
+        assert_eq!(fold("(1 + 2) * (x + 3 * 4)", &default), "3 * (x + 12)");
+        assert_eq!(
+            fold("2 - 5 + x in (1 + 1, y)", &default),
+            "-3 + x in (2, y)"
+        );
+        assert_eq!(fold("x + 1 / 0", &default), "x + 1 / 0");
+        assert_eq!(fold("x + 2147483647 + 1", &default), "x + 2147483647 + 1");
+        assert_eq!(
+            fold("x + (2147483647 + 1)", &default),
+            "x + (2147483647 + 1)"
+        );
+
+        let saturating = EvalOptions::default().overflow(OverflowPolicy::Saturating);
+        assert_eq!(fold("x + (2147483647 + 1)", &saturating), "x + 2147483647");
+        let floor = EvalOptions::default().division(DivisionMode::Floor);
+        assert_eq!(fold("x * (-7 / 2)", &floor), "x * -4");
+        let modular = EvalOptions::default().modulus(7);
+        assert_eq!(fold("x ^ (5 + 5) + 10", &modular), "x ^ 10 + 3");
+        let intervals = EvalOptions::default().intervals(true);
+        assert_eq!(fold("x * ([1, 2] - 3)", &intervals), "x * [-2, -1]");
+        assert_eq!(fold("x * [1, 2]", &default), "x * [1, 2]");
+
+        let mut context = Context::new();
+        context.set("x", 5);
+        for src in [
+            "x ^ (1 + 1) * (3 - 10 % 4)",
+            "-(2 * 3) - x",
+            "not (1 and 0) or x",
+        ] {
+            let ast = Parser::new(src, OperatorTable::default()).parse().unwrap();
+            for options in [&default, &floor, &modular] {
+                assert_eq!(
+                    ast.fold_constants(options).eval_with(options, &context),
+                    ast.eval_with(options, &context),
+                    "{}",
+                    src
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_inline_functions() {
+        let parse = |src| Parser::new(src, OperatorTable::default()).parse().unwrap();
+        let mut context = Context::new();
+        context.define_function("sq", &["x"], "x * x").unwrap();
+        context
+            .define_function("hyp", &["a", "b"], "sq(a) + sq(b)")
+            .unwrap();
+        context
+            .define_function("swap", &["a", "b"], "a - b")
+            .unwrap();
+        let big = vec!["x"; INLINE_LIMIT].join(" + ");
+        context.define_function("big", &["x"], &big).unwrap();
+        context.define_function("g", &["n"], "y + n").unwrap();
+        context.define_function("shadow", &["y"], "g(0)").unwrap();
+        context
+            .define_function("both", &["x", "y"], "sq(y) - g(x)")
+            .unwrap();
+
+        let inline = |src| parse(src).inline_functions(&context).to_string();
+        assert_eq!(inline("sq(y + 1) - 1"), "(y + 1) * (y + 1) - 1");
+        assert_eq!(inline("hyp(3, 4)"), "3 * 3 + 4 * 4");
+        assert_eq!(inline("swap(b, a)"), "b - a");
+        assert_eq!(inline("sq(sq(2))"), "2 * 2 * (2 * 2)");
+        assert_eq!(inline("big(1)"), "big(1)");
+        assert_eq!(inline("sq(1, 2) + nope(3)"), "sq(1, 2) + nope(3)");
+        assert_eq!(
+            parse("hyp(3, 4)")
+                .inline_functions(&context)
+                .fold_constants(&EvalOptions::default())
+                .to_string(),
+            "25"
+        );
+
+        assert_eq!(inline("shadow(5)"), "y + 0");
+        assert_eq!(inline("both(y, x)"), "x * x - (y + y)");
+
+        context.set("x", 3);
+        context.set("y", 6);
+        for src in [
+            "sq(y + 1) - 1",
+            "hyp(y, 2) * swap(y, 1)",
+            "big(y)",
+            "shadow(5)",
+            "both(y, x) + both(1, 2)",
+        ] {
+            let ast = parse(src);
+            assert_eq!(
+                ast.inline_functions(&context)
+                    .eval_with(&EvalOptions::default(), &context),
+                ast.eval_with(&EvalOptions::default(), &context),
+                "{}",
+                src
+            );
+        }
+        assert_eq!(rpn("f(1, x + 2) + g()"), "1 x 2 + f/2 g/0 +");
+    }
+
+    #[test]
+    fn test_simplify() {
+        let simplify = |src| {
+            Parser::new(src, OperatorTable::default())
+                .parse()
+                .unwrap()
+                .simplify()
+                .to_string()
+        };
+
+        assert_eq!(simplify("x * 1 + 0"), "x");
+        assert_eq!(simplify("(y - y) + x ^ 1"), "x");
+        assert_eq!(simplify("1 * (a + b) - (a + (b))"), "0");
+        assert_eq!(simplify("0 - -(x // 1)"), "x");
+        assert_eq!(simplify("2 ^ (x - x + 1)"), "2");
+        assert_eq!(simplify("(x * 2) / 1 - 0"), "x * 2");
+        assert_eq!(simplify("x - y"), "x - y");
+        assert_eq!(simplify("0 - x ^ 2"), "-x ^ 2");
+        assert_eq!(simplify("1 ^ x * 0"), "1 ^ x * 0");
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let canonical = |src| {
+            Parser::new(src, OperatorTable::default())
+                .parse()
+                .unwrap()
+                .canonical_string()
+        };
+
+        assert_eq!(canonical("(y + x) * 2"), "2 * (x + y)");
+        assert_eq!(canonical("2 * (x + (y))"), "2 * (x + y)");
+        assert_eq!(canonical("c + (b + a) + 1"), "1 + a + b + c");
+        assert_eq!(canonical("((2 ^ 3)) ^ 2"), "(2 ^ 3) ^ 2");
+        assert_eq!(canonical("2 ^ (3 ^ 2)"), "2 ^ 3 ^ 2");
+        assert_eq!(canonical("(x - y) - (z - 1)"), "x - y - (z - 1)");
+        assert_eq!(canonical("(-x) ^ 2 + -(x ^ 2)"), "(-x) ^ 2 + -x ^ 2");
+        assert_eq!(canonical("- -7 * -0"), "0 * 7");
+        assert_eq!(canonical("(not x) - 1"), "(not x) - 1");
+        assert_eq!(canonical("not (x in (3, 1, 3))"), "not x in (1, 3)");
+        assert_eq!(canonical("b or (a and c)"), "b or a and c");
+        assert_eq!(canonical("2 * [1 + 1, (4)]"), "2 * [1 + 1, 4]");
+
+        for src in [
+            "(a + b) * (c - -d)",
+            "not a or b in (1, 2) and -c",
+            "(-2) ^ 2",
+        ] {
+            let ast = Parser::new(src, OperatorTable::default()).parse().unwrap();
+            let canonical = ast.canonicalize();
+            let reparsed = Parser::new(&canonical.to_string(), OperatorTable::default()).parse();
+            assert_eq!(reparsed, Ok(canonical), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_equivalent_to() {
+        let equivalent = |a: &str, b: &str| {
+            let a = Parser::new(a, OperatorTable::default()).parse().unwrap();
+            let b = Parser::new(b, OperatorTable::default()).parse().unwrap();
+            a.equivalent_to(&b)
+        };
+
+        assert!(equivalent("a * (b + c)", "(c + b) * a"));
+        assert!(equivalent("x + x", "2 * x"));
+        assert!(equivalent("(x + 1) ^ 2", "x ^ 2 + 2 * x + 1"));
+        assert!(equivalent("x - x + y", "y"));
+        assert!(equivalent("x / 1", "x"));
+        assert!(!equivalent("x - y", "y - x"));
+        assert!(!equivalent("x / 2 * 2", "x"));
+        assert!(!equivalent("x * x", "x + x"));
+        assert!(!equivalent("f(x)", "g(x)"));
+        assert!(equivalent("f(x)", "f(x)"));
+    }
+
+    #[test]
+    fn test_variables() {
+        let ast = Parser::new("b * (a + f(c)) in (a, 1)", OperatorTable::default())
+            .parse()
+            .unwrap();
+        assert_eq!(
+            ast.variables().into_iter().collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let infix = |src| {
+            Parser::new(src, OperatorTable::default())
+                .parse()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(infix("2*(3+4)"), "2 * (3 + 4)");
+        assert_eq!(infix("- -x ** 2"), "--x ^ 2");
+        assert_eq!(infix("not x in(1,y) mod 2"), "not (x in (1, y)) % 2");
+        assert_eq!(infix("[1,2]//3"), "[1, 2] // 3");
+        assert_eq!(infix("((1)+(2*3))"), "1 + 2 * 3");
+        assert_eq!(infix("(2 ^ 3) ^ (2 ^ 1)"), "(2 ^ 3) ^ 2 ^ 1");
+        assert_eq!(infix("(1 - 2) - (3 - 4)"), "1 - 2 - (3 - 4)");
+        assert_eq!(infix("(-2) ^ 2 + (-(2 ^ 2))"), "(-2) ^ 2 + -2 ^ 2");
+        assert_eq!(infix("(not x) and (y in (1))"), "not x and y in (1)");
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use crate::{CompiledExpr, EvalOptions, Expr, Result};
+
+/// Memoizes [`Expr::compile`] by source text, for embedders that see the same
+/// formulas over and over. Holds at most `capacity` expressions and evicts the
+/// least recently used one to make room. Like [`Expr::compile`], it rejects
+/// sources that use custom operators.
+///
+/// The cache can be shared between threads; lookups only hold its lock
+/// briefly, never while compiling.
+#[derive(Debug)]
+pub struct ExprCache {
+    options: EvalOptions,
+    capacity: usize,
+    lru: Mutex<Lru>,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    entries: HashMap<String, (Arc<CompiledExpr>, u64)>,
+    // Source of each entry by the tick it was last used at, oldest first.
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl ExprCache {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_options(capacity, EvalOptions::default())
+    }
+
+    /// A cache whose expressions are compiled under `options`.
+    pub fn with_options(capacity: usize, options: EvalOptions) -> Self {
+        Self {
+            options,
+            capacity,
+            lru: Mutex::default(),
+        }
+    }
+
+    /// The compiled form of `src`, compiled now if it is not cached.
+    /// Sources that fail to parse are not cached.
+    pub fn get(&self, src: &str) -> Result<Arc<CompiledExpr>> {
+        if let Some(compiled) = self.lock().touch(src) {
+            return Ok(compiled);
+        }
+
+        let compiled = Arc::new(Expr::with_options(src, self.options.clone()).compile()?);
+        if self.capacity > 0 {
+            self.lock().insert(src, compiled.clone(), self.capacity);
+        }
+        Ok(compiled)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How many expressions are cached.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        *self.lock() = Lru::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lru> {
+        // The map is never left half-updated, so a panic elsewhere while the
+        // lock was held does not make it unusable.
+        self.lru
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Lru {
+    // Looks `src` up, marking it as the most recently used.
+    fn touch(&mut self, src: &str) -> Option<Arc<CompiledExpr>> {
+        self.tick += 1;
+        let (compiled, used) = self.entries.get_mut(src)?;
+        self.order.remove(used);
+        *used = self.tick;
+        self.order.insert(self.tick, src.to_string());
+        Some(compiled.clone())
+    }
+
+    fn insert(&mut self, src: &str, compiled: Arc<CompiledExpr>, capacity: usize) {
+        // Another thread may have compiled the same source meanwhile.
+        if self.touch(src).is_some() {
+            return;
+        }
+        while self.entries.len() >= capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.entries.insert(src.to_string(), (compiled, self.tick));
+        self.order.insert(self.tick, src.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{Context, ExprError, Span, Value};
+
+    #[test]
+    fn test_expr_cache() {
+        let cache = ExprCache::new(2);
+        let ctx = Context::new();
+
+        let first = cache.get("1 + 2").unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.get("1 + 2").unwrap()));
+        assert_eq!(first.eval(&ctx), Ok(Value::Int(3)));
+
+        cache.get("2 * 3").unwrap();
+        cache.get("1 + 2").unwrap();
+        cache.get("4 - 1").unwrap();
+        assert_eq!(cache.len(), 2);
+        // `2 * 3` was the least recently used, so it went.
+        assert!(Arc::ptr_eq(&first, &cache.get("1 + 2").unwrap()));
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(
+            cache.get("1 +").unwrap_err(),
+            ExprError::UnexpectedToken {
+                found: None,
+                expected: "number or parenthesis",
+                span: Some(Span { start: 3, end: 3 })
+            }
+        );
+        assert_eq!(cache.len(), 2);
+        cache.clear();
+        assert!(cache.is_empty());
+
+        let uncached = ExprCache::new(0);
+        assert_eq!(uncached.get("7").unwrap().eval(&ctx), Ok(Value::Int(7)));
+        assert!(uncached.is_empty());
+
+        let modular = ExprCache::with_options(4, EvalOptions::default().modulus(5));
+        assert_eq!(modular.get("3 + 4").unwrap().eval(&ctx), Ok(Value::Int(2)));
+    }
+
+    #[test]
+    fn test_threads() {
+        let cache = Arc::new(ExprCache::new(8));
+        let handles = (0..4)
+            .map(|t| {
+                let cache = cache.clone();
+                thread::spawn(move || {
+                    let mut ctx = Context::new();
+                    for i in 0..100 {
+                        ctx.set("x", i);
+                        let src = format!("x * {}", i % 10);
+                        let value = cache.get(&src).unwrap().eval(&ctx).unwrap();
+                        assert_eq!(value, Value::Int(i * (i % 10)), "thread {}", t);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(cache.len(), 8);
+    }
+}
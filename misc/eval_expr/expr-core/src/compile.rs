@@ -0,0 +1,649 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    ast::{BINARY_OPS, UNARY_OPS},
+    meter::Meter,
+    Ast, BinaryOp, Context, DivisionMode, EvalOptions, ExprError, Interval, LanguageLevel,
+    OverflowPolicy, Result, UnaryOp, Value,
+};
+
+/// The first bytes of every [`CompiledExpr::to_bytes`] output.
+const MAGIC: &[u8; 4] = b"EXPR";
+
+/// Bumped whenever the instruction set or its encoding changes, so that
+/// bytes saved by another version are rejected rather than misread. Version 1
+/// predates language levels, which version 2 added; it is still read, as
+/// [`LanguageLevel::V1`].
+const BYTECODE_VERSION: u8 = 2;
+
+const OVERFLOW_POLICIES: [OverflowPolicy; 3] = [
+    OverflowPolicy::Checked,
+    OverflowPolicy::Wrapping,
+    OverflowPolicy::Saturating,
+];
+
+const DIVISION_MODES: [DivisionMode; 3] = [
+    DivisionMode::Truncating,
+    DivisionMode::Floor,
+    DivisionMode::Euclidean,
+];
+
+const LANGUAGE_LEVELS: [LanguageLevel; 2] = [LanguageLevel::V1, LanguageLevel::V2];
+
+const POSITIONS: [&str; 2] = ["interval bound", "in"];
+
+impl Ast {
+    /// Lowers the tree to a [`CompiledExpr`] for `options`, a flat list of
+    /// instructions that can be evaluated against many contexts without
+    /// walking the tree again.
+    ///
+    /// Repeated subtrees are computed once: in `(a * b) + (a * b) * c` the
+    /// product is evaluated a single time and its value reused.
+    pub fn compile(&self, options: &EvalOptions) -> CompiledExpr {
+        let mut compiler = Compiler::default();
+        compiler.compile(self, false, options);
+        CompiledExpr {
+            steps: compiler.steps,
+            options: options.clone(),
+            plain: EvalOptions {
+                modulus: None,
+                ..options.clone()
+            },
+        }
+    }
+}
+
+/// An expression compiled by [`Ast::compile`] or
+/// [`Expr::compile`](crate::Expr::compile), which evaluates like
+/// [`Ast::eval_with`] under the options it was compiled for, down to which
+/// error is reported first. The exception is [`EvalOptions::fuel`]: a
+/// repeated subtree burns fuel once, as it is computed once.
+#[derive(Debug, Clone)]
+pub struct CompiledExpr {
+    steps: Vec<Step>,
+    options: EvalOptions,
+    // `options` without the modulus, for exponents.
+    plain: EvalOptions,
+}
+
+// One instruction and the options it runs under: `plain` steps are inside an
+// exponent, where any modulus does not apply.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Step {
+    instr: Instr,
+    plain: bool,
+}
+
+// Operands are the indices of earlier steps.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Instr {
+    Number(i32),
+    Var(String),
+    /// Fails as an interval literal does when the options rule it out.
+    BadInterval,
+    Interval(usize, usize),
+    /// The operand, checked to be an integer in the named position.
+    ExpectInt(usize, &'static str),
+    Unary(UnaryOp, usize),
+    Binary(BinaryOp, usize, usize),
+    In(usize, Vec<usize>),
+    Call(String, Vec<usize>),
+}
+
+#[derive(Default)]
+struct Compiler {
+    steps: Vec<Step>,
+    seen: HashMap<Step, usize>,
+}
+
+impl Compiler {
+    // Emits the steps computing `ast` and returns the index of the last.
+    fn compile(&mut self, ast: &Ast, plain: bool, options: &EvalOptions) -> usize {
+        let instr = match ast {
+            Ast::Number(n) => Instr::Number(*n),
+            Ast::Var(name) => Instr::Var(name.clone()),
+            Ast::Interval { lo, hi }
+                if options.intervals && (plain || options.modulus.is_none()) =>
+            {
+                let lo = self.compile(lo, plain, options);
+                let lo = self.emit(Instr::ExpectInt(lo, "interval bound"), plain);
+                let hi = self.compile(hi, plain, options);
+                let hi = self.emit(Instr::ExpectInt(hi, "interval bound"), plain);
+                Instr::Interval(lo, hi)
+            }
+            Ast::Interval { .. } => Instr::BadInterval,
+            Ast::Unary { op, operand } => Instr::Unary(*op, self.compile(operand, plain, options)),
+            Ast::Binary { op, lhs, rhs } => {
+                let lhs = self.compile(lhs, plain, options);
+                let rhs = self.compile(rhs, plain || *op == BinaryOp::Pow, options);
+                Instr::Binary(*op, lhs, rhs)
+            }
+            Ast::In { needle, list } => {
+                let needle = self.compile(needle, plain, options);
+                let needle = self.emit(Instr::ExpectInt(needle, "in"), plain);
+                let list = list
+                    .iter()
+                    .map(|item| {
+                        let item = self.compile(item, plain, options);
+                        self.emit(Instr::ExpectInt(item, "in"), plain)
+                    })
+                    .collect();
+                Instr::In(needle, list)
+            }
+            Ast::Call { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.compile(arg, plain, options))
+                    .collect();
+                Instr::Call(name.clone(), args)
+            }
+            Ast::Paren(inner) => return self.compile(inner, plain, options),
+        };
+        self.emit(instr, plain)
+    }
+
+    // Appends `instr` unless an identical step already exists. Evaluation
+    // stops at the first failing step, so reusing the earlier result cannot
+    // change which error is reported.
+    fn emit(&mut self, instr: Instr, plain: bool) -> usize {
+        let step = Step { instr, plain };
+        if let Some(&index) = self.seen.get(&step) {
+            return index;
+        }
+        self.steps.push(step.clone());
+        self.seen.insert(step, self.steps.len() - 1);
+        self.steps.len() - 1
+    }
+}
+
+impl CompiledExpr {
+    /// Evaluates the expression, resolving identifiers against `context`.
+    pub fn eval(&self, context: &Context) -> Result<Value> {
+        let meter = Meter::new(&self.options);
+        let mut slots: Vec<Value> = Vec::with_capacity(self.steps.len());
+        for step in &self.steps {
+            let options = if step.plain {
+                &self.plain
+            } else {
+                &self.options
+            };
+            let value = match &step.instr {
+                Instr::Number(n) => Value::Int(match options.modulus {
+                    Some(p) => n.rem_euclid(p),
+                    None => *n,
+                }),
+                Instr::Var(name) => context
+                    .get(name)
+                    .ok_or_else(|| ExprError::UnknownVariable(name.clone()))?,
+                Instr::BadInterval if options.modulus.is_some() => {
+                    return Err(ExprError::Unsupported(
+                        "interval in modular arithmetic".into(),
+                    ))
+                }
+                Instr::BadInterval => {
+                    return Err(ExprError::UnexpectedToken {
+                        found: Some("[".into()),
+                        expected: "number or parenthesis",
+                        span: None,
+                    })
+                }
+                Instr::Interval(lo, hi) => {
+                    // Both bounds went through `ExpectInt` already.
+                    let lo = slots[*lo].expect_int("interval bound")?;
+                    let hi = slots[*hi].expect_int("interval bound")?;
+                    Value::Interval(Interval::new(lo, hi)?)
+                }
+                Instr::ExpectInt(operand, position) => {
+                    Value::Int(slots[*operand].expect_int(position)?)
+                }
+                Instr::Unary(UnaryOp::Neg, operand) => {
+                    meter.burn()?;
+                    slots[*operand].negate(options)?
+                }
+                Instr::Unary(UnaryOp::Not, operand) => {
+                    let operand = slots[*operand].expect_int("not")?;
+                    meter.burn()?;
+                    Value::Int((operand == 0) as i32)
+                }
+                Instr::Binary(op, lhs, rhs) => {
+                    meter.burn()?;
+                    op.token().apply(slots[*lhs], slots[*rhs], options)?
+                }
+                Instr::In(needle, list) => {
+                    let needle = slots[*needle];
+                    meter.burn()?;
+                    Value::Int(list.iter().any(|item| slots[*item] == needle) as i32)
+                }
+                Instr::Call(name, args) => {
+                    let args = args.iter().map(|arg| slots[*arg]).collect();
+                    meter.burn()?;
+                    context.call(name, args, options, &meter)?
+                }
+            };
+            slots.push(value);
+        }
+
+        Ok(slots
+            .pop()
+            .expect("a compiled expression has at least one step"))
+    }
+
+    /// Encodes the compiled expression, with the options it was compiled
+    /// for, so that [`CompiledExpr::from_bytes`] can restore it later, e.g.
+    /// to keep compiled formulas across restarts. The operator table is not
+    /// saved: it only matters for parsing. Neither are the
+    /// [`fuel`](EvalOptions::fuel) and [`timeout`](EvalOptions::timeout)
+    /// limits, which restored expressions run without.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(BYTECODE_VERSION);
+        out.push(index_of(&OVERFLOW_POLICIES, &self.options.overflow));
+        out.push(index_of(&DIVISION_MODES, &self.options.division));
+        out.push(self.options.intervals as u8);
+        out.push(index_of(&LANGUAGE_LEVELS, &self.options.level));
+        write_u32(&mut out, self.options.modulus.unwrap_or(0) as u32);
+
+        write_u32(&mut out, self.steps.len() as u32);
+        for step in &self.steps {
+            match &step.instr {
+                Instr::Number(n) => {
+                    out.push(0);
+                    write_u32(&mut out, *n as u32);
+                }
+                Instr::Var(name) => {
+                    out.push(1);
+                    write_str(&mut out, name);
+                }
+                Instr::BadInterval => out.push(2),
+                Instr::Interval(lo, hi) => {
+                    out.push(3);
+                    write_slots(&mut out, &[*lo, *hi]);
+                }
+                Instr::ExpectInt(operand, position) => {
+                    out.push(4);
+                    out.push(index_of(&POSITIONS, position));
+                    write_slots(&mut out, &[*operand]);
+                }
+                Instr::Unary(op, operand) => {
+                    out.push(5);
+                    out.push(index_of(&UNARY_OPS, op));
+                    write_slots(&mut out, &[*operand]);
+                }
+                Instr::Binary(op, lhs, rhs) => {
+                    out.push(6);
+                    out.push(index_of(&BINARY_OPS, op));
+                    write_slots(&mut out, &[*lhs, *rhs]);
+                }
+                Instr::In(needle, list) => {
+                    out.push(7);
+                    write_slots(&mut out, &[*needle]);
+                    write_u32(&mut out, list.len() as u32);
+                    write_slots(&mut out, list);
+                }
+                Instr::Call(name, args) => {
+                    out.push(8);
+                    write_str(&mut out, name);
+                    write_u32(&mut out, args.len() as u32);
+                    write_slots(&mut out, args);
+                }
+            }
+            out.push(step.plain as u8);
+        }
+        out
+    }
+
+    /// Restores a compiled expression saved with [`CompiledExpr::to_bytes`].
+    /// Fails with [`ExprError::InvalidBytecode`] if the bytes were written by
+    /// a different version of the format or are damaged: every instruction
+    /// must be known and may only refer to the ones before it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<CompiledExpr> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a compiled expression"));
+        }
+        let version = reader.u8()?;
+        if !(1..=BYTECODE_VERSION).contains(&version) {
+            return Err(invalid(&format!(
+                "format version {} is not 1 to {}",
+                version, BYTECODE_VERSION
+            )));
+        }
+
+        let mut options = EvalOptions {
+            overflow: reader.pick(&OVERFLOW_POLICIES)?,
+            division: reader.pick(&DIVISION_MODES)?,
+            intervals: reader.pick(&[false, true])?,
+            ..EvalOptions::default()
+        };
+        if version >= 2 {
+            options.level = reader.pick(&LANGUAGE_LEVELS)?;
+        }
+        match reader.u32()? as i32 {
+            0 => {}
+            p if p > 0 => options.modulus = Some(p),
+            _ => return Err(invalid("negative modulus")),
+        }
+
+        let len = reader.u32()? as usize;
+        let mut steps = Vec::new();
+        for i in 0..len {
+            let instr = match reader.u8()? {
+                0 => Instr::Number(reader.u32()? as i32),
+                1 => Instr::Var(reader.string()?),
+                2 => Instr::BadInterval,
+                3 => Instr::Interval(reader.slot(i)?, reader.slot(i)?),
+                4 => {
+                    let position = reader.pick(&POSITIONS)?;
+                    Instr::ExpectInt(reader.slot(i)?, position)
+                }
+                5 => Instr::Unary(reader.pick(&UNARY_OPS)?, reader.slot(i)?),
+                6 => Instr::Binary(reader.pick(&BINARY_OPS)?, reader.slot(i)?, reader.slot(i)?),
+                7 => Instr::In(reader.slot(i)?, reader.slots(i)?),
+                8 => Instr::Call(reader.string()?, reader.slots(i)?),
+                op => return Err(invalid(&format!("unknown instruction {}", op))),
+            };
+            let plain = reader.pick(&[false, true])?;
+            steps.push(Step { instr, plain });
+        }
+        if steps.is_empty() {
+            return Err(invalid("no instructions"));
+        }
+        if reader.pos != bytes.len() {
+            return Err(invalid("trailing bytes"));
+        }
+
+        Ok(CompiledExpr {
+            steps,
+            plain: EvalOptions {
+                modulus: None,
+                ..options.clone()
+            },
+            options,
+        })
+    }
+
+    /// The expression as a closure, for APIs that take an
+    /// `Fn(&Context) -> Result<Value>`.
+    pub fn into_fn(self) -> impl Fn(&Context) -> Result<Value> {
+        move |context| self.eval(context)
+    }
+}
+
+fn index_of<T: PartialEq>(table: &[T], item: &T) -> u8 {
+    table
+        .iter()
+        .position(|entry| entry == item)
+        .expect("every variant is in its table") as u8
+}
+
+fn write_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_u32(out, s.len() as u32);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_slots(out: &mut Vec<u8>, slots: &[usize]) {
+    for &slot in slots {
+        write_u32(out, slot as u32);
+    }
+}
+
+fn invalid(reason: &str) -> ExprError {
+    ExprError::InvalidBytecode(reason.to_string())
+}
+
+// Reads what `CompiledExpr::to_bytes` wrote, checking as it goes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.saturating_add(len);
+        let bytes = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or_else(|| invalid("unexpected end"))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("four bytes")))
+    }
+
+    // An entry of `table`, by its index.
+    fn pick<T: Copy>(&mut self, table: &[T]) -> Result<T> {
+        let index = self.u8()?;
+        table
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| invalid(&format!("unknown code {}", index)))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("name is not UTF-8"))
+    }
+
+    // The index of a step before step `current`.
+    fn slot(&mut self, current: usize) -> Result<usize> {
+        let slot = self.u32()? as usize;
+        if slot >= current {
+            return Err(invalid(&format!(
+                "step {} refers to step {}",
+                current, slot
+            )));
+        }
+        Ok(slot)
+    }
+
+    fn slots(&mut self, current: usize) -> Result<Vec<usize>> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.slot(current)).collect()
+    }
+}
+
+/// Lists the instructions one per line, as `%2 = %0 * %1`.
+impl Display for CompiledExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let slots = |slots: &[usize]| {
+            let slots = slots
+                .iter()
+                .map(|slot| format!("%{}", slot))
+                .collect::<Vec<_>>();
+            slots.join(", ")
+        };
+
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(f, "%{} = ", i)?;
+            match &step.instr {
+                Instr::Number(n) => write!(f, "{}", n)?,
+                Instr::Var(name) => write!(f, "{}", name)?,
+                Instr::BadInterval => write!(f, "error")?,
+                Instr::Interval(lo, hi) => write!(f, "[%{}, %{}]", lo, hi)?,
+                Instr::ExpectInt(operand, _) => write!(f, "int %{}", operand)?,
+                Instr::Unary(UnaryOp::Neg, operand) => write!(f, "-%{}", operand)?,
+                Instr::Unary(op, operand) => write!(f, "{} %{}", op.symbol(), operand)?,
+                Instr::Binary(op, lhs, rhs) => write!(f, "%{} {} %{}", lhs, op.symbol(), rhs)?,
+                Instr::In(needle, list) => write!(f, "%{} in ({})", needle, slots(list))?,
+                Instr::Call(name, args) => write!(f, "{}({})", name, slots(args))?,
+            }
+            if step.plain && self.options.modulus.is_some() {
+                write!(f, " (no modulus)")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, ErrorKind};
+
+    #[test]
+    fn test_common_subexpressions() {
+        let compiled = parse("(a * b) + (a * b) * c")
+            .unwrap()
+            .compile(&EvalOptions::default());
+        assert_eq!(
+            compiled.to_string(),
+            "%0 = a\n%1 = b\n%2 = %0 * %1\n%3 = c\n%4 = %2 * %3\n%5 = %2 + %4\n"
+        );
+
+        let compiled = parse("x in (x, 1, x)")
+            .unwrap()
+            .compile(&EvalOptions::default());
+        assert_eq!(
+            compiled.to_string(),
+            "%0 = x\n%1 = int %0\n%2 = 1\n%3 = int %2\n%4 = %1 in (%1, %3, %1)\n"
+        );
+
+        let compiled = parse("2 ^ 2 * 2")
+            .unwrap()
+            .compile(&EvalOptions::default().modulus(3));
+        assert_eq!(
+            compiled.to_string(),
+            "%0 = 2\n%1 = 2 (no modulus)\n%2 = %0 ^ %1\n%3 = %2 * %0\n"
+        );
+
+        // Fuel is burnt per step, not per node of the tree.
+        let mut ctx = Context::new();
+        ctx.set("a", 2);
+        ctx.set("b", 3);
+        let ast = parse("(a * b) + (a * b)").unwrap();
+        let options = EvalOptions::default().fuel(2);
+        assert_eq!(ast.compile(&options).eval(&ctx), Ok(Value::Int(12)));
+        assert_eq!(
+            ast.eval_with(&options, &ctx).map_err(|err| err.kind()),
+            Err(ErrorKind::BudgetExceeded)
+        );
+        let options = EvalOptions::default().fuel(3);
+        assert_eq!(ast.eval_with(&options, &ctx), Ok(Value::Int(12)));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut ctx = Context::new();
+        ctx.set("a", 3);
+        ctx.define_function("sq", &["n"], "n * n").unwrap();
+
+        for (src, options) in [
+            ("(a * 2) + (a * 2) * -a", EvalOptions::default()),
+            ("sq(a) in (9, a) and not 0", EvalOptions::default()),
+            (
+                "[a, 5] * 2 - [1, 2]",
+                EvalOptions::default().intervals(true),
+            ),
+            ("a ^ 10 / 2 + [1, 2]", EvalOptions::default().modulus(11)),
+            (
+                "a * 2000000000 - 7 / -2",
+                EvalOptions::default()
+                    .overflow(OverflowPolicy::Wrapping)
+                    .division(DivisionMode::Floor),
+            ),
+            ("-7 % a", EvalOptions::default().level(LanguageLevel::V2)),
+        ] {
+            let compiled = parse(src).unwrap().compile(&options);
+            let restored = CompiledExpr::from_bytes(&compiled.to_bytes()).unwrap();
+            assert_eq!(restored.to_string(), compiled.to_string(), "{}", src);
+            assert_eq!(restored.eval(&ctx), compiled.eval(&ctx), "{}", src);
+        }
+
+        let bytes = parse("a + 1")
+            .unwrap()
+            .compile(&EvalOptions::default())
+            .to_bytes();
+        let invalid = |bytes: &[u8]| match CompiledExpr::from_bytes(bytes) {
+            Err(ExprError::InvalidBytecode(reason)) => reason,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(invalid(b"JUNK"), "not a compiled expression");
+        assert_eq!(invalid(&bytes[..bytes.len() - 1]), "unexpected end");
+        assert_eq!(
+            invalid(&[bytes.as_slice(), &[0]].concat()),
+            "trailing bytes"
+        );
+
+        let mut future = bytes.clone();
+        future[4] = BYTECODE_VERSION + 1;
+        assert_eq!(invalid(&future), "format version 3 is not 1 to 2");
+
+        // Version 1 had no language level byte.
+        let mut old = bytes.clone();
+        old[4] = 1;
+        old.remove(8);
+        let restored = CompiledExpr::from_bytes(&old).unwrap();
+        assert_eq!(restored.options.level, LanguageLevel::V1);
+        assert_eq!(restored.eval(&ctx), Ok(Value::Int(4)));
+
+        // `%2 = %0 + %1` rewritten to refer to itself.
+        let mut cyclic = bytes.clone();
+        let lhs = bytes.len() - 9;
+        cyclic[lhs] = 2;
+        assert_eq!(invalid(&cyclic), "step 2 refers to step 2");
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut ctx = Context::new();
+        ctx.set("a", 3);
+        ctx.set("b", 4);
+        ctx.set("c", 5);
+        ctx.define_function("sq", &["n"], "n * n").unwrap();
+
+        let options = EvalOptions::default().intervals(true);
+        let modular = EvalOptions::default().modulus(7);
+        let cases: [(&str, &EvalOptions); 12] = [
+            ("(a * b) + (a * b) * c", &options),
+            ("sq(a) + sq(a) - sq(b)", &options),
+            ("not a in (1, 2, a) and -b", &options),
+            ("[a, b] * [a, b] - 1", &options),
+            ("[b, a] + d / 0", &options),
+            ("a / 0 + d", &options),
+            ("d + a / 0", &options),
+            ("[1, 2] in (1, [1, 2])", &options),
+            ("a ^ 10 ^ 10 + a * a", &options),
+            ("a ^ 10 + 10 * 2 / b", &modular),
+            ("[1, 2] + 1 / 0", &modular),
+            ("sq(c) + 2 ^ sq(c)", &modular),
+        ];
+        for (src, options) in cases {
+            let ast = parse(src).unwrap();
+            assert_eq!(
+                ast.compile(options).eval(&ctx),
+                ast.eval_with(options, &ctx),
+                "{}",
+                src
+            );
+        }
+
+        let compiled = parse("a * a + b").unwrap().compile(&EvalOptions::default());
+        assert_eq!(compiled.eval(&ctx), Ok(Value::Int(13)));
+        ctx.set("a", -2);
+        assert_eq!(compiled.eval(&ctx), Ok(Value::Int(8)));
+        assert_eq!(
+            parse("[1, 2]")
+                .unwrap()
+                .compile(&EvalOptions::default())
+                .eval(&ctx),
+            Err(ExprError::UnexpectedToken {
+                found: Some("[".into()),
+                expected: "number or parenthesis",
+                span: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::{meter::Meter, parse, visit::Visitor, Ast, EvalOptions, ExprError, Result, Value};
+
+/// Variable bindings and functions that identifiers in an expression resolve
+/// against.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    vars: HashMap<String, Value>,
+    functions: HashMap<String, Function>,
+}
+
+static PROCESS_DEFAULT: RwLock<Option<Arc<Context>>> = RwLock::new(None);
+
+thread_local! {
+    static SCOPED_DEFAULT: RefCell<Option<Arc<Context>>> = const { RefCell::new(None) };
+}
+
+/// Makes `context` what [`eval`](crate::eval) resolves identifiers against,
+/// on every thread, e.g. so that scripts can define constants once. Code
+/// that builds an [`Expr`](crate::Expr) itself is unaffected.
+pub fn set_default_context(context: Context) {
+    *PROCESS_DEFAULT
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(context));
+}
+
+/// Runs `f` with `context` in place of the default context on this thread,
+/// restoring the previous one afterwards, even if `f` panics. Handy in tests,
+/// which run in parallel and so should not change the process-wide default.
+pub fn with_default_context<R>(context: Context, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<Context>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED_DEFAULT.with(|scoped| *scoped.borrow_mut() = self.0.take());
+        }
+    }
+
+    let previous = SCOPED_DEFAULT.with(|scoped| scoped.replace(Some(Arc::new(context))));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// The context [`eval`](crate::eval) uses: this thread's scoped default if
+/// any, else the process-wide one.
+pub(crate) fn default_context() -> Option<Arc<Context>> {
+    SCOPED_DEFAULT
+        .with(|scoped| scoped.borrow().clone())
+        .or_else(|| {
+            PROCESS_DEFAULT
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        })
+}
+
+/// A user-defined function, see [`Context::define_function`].
+#[derive(Debug, Clone)]
+pub struct Function {
+    params: Vec<String>,
+    body: Ast,
+}
+
+impl Function {
+    /// A function of `params` computing `body`, to give to
+    /// [`Context::set_function`].
+    pub fn new(params: &[&str], body: &str) -> Result<Self> {
+        Ok(Function {
+            params: params.iter().map(|param| param.to_string()).collect(),
+            body: parse(body)?,
+        })
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    pub fn body(&self) -> &Ast {
+        &self.body
+    }
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds `name` to `value`, returning the value it replaced.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        self.vars.insert(name.into(), value.into())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.vars.get(name).copied()
+    }
+
+    /// Unbinds `name`, returning its value.
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.vars.remove(name)
+    }
+
+    /// Defines `name(params) = body`, replacing any function of that name.
+    /// The body sees the parameters and this context's variables, and may
+    /// call other functions, but not itself, directly or indirectly: there is
+    /// no conditional to end the recursion.
+    pub fn define_function(&mut self, name: &str, params: &[&str], body: &str) -> Result<()> {
+        self.set_function(name, Function::new(params, body)?)
+            .map(drop)
+    }
+
+    /// Binds `name` to `function`, returning the function it replaced, e.g.
+    /// to put it back later. Fails like [`Context::define_function`] if
+    /// `function` would call itself.
+    pub fn set_function(&mut self, name: &str, function: Function) -> Result<Option<Function>> {
+        if self.reaches(&function.body, name, &mut HashSet::new()) {
+            return Err(ExprError::Unsupported(format!(
+                "recursive function '{}'",
+                name
+            )));
+        }
+        Ok(self.functions.insert(name.to_string(), function))
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    /// Forgets the function `name`, returning it.
+    pub fn remove_function(&mut self, name: &str) -> Option<Function> {
+        self.functions.remove(name)
+    }
+
+    /// The variable bindings, in no particular order.
+    pub fn vars(&self) -> impl Iterator<Item = (&str, Value)> {
+        self.vars
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    /// The functions defined, in no particular order.
+    pub fn functions(&self) -> impl Iterator<Item = (&str, &Function)> {
+        self.functions
+            .iter()
+            .map(|(name, function)| (name.as_str(), function))
+    }
+
+    /// Calls `name` with already evaluated arguments, burning fuel from the
+    /// caller's `meter`.
+    pub(crate) fn call(
+        &self,
+        name: &str,
+        args: Vec<Value>,
+        options: &EvalOptions,
+        meter: &Meter,
+    ) -> Result<Value> {
+        Scope::new(self).call(name, args, options, meter)
+    }
+
+    // Whether evaluating `ast` can end up calling `target`.
+    fn reaches(&self, ast: &Ast, target: &str, seen: &mut HashSet<String>) -> bool {
+        struct Calls(Vec<String>);
+
+        impl Visitor for Calls {
+            fn visit_call(&mut self, name: &str, _args: usize) {
+                self.0.push(name.to_string());
+            }
+        }
+
+        let mut calls = Calls(Vec::new());
+        calls.visit(ast);
+        calls.0.into_iter().any(|name| {
+            name == target
+                || (seen.insert(name.clone())
+                    && self
+                        .function(&name)
+                        .is_some_and(|function| self.reaches(&function.body, target, seen)))
+        })
+    }
+}
+
+/// The functions there are without defining any: `hex`, `bin` and `oct`
+/// give their argument back, asking for the result to be written in base
+/// 16, 2 or 8, see [`Expr::radix`](crate::Expr::radix). A function defined
+/// with [`Context::define_function`] takes the place of one of the same name.
+pub const BUILTINS: [&str; 3] = ["hex", "bin", "oct"];
+
+/// Calls the builtin `name`, burning fuel from `meter`, see [`BUILTINS`].
+pub(crate) fn call_builtin(name: &str, args: Vec<Value>, meter: &Meter) -> Result<Value> {
+    let radix = match name {
+        "hex" => 16,
+        "bin" => 2,
+        "oct" => 8,
+        _ => return Err(ExprError::UnknownFunction(name.to_string())),
+    };
+    let [value] = args[..] else {
+        return Err(ExprError::WrongArity {
+            name: name.to_string(),
+            expected: 1,
+            found: args.len(),
+        });
+    };
+    meter.set_radix(radix);
+    Ok(value)
+}
+
+/// What identifiers resolve against while a syntax tree is evaluated: a
+/// context, under the parameters of the function being called, if any.
+/// Scoping is lexical: a function body sees its own parameters and the
+/// context's variables, never the parameters of its caller. Calls make a
+/// frame borrowing the arguments rather than copying the context, so a call
+/// costs the same however much the context holds.
+#[derive(Clone, Copy)]
+pub(crate) struct Scope<'a> {
+    context: &'a Context,
+    // The parameters and arguments of the call being evaluated.
+    frame: Option<(&'a [String], &'a [Value])>,
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn new(context: &'a Context) -> Self {
+        Scope {
+            context,
+            frame: None,
+        }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<Value> {
+        match self.frame {
+            Some((params, args)) => match params.iter().position(|param| param == name) {
+                Some(i) => Some(args[i]),
+                None => self.context.get(name),
+            },
+            None => self.context.get(name),
+        }
+    }
+
+    /// Calls `name` with already evaluated arguments, burning fuel from the
+    /// caller's `meter`. The body sees the arguments over the context, not
+    /// over this scope.
+    pub(crate) fn call(
+        &self,
+        name: &str,
+        args: Vec<Value>,
+        options: &EvalOptions,
+        meter: &Meter,
+    ) -> Result<Value> {
+        let Some(function) = self.context.function(name) else {
+            return call_builtin(name, args, meter);
+        };
+        if args.len() != function.params.len() {
+            return Err(ExprError::WrongArity {
+                name: name.to_string(),
+                expected: function.params.len(),
+                found: args.len(),
+            });
+        }
+
+        let scope = Scope {
+            context: self.context,
+            frame: Some((&function.params, &args)),
+        };
+        function.body.eval_metered(options, &scope, meter)
+    }
+}
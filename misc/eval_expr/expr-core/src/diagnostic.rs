@@ -0,0 +1,62 @@
+use std::fmt::Display;
+
+use miette::{Diagnostic, LabeledSpan};
+
+use crate::ExprError;
+
+/// Lets embedders that use miette report errors with their own handler.
+/// The error does not keep the source, so attach it when reporting:
+///
+/// ```text
+/// let report = miette::Report::new(err).with_source_code(src.to_string());
+/// ```
+impl Diagnostic for ExprError {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(ExprError::code(self)))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let suggestions = self.suggestions();
+        if suggestions.is_empty() {
+            return None;
+        }
+        Some(Box::new(suggestions.join(", or ")))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let span = self.span()?;
+        let label = match self {
+            Self::UnexpectedToken { expected, .. } => format!("expected {}", expected),
+            Self::UnclosedParen { .. } => "expected ')'".to_string(),
+            _ => return None,
+        };
+        Some(Box::new(std::iter::once(LabeledSpan::at(
+            span.start..span.end,
+            label,
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval, parse};
+
+    #[test]
+    fn test_diagnostic() {
+        let err = parse("1 + )").unwrap_err();
+        assert_eq!(Diagnostic::code(&err).unwrap().to_string(), "E001");
+        let labels = err.labels().unwrap().collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            [LabeledSpan::at(4..5, "expected number or parenthesis")]
+        );
+
+        let err = parse("(1 + 2").unwrap_err();
+        assert_eq!(err.help().unwrap().to_string(), "add ')'");
+
+        let err = eval("1 / 0").unwrap_err();
+        assert!(err.labels().is_none());
+        assert!(err.help().is_none());
+    }
+}
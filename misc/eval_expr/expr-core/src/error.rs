@@ -0,0 +1,410 @@
+use std::fmt::Display;
+
+use crate::{json::json_string, Span};
+
+pub type Result<T> = std::result::Result<T, ExprError>;
+
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExprError {
+    /// Malformed input: a token where something else was `expected`, or the
+    /// end of the input (a `found` of `None`) where more was. `span` is the
+    /// token, or the point where more input was expected; it is `None` when
+    /// the error was found in an [`Ast`](crate::Ast), which does not keep
+    /// positions.
+    UnexpectedToken {
+        found: Option<String>,
+        expected: &'static str,
+        span: Option<Span>,
+    },
+    /// A `(` that is not closed. `span` is where the `)` was expected.
+    UnclosedParen {
+        span: Option<Span>,
+    },
+    /// Division or remainder by zero, or by an interval containing zero.
+    DivisionByZero,
+    /// A number beyond the range of `i32`, such as `2147483648`. `span` is
+    /// the number, as for [`ExprError::UnexpectedToken`].
+    InvalidNumber {
+        span: Option<Span>,
+    },
+    Overflow {
+        op: &'static str,
+        lhs: i32,
+        rhs: i32,
+    },
+    /// An integer raised to a negative power, which has no integer result.
+    NegativeExponent {
+        base: i32,
+        exponent: i32,
+    },
+    UnknownVariable(String),
+    UnknownFunction(String),
+    /// A call with a different number of arguments than the function takes.
+    WrongArity {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidInterval {
+        lo: i32,
+        hi: i32,
+    },
+    Unsupported(String),
+    /// Division by a value with no inverse modulo the configured modulus.
+    NotInvertible {
+        value: i32,
+        modulus: i32,
+    },
+    /// Bytes passed to
+    /// [`CompiledExpr::from_bytes`](crate::CompiledExpr::from_bytes) that
+    /// are not a compiled expression from this version of the crate.
+    InvalidBytecode(String),
+    /// Text passed to [`Ast::from_json`](crate::Ast::from_json) that is not
+    /// JSON, or not an expression in a schema version this crate reads.
+    InvalidJson(String),
+    /// Input nested deeper than [`EvalOptions::max_depth`](crate::EvalOptions::max_depth).
+    DepthLimitExceeded {
+        limit: usize,
+    },
+    /// Evaluation ran past [`EvalOptions::fuel`](crate::EvalOptions::fuel) or
+    /// [`EvalOptions::timeout`](crate::EvalOptions::timeout), after burning
+    /// `fuel` units.
+    BudgetExceeded {
+        fuel: u64,
+    },
+    /// Evaluation was stopped through a
+    /// [`CancelToken`](crate::CancelToken).
+    Cancelled,
+    /// Input needed more than the `limit` `what` a
+    /// [`FixedEvaluator`](crate::FixedEvaluator) has room for.
+    CapacityExceeded {
+        what: &'static str,
+        limit: usize,
+    },
+}
+
+/// The variant of an [`ExprError`] without its details, for branching on
+/// the kind of error. See [`ErrorKind::code`] for a stable identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    UnexpectedToken,
+    DivisionByZero,
+    InvalidNumber,
+    Overflow,
+    NegativeExponent,
+    UnknownVariable,
+    UnknownFunction,
+    WrongArity,
+    InvalidInterval,
+    Unsupported,
+    NotInvertible,
+    InvalidBytecode,
+    DepthLimitExceeded,
+    BudgetExceeded,
+    Cancelled,
+    CapacityExceeded,
+    UnclosedParen,
+    InvalidJson,
+}
+
+impl ErrorKind {
+    /// A code for the kind, such as `E001` for unexpected tokens, that stays the
+    /// same across releases even if messages are reworded. New kinds get new
+    /// codes; codes are never reused.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::UnexpectedToken => "E001",
+            Self::DivisionByZero => "E002",
+            Self::InvalidNumber => "E003",
+            Self::Overflow => "E004",
+            Self::NegativeExponent => "E005",
+            Self::UnknownVariable => "E006",
+            Self::UnknownFunction => "E007",
+            Self::WrongArity => "E008",
+            Self::InvalidInterval => "E009",
+            Self::Unsupported => "E010",
+            Self::NotInvertible => "E011",
+            Self::InvalidBytecode => "E012",
+            Self::DepthLimitExceeded => "E013",
+            Self::BudgetExceeded => "E014",
+            Self::Cancelled => "E015",
+            Self::CapacityExceeded => "E016",
+            Self::UnclosedParen => "E017",
+            Self::InvalidJson => "E018",
+        }
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+impl ExprError {
+    /// A parse error at `span` in `src`, which is empty at the end of input.
+    pub(crate) fn unexpected(src: &str, span: Span, expected: &'static str) -> Self {
+        Self::UnexpectedToken {
+            found: src
+                .get(span.start..span.end)
+                .filter(|found| !found.is_empty())
+                .map(str::to_string),
+            expected,
+            span: Some(span),
+        }
+    }
+
+    pub(crate) fn unclosed(span: Span) -> Self {
+        Self::UnclosedParen { span: Some(span) }
+    }
+
+    /// Whether the error is for malformed input rather than a failure to
+    /// evaluate well-formed input.
+    pub fn is_parse(&self) -> bool {
+        matches!(
+            self,
+            Self::UnexpectedToken { .. } | Self::UnclosedParen { .. } | Self::InvalidNumber { .. }
+        )
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedToken { .. } => ErrorKind::UnexpectedToken,
+            Self::UnclosedParen { .. } => ErrorKind::UnclosedParen,
+            Self::DivisionByZero => ErrorKind::DivisionByZero,
+            Self::InvalidNumber { .. } => ErrorKind::InvalidNumber,
+            Self::Overflow { .. } => ErrorKind::Overflow,
+            Self::NegativeExponent { .. } => ErrorKind::NegativeExponent,
+            Self::UnknownVariable(_) => ErrorKind::UnknownVariable,
+            Self::UnknownFunction(_) => ErrorKind::UnknownFunction,
+            Self::WrongArity { .. } => ErrorKind::WrongArity,
+            Self::InvalidInterval { .. } => ErrorKind::InvalidInterval,
+            Self::Unsupported(_) => ErrorKind::Unsupported,
+            Self::NotInvertible { .. } => ErrorKind::NotInvertible,
+            Self::InvalidBytecode(_) => ErrorKind::InvalidBytecode,
+            Self::InvalidJson(_) => ErrorKind::InvalidJson,
+            Self::DepthLimitExceeded { .. } => ErrorKind::DepthLimitExceeded,
+            Self::BudgetExceeded { .. } => ErrorKind::BudgetExceeded,
+            Self::Cancelled => ErrorKind::Cancelled,
+            Self::CapacityExceeded { .. } => ErrorKind::CapacityExceeded,
+        }
+    }
+
+    /// The stable code of the error's kind, see [`ErrorKind::code`].
+    pub fn code(&self) -> &'static str {
+        self.kind().code()
+    }
+
+    /// Where in the source the error is, for errors that know.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::UnexpectedToken { span, .. }
+            | Self::UnclosedParen { span }
+            | Self::InvalidNumber { span } => *span,
+            _ => None,
+        }
+    }
+
+    /// Fixes a person might try, such as `add ')'` for an unclosed
+    /// parenthesis; often there are none.
+    pub fn suggestions(&self) -> Vec<String> {
+        let suggestion = match self {
+            Self::UnclosedParen { .. } => "add ')'".to_string(),
+            Self::UnexpectedToken {
+                found: Some(found),
+                expected: "operator or end of input",
+                ..
+            } if found == ")" => "remove the unmatched ')'".to_string(),
+            Self::UnexpectedToken {
+                found: None,
+                expected: "number or parenthesis",
+                ..
+            } => "add an operand at the end".to_string(),
+            Self::UnknownVariable(name) => format!("define '{}' in the context", name),
+            Self::WrongArity { name, expected, .. } => {
+                format!("pass {} arguments to {}", expected, name)
+            }
+            Self::Overflow { .. } => "use wrapping or saturating overflow".to_string(),
+            _ => return Vec::new(),
+        };
+        vec![suggestion]
+    }
+
+    /// The error as a JSON object, for returning to programs such as a web
+    /// frontend:
+    ///
+    /// ```text
+    /// {"code":"E017","message":"Parse error: Unclosed parenthesis","span":{"start":6,"end":6},"suggestions":["add ')'"]}
+    /// ```
+    ///
+    /// `span` is `null` for errors without a position.
+    pub fn to_json(&self) -> String {
+        let span = match self.span() {
+            Some(span) => format!("{{\"start\":{},\"end\":{}}}", span.start, span.end),
+            None => "null".to_string(),
+        };
+        let suggestions = self
+            .suggestions()
+            .iter()
+            .map(|suggestion| json_string(suggestion))
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"code\":{},\"message\":{},\"span\":{},\"suggestions\":[{}]}}",
+            json_string(self.code()),
+            json_string(&self.to_string()),
+            span,
+            suggestions.join(",")
+        )
+    }
+
+    /// Describes the error for a person, quoting the line of `source` it is
+    /// on with a caret under the offending part, like rustc does:
+    ///
+    /// ```text
+    /// Parse error: Unclosed parenthesis
+    ///  --> 1:11
+    ///   |
+    /// 1 | (1 + 2 * 3
+    ///   |           ^
+    /// ```
+    ///
+    /// Errors without a position are described on a single line.
+    pub fn render(&self, source: &str) -> String {
+        let Some(span) = self.span().filter(|span| span.end <= source.len()) else {
+            return self.to_string();
+        };
+
+        render_at(&self.to_string(), span, source)
+    }
+}
+
+// `heading`, then the line of `source` containing `span` with carets under it.
+pub(crate) fn render_at(heading: &str, span: Span, source: &str) -> String {
+    if span.end > source.len() {
+        return heading.to_string();
+    }
+    let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |i| span.start + i);
+    let line = &source[line_start..line_end];
+    let number = source[..span.start].matches('\n').count() + 1;
+    let column = source[line_start..span.start].chars().count();
+    let width = source[span.start..span.end.min(line_end)].chars().count();
+    let gutter = " ".repeat(number.to_string().len());
+
+    format!(
+        "{}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}",
+        heading,
+        gutter,
+        number,
+        column + 1,
+        gutter,
+        number,
+        line,
+        gutter,
+        " ".repeat(column),
+        "^".repeat(width.max(1))
+    )
+}
+
+impl Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedToken {
+                found: Some(found),
+                expected,
+                ..
+            } => write!(f, "Parse error: Expected {}, found '{}'", expected, found),
+            Self::UnexpectedToken {
+                found: None,
+                expected,
+                ..
+            } => write!(f, "Parse error: Expected {}, found end of input", expected),
+            Self::UnclosedParen { .. } => write!(f, "Parse error: Unclosed parenthesis"),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::InvalidNumber { .. } => {
+                write!(f, "Invalid number: out of the 32-bit range")
+            }
+            Self::Overflow { op, lhs, rhs } => write!(f, "Overflow: {} {} {}", lhs, op, rhs),
+            Self::NegativeExponent { base, exponent } => {
+                write!(f, "Negative exponent: {} ^ {}", base, exponent)
+            }
+            Self::UnknownVariable(name) => write!(f, "Unknown variable: {}", name),
+            Self::UnknownFunction(name) => write!(f, "Unknown function: {}", name),
+            Self::WrongArity {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} arguments but {} were given",
+                name, expected, found
+            ),
+            Self::InvalidInterval { lo, hi } => write!(f, "Invalid interval: [{}, {}]", lo, hi),
+            Self::Unsupported(s) => write!(f, "Unsupported operation: {}", s),
+            Self::NotInvertible { value, modulus } => {
+                write!(f, "{} has no inverse modulo {}", value, modulus)
+            }
+            Self::InvalidBytecode(s) => write!(f, "Invalid compiled expression: {}", s),
+            Self::InvalidJson(s) => write!(f, "Invalid expression JSON: {}", s),
+            Self::DepthLimitExceeded { limit } => {
+                write!(f, "Expression nested more than {} levels deep", limit)
+            }
+            Self::BudgetExceeded { fuel } => {
+                write!(f, "Evaluation budget exceeded after {} operations", fuel)
+            }
+            Self::Cancelled => write!(f, "Evaluation cancelled"),
+            Self::CapacityExceeded { what, limit } => {
+                write!(f, "Expression needs more than {} {}", limit, what)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval, parse};
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(parse("1 +").unwrap_err().code(), "E001");
+        assert_eq!(parse("(1 +").unwrap_err().code(), "E001");
+        assert_eq!(parse("(1").unwrap_err().code(), "E017");
+        assert_eq!(eval("1 / 0").unwrap_err().kind(), ErrorKind::DivisionByZero);
+        let err = eval("x").unwrap_err();
+        assert_eq!(
+            (err.kind(), err.code()),
+            (ErrorKind::UnknownVariable, "E006")
+        );
+        assert_eq!(ExprError::Cancelled.code(), "E015");
+        assert_eq!(
+            ExprError::CapacityExceeded {
+                what: "tokens",
+                limit: 1
+            }
+            .code(),
+            "E016"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            parse("(1 + 2").unwrap_err().to_json(),
+            r#"{"code":"E017","message":"Parse error: Unclosed parenthesis","span":{"start":6,"end":6},"suggestions":["add ')'"]}"#
+        );
+        assert_eq!(
+            ExprError::UnknownVariable("a\"b\n".into()).to_json(),
+            r#"{"code":"E006","message":"Unknown variable: a\"b\u000a","span":null,"suggestions":["define 'a\"b\u000a' in the context"]}"#
+        );
+        assert_eq!(
+            parse("1 )").unwrap_err().suggestions(),
+            ["remove the unmatched ')'"]
+        );
+        assert_eq!(
+            parse("1 +").unwrap_err().suggestions(),
+            ["add an operand at the end"]
+        );
+        assert!(eval("1 / 0").unwrap_err().suggestions().is_empty());
+    }
+}
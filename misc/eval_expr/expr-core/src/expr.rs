@@ -0,0 +1,1730 @@
+use std::{collections::BTreeMap, iter::Peekable};
+
+use crate::{
+    ast::Parser,
+    context::call_builtin,
+    meter::Meter,
+    options::WarningHook,
+    token::{Fixity, Token, Tokenizer},
+    CompiledExpr, Context, DivisionMode, EvalOptions, ExprError, Interval, OverflowPolicy, Result,
+    Span, UsageStats, Value, Warning, WarningKind,
+};
+
+impl Token {
+    pub(crate) fn compute(&self, l: i32, r: i32, options: &EvalOptions) -> Result<i32> {
+        if matches!(self, Token::Divide | Token::FloorDivide | Token::Modulo) && r == 0 {
+            return Err(ExprError::DivisionByZero);
+        }
+
+        if let Some(modulus) = options.modulus {
+            return self.compute_modular(l, r, modulus);
+        }
+
+        if *self == Token::Power && r < 0 {
+            return Err(ExprError::NegativeExponent {
+                base: l,
+                exponent: r,
+            });
+        }
+
+        let overflowed = ExprError::Overflow {
+            op: self.symbol(),
+            lhs: l,
+            rhs: r,
+        };
+
+        let checked = match &self {
+            Token::Plus => l.checked_add(r),
+            Token::Minus => l.checked_sub(r),
+            Token::Multiply => l.checked_mul(r),
+            Token::Divide => options.division.quotient(l, r),
+            Token::FloorDivide => DivisionMode::Floor.quotient(l, r),
+            Token::Modulo => Some(options.remainder().remainder(l, r)),
+            Token::Power => l.checked_pow(r as u32),
+            Token::And => Some((l != 0 && r != 0) as i32),
+            Token::Or => Some((l != 0 || r != 0) as i32),
+            _ => {
+                return Err(ExprError::Unsupported(format!(
+                    "'{}' on numbers",
+                    self.symbol()
+                )))
+            }
+        };
+
+        if let Some(value) = checked {
+            return Ok(value);
+        }
+
+        // Every remaining case overflowed, so all division modes agree (the
+        // quotient of `i32::MIN / -1` is exact).
+        match options.overflow {
+            OverflowPolicy::Checked => Err(overflowed),
+            OverflowPolicy::Wrapping => Ok(match &self {
+                Token::Plus => l.wrapping_add(r),
+                Token::Minus => l.wrapping_sub(r),
+                Token::Multiply => l.wrapping_mul(r),
+                Token::Power => l.wrapping_pow(r as u32),
+                _ => l.wrapping_div(r),
+            }),
+            OverflowPolicy::Saturating => Ok(match &self {
+                Token::Plus => l.saturating_add(r),
+                Token::Minus => l.saturating_sub(r),
+                Token::Multiply => l.saturating_mul(r),
+                Token::Power => l.saturating_pow(r as u32),
+                _ => l.saturating_div(r),
+            }),
+        }
+    }
+
+    // `l` and `r` are residues, except for the exponent of `^`, which is an
+    // ordinary integer.
+    fn compute_modular(&self, l: i32, r: i32, modulus: i32) -> Result<i32> {
+        let (l, r, p) = (l as i64, r as i64, modulus as i64);
+        let inverse = |value: i64| {
+            mod_inverse(value, p).ok_or(ExprError::NotInvertible {
+                value: value as i32,
+                modulus,
+            })
+        };
+
+        let result = match self {
+            Token::Plus => l + r,
+            Token::Minus => l - r,
+            Token::Multiply => l * r,
+            Token::Divide => l * inverse(r)?,
+            Token::Power if r < 0 => mod_pow(inverse(l)?, r.unsigned_abs(), p),
+            Token::Power => mod_pow(l, r as u64, p),
+            Token::And => (l != 0 && r != 0) as i64,
+            Token::Or => (l != 0 || r != 0) as i64,
+            _ => {
+                return Err(ExprError::Unsupported(format!(
+                    "'{}' in modular arithmetic",
+                    self.symbol()
+                )))
+            }
+        };
+
+        Ok(result.rem_euclid(p) as i32)
+    }
+
+    pub(crate) fn apply(&self, l: Value, r: Value, options: &EvalOptions) -> Result<Value> {
+        if let Token::Custom { index, .. } = self {
+            return options.operators.apply_custom(*index, &[l, r]);
+        }
+        match (l, r) {
+            (Value::Int(l), Value::Int(r)) => self.compute(l, r, options).map(Value::Int),
+            (l, r) => Interval::from(l)
+                .compute(self, r.into(), options)
+                .map(Value::Interval),
+        }
+    }
+}
+
+impl Value {
+    pub(crate) fn negate(self, options: &EvalOptions) -> Result<Value> {
+        match self {
+            Value::Int(n) => Token::Minus.compute(0, n, options).map(Value::Int),
+            Value::Interval(i) => i.negate().map(Value::Interval),
+        }
+    }
+
+    pub(crate) fn expect_int(self, context: &str) -> Result<i32> {
+        match self {
+            Value::Int(n) => Ok(n),
+            Value::Interval(_) => Err(ExprError::Unsupported(format!("interval in '{}'", context))),
+        }
+    }
+}
+
+fn mod_pow(mut base: i64, mut exp: u64, p: i64) -> i64 {
+    let mut result = 1 % p;
+    base = base.rem_euclid(p);
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % p;
+        }
+        base = base * base % p;
+        exp >>= 1;
+    }
+    result
+}
+
+// Extended Euclid; `None` unless `gcd(value, p) == 1`.
+fn mod_inverse(value: i64, p: i64) -> Option<i64> {
+    let (mut old_r, mut r) = (value.rem_euclid(p), p);
+    let (mut old_s, mut s) = (1, 0);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    (old_r == 1).then(|| old_s.rem_euclid(p))
+}
+
+pub struct Expr<'a> {
+    src: &'a str,
+    iter: Peekable<Tokenizer<'a>>,
+    options: EvalOptions,
+    context: Option<&'a Context>,
+    op_counts: BTreeMap<&'static str, usize>,
+    calls: BTreeMap<String, usize>,
+    trace: Vec<(String, Value)>,
+    warnings: Vec<Warning>,
+    meter: Meter,
+    // The token consumed last, where parse errors point.
+    last: Span,
+}
+
+impl<'a> Expr<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            iter: Tokenizer::new(src).peekable(),
+            options: EvalOptions::default(),
+            context: None,
+            op_counts: BTreeMap::new(),
+            calls: BTreeMap::new(),
+            trace: Vec::new(),
+            warnings: Vec::new(),
+            meter: Meter::default(),
+            last: Span::default(),
+        }
+    }
+
+    pub fn with_options(src: &'a str, options: EvalOptions) -> Self {
+        Self {
+            src,
+            iter: Tokenizer::with_operators(src, options.operators.clone())
+                .with_numbers(options.numbers)
+                .peekable(),
+            options,
+            context: None,
+            op_counts: BTreeMap::new(),
+            calls: BTreeMap::new(),
+            trace: Vec::new(),
+            warnings: Vec::new(),
+            meter: Meter::default(),
+            last: Span::default(),
+        }
+    }
+
+    /// Resolves identifiers against `context` instead of rejecting them.
+    pub fn with_context(mut self, context: &'a Context) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// How many times each operator was applied by [`Expr::eval`], keyed by
+    /// its symbol (`neg` for unary minus).
+    pub fn op_counts(&self) -> &BTreeMap<&'static str, usize> {
+        &self.op_counts
+    }
+
+    // Counts `op` as applied, burning a unit of fuel for it.
+    fn count(&mut self, op: &'static str) -> Result<()> {
+        *self.op_counts.entry(op).or_default() += 1;
+        self.meter.burn()
+    }
+
+    /// What [`Expr::eval`] used: its operator counts, the functions it
+    /// called and the modes it ran in, for collecting across many
+    /// evaluations.
+    pub fn usage(&self) -> UsageStats {
+        UsageStats::new(&self.options, &self.op_counts, &self.calls)
+    }
+
+    /// The named values [`Expr::eval`] used, in the order first seen, when
+    /// [`EvalOptions::trace_vars`] is on: each variable read, and each call
+    /// keyed by its arguments, such as `sq(3)`.
+    pub fn trace(&self) -> &[(String, Value)] {
+        &self.trace
+    }
+
+    /// What [`Expr::eval`] found suspicious in the expression, see
+    /// [`Warning`].
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// The base the last `hex`, `bin` or `oct` call in [`Expr::eval`] asked
+    /// for the value to be written in, e.g. 16 for `hex(255) + 1`, see
+    /// [`BUILTINS`](crate::BUILTINS).
+    pub fn radix(&self) -> Option<u32> {
+        self.meter.radix()
+    }
+
+    fn warn(&mut self, kind: WarningKind, message: String, span: Span) {
+        let warning = Warning::new(kind, message, span);
+        if let Some(WarningHook(hook)) = &self.options.on_warning {
+            hook(self.src, &warning);
+        }
+        self.warnings.push(warning);
+    }
+
+    fn record(&mut self, name: String, value: Value) {
+        if self.options.trace_vars && self.trace.iter().all(|(seen, _)| *seen != name) {
+            self.trace.push((name, value));
+        }
+    }
+
+    /// Parses the expression once into a [`CompiledExpr`] that can then be
+    /// evaluated against any number of contexts, with none of the
+    /// tokenizing and parsing [`Expr::eval`] repeats on every call. Any
+    /// context set with [`Expr::with_context`] is ignored; pass one to
+    /// [`CompiledExpr::eval`] instead. Compiling goes through a syntax tree,
+    /// so input using a custom operator fails, as in
+    /// [`parse_with`](crate::parse_with).
+    pub fn compile(self) -> Result<CompiledExpr> {
+        let ast = Parser::from_tokens(self.src, self.iter)
+            .with_options(&self.options)
+            .parse();
+        let ast = self.options.report(self.src, ast)?;
+        Ok(ast.compile(&self.options))
+    }
+
+    pub fn eval(&mut self) -> Result<Value> {
+        self.meter = Meter::new(&self.options);
+        let modulus = self.options.modulus;
+        let result = self.compute_expr().and_then(|result| {
+            if self.next_token().is_some() {
+                return Err(self.unexpected("operator or end of input"));
+            };
+            Ok(result)
+        });
+        // An error inside an exponent leaves the modulus taken.
+        self.options.modulus = modulus;
+        self.options.report(self.src, result)
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.iter.peek().map(|token| &token.value)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.iter.next();
+        self.last = match &token {
+            Some(token) => token.span,
+            // Past the end, right after the last token.
+            None => Span {
+                start: self.last.end,
+                end: self.last.end,
+            },
+        };
+        token.map(|token| token.value)
+    }
+
+    // Parse errors at the token consumed last.
+    fn unexpected(&self, expected: &'static str) -> ExprError {
+        ExprError::unexpected(self.src, self.last, expected)
+    }
+
+    fn unclosed(&self) -> ExprError {
+        ExprError::unclosed(self.last)
+    }
+
+    // Starts an atom (a number, variable, call, interval, parenthesized
+    // expression or prefix operator). Operands that are themselves
+    // expressions push a frame saying what to do with their value.
+    fn compute_atom(&mut self, stack: &mut Stack) -> Result<Next> {
+        match self.iter.peek().map(|token| &token.value) {
+            Some(Token::Minus) => {
+                // Negation binds looser than `^`, so `-2 ^ 2` is `-(2 ^ 2)`.
+                self.next_token(); // consume '-'
+                stack.push(Frame::Neg);
+                Ok(Next::Expr(
+                    self.options.precedence.operand_precedence(&Token::Minus),
+                ))
+            }
+            Some(Token::Not) => {
+                self.next_token(); // consume 'not'
+                stack.push(Frame::Not);
+                Ok(Next::Expr(
+                    self.options.precedence.operand_precedence(&Token::Not),
+                ))
+            }
+            Some(&Token::Custom {
+                spelling,
+                index,
+                precedence,
+                fixity: Fixity::Prefix,
+            }) => {
+                self.next_token();
+                stack.push(Frame::Prefix { spelling, index });
+                Ok(Next::Expr(precedence))
+            }
+            Some(Token::Number(num)) => {
+                let val = match self.options.modulus {
+                    Some(p) => num.rem_euclid(p),
+                    None => *num,
+                };
+                self.next_token();
+                Ok(Next::Value(Value::Int(val)))
+            }
+            Some(Token::LeftBracket) if self.options.modulus.is_some() => Err(
+                ExprError::Unsupported("interval in modular arithmetic".into()),
+            ),
+            Some(Token::LeftBracket) if self.options.intervals => {
+                self.next_token(); // consume '['
+                stack.push(Frame::IntervalLo);
+                Ok(Next::Expr(1))
+            }
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.next_token();
+                if self.peek() == Some(&Token::LeftParen) {
+                    self.next_token(); // consume '('
+                    if self.peek() == Some(&Token::RightParen) {
+                        self.next_token();
+                        return self.compute_call(name, Vec::new()).map(Next::Value);
+                    }
+                    stack.push(Frame::Arg {
+                        name,
+                        args: Vec::new(),
+                    });
+                    return Ok(Next::Expr(1));
+                }
+                let value = self.context.and_then(|ctx| ctx.get(&name));
+                let value = value.ok_or_else(|| ExprError::UnknownVariable(name.clone()))?;
+                self.record(name, value);
+                Ok(Next::Value(value))
+            }
+            Some(Token::LeftParen) => {
+                self.next_token(); // consume '('
+                stack.push(Frame::Paren);
+                Ok(Next::Expr(1))
+            }
+            token => {
+                let token = token.cloned();
+                self.next_token();
+                Err(Token::operand_error(token.as_ref(), self.src, self.last))
+            }
+        }
+    }
+
+    // Evaluates the whole input by precedence climbing. The climb would
+    // naturally recurse once per operand; the pending work is kept in `stack`
+    // instead, so that nesting depth is bounded by memory, not the call stack.
+    fn compute_expr(&mut self) -> Result<Value> {
+        let mut stack = Stack::default();
+        let mut next = Next::Expr(1);
+        loop {
+            next = match next {
+                Next::Expr(min_prec) => {
+                    stack.push(Frame::Lhs { min_prec });
+                    if let Some(limit) = self.options.max_depth.filter(|&limit| stack.depth > limit)
+                    {
+                        return Err(ExprError::DepthLimitExceeded { limit });
+                    }
+                    self.compute_atom(&mut stack)?
+                }
+                Next::Value(value) => match stack.frames.pop() {
+                    Some(frame) => {
+                        if frame.is_expr() {
+                            stack.depth -= 1;
+                        }
+                        self.resume(frame, value, &mut stack)?
+                    }
+                    None => return Ok(value),
+                },
+            };
+        }
+    }
+
+    // Hands the value of a finished operand to the frame waiting for it.
+    fn resume(&mut self, frame: Frame, value: Value, stack: &mut Stack) -> Result<Next> {
+        match frame {
+            Frame::Lhs { min_prec } => self.climb(min_prec, value, stack),
+            Frame::Rhs {
+                min_prec,
+                lhs,
+                op,
+                at,
+                modulus,
+            } => {
+                if op == Token::Power {
+                    self.options.modulus = modulus;
+                }
+                self.count(op.symbol())?;
+                if let (Token::Divide, Value::Int(l), Value::Int(r), None) =
+                    (&op, lhs, value, self.options.modulus)
+                {
+                    if l.checked_rem(r).is_some_and(|rem| rem != 0) {
+                        let message = format!("'{} / {}' discards a remainder", l, r);
+                        self.warn(WarningKind::InexactDivision, message, at);
+                    }
+                }
+                let lhs = op.apply(lhs, value, &self.options)?;
+                self.climb(min_prec, lhs, stack)
+            }
+            Frame::Neg => {
+                self.count("neg")?;
+                value.negate(&self.options).map(Next::Value)
+            }
+            Frame::Not => {
+                self.count(Token::Not.symbol())?;
+                Ok(Next::Value(Value::Int(
+                    (value.expect_int("not")? == 0) as i32,
+                )))
+            }
+            Frame::Prefix { spelling, index } => {
+                self.count(spelling)?;
+                let operators = &self.options.operators;
+                operators.apply_custom(index, &[value]).map(Next::Value)
+            }
+            Frame::IntervalLo => {
+                if self.next_token() != Some(Token::Comma) {
+                    return Err(self.unexpected("','"));
+                }
+                stack.push(Frame::IntervalHi { lo: value });
+                Ok(Next::Expr(1))
+            }
+            Frame::IntervalHi { lo } => {
+                if self.next_token() != Some(Token::RightBracket) {
+                    return Err(self.unexpected("']'"));
+                }
+                let lo = lo.expect_int("interval bound")?;
+                let hi = value.expect_int("interval bound")?;
+                Interval::new(lo, hi).map(|i| Next::Value(Value::Interval(i)))
+            }
+            Frame::Paren => match self.next_token() {
+                Some(Token::RightParen) => Ok(Next::Value(value)),
+                _ => Err(self.unclosed()),
+            },
+            Frame::Arg { name, mut args } => {
+                args.push(value);
+                match self.next_token() {
+                    Some(Token::Comma) => {
+                        stack.push(Frame::Arg { name, args });
+                        Ok(Next::Expr(1))
+                    }
+                    Some(Token::RightParen) => self.compute_call(name, args).map(Next::Value),
+                    _ => Err(self.unclosed()),
+                }
+            }
+            Frame::Item {
+                min_prec,
+                needle,
+                found,
+            } => {
+                let found = found || value.expect_int("in")? == needle;
+                match self.next_token() {
+                    Some(Token::Comma) => {
+                        stack.push(Frame::Item {
+                            min_prec,
+                            needle,
+                            found,
+                        });
+                        Ok(Next::Expr(1))
+                    }
+                    Some(Token::RightParen) => {
+                        self.count(Token::In.symbol())?;
+                        self.climb(min_prec, Value::Int(found as i32), stack)
+                    }
+                    _ => Err(self.unclosed()),
+                }
+            }
+        }
+    }
+
+    // Applies the operators that follow `lhs` and bind at least as tightly
+    // as `min_prec`, starting on the right operand of the first one.
+    fn climb(&mut self, min_prec: i32, mut lhs: Value, stack: &mut Stack) -> Result<Next> {
+        let op = loop {
+            match self.peek().cloned() {
+                // Postfix operators apply at once, and operators may follow.
+                Some(Token::Custom {
+                    spelling,
+                    index,
+                    precedence,
+                    fixity: Fixity::Postfix,
+                }) if precedence >= min_prec => {
+                    self.next_token();
+                    self.count(spelling)?;
+                    lhs = self.options.operators.apply_custom(index, &[lhs])?;
+                }
+                Some(token) if self.options.implicit_mul && token.follows_implicitly() => {
+                    if self.options.precedence.precedence(&Token::Multiply) < min_prec {
+                        return Ok(Next::Value(lhs));
+                    }
+                    // The operand is left for the right side to read.
+                    break Token::Multiply;
+                }
+                Some(token)
+                    if token.is_operator()
+                        && self.options.precedence.precedence(&token) >= min_prec =>
+                {
+                    self.next_token();
+                    break token;
+                }
+                _ => return Ok(Next::Value(lhs)),
+            }
+        };
+        let at = self.last;
+
+        if op == Token::Power
+            && matches!(
+                stack.frames.last(),
+                Some(Frame::Rhs {
+                    op: Token::Power,
+                    ..
+                })
+            )
+        {
+            self.warn(
+                WarningKind::PowerChain,
+                "'^' groups to the right, so 'a ^ b ^ c' is 'a ^ (b ^ c)'".into(),
+                at,
+            );
+        }
+
+        if op == Token::In {
+            // Evaluates the `(a, b, ...)` list, yielding 1 if `lhs` is in it
+            let needle = lhs.expect_int("in")?;
+            if self.next_token() != Some(Token::LeftParen) {
+                return Err(self.unexpected("'(' after 'in'"));
+            }
+            stack.push(Frame::Item {
+                min_prec,
+                needle,
+                found: false,
+            });
+            return Ok(Next::Expr(1));
+        }
+
+        let next_min_prec = self.options.precedence.rhs_precedence(&op);
+
+        // Exponents count repetitions, so they are never reduced.
+        let modulus = if op == Token::Power {
+            self.options.modulus.take()
+        } else {
+            self.options.modulus
+        };
+        stack.push(Frame::Rhs {
+            min_prec,
+            lhs,
+            op,
+            at,
+            modulus,
+        });
+        Ok(Next::Expr(next_min_prec))
+    }
+
+    // Calls `name` with its evaluated arguments
+    fn compute_call(&mut self, name: String, args: Vec<Value>) -> Result<Value> {
+        *self.calls.entry(name.clone()).or_default() += 1;
+        self.meter.burn()?;
+        let key = self.options.trace_vars.then(|| {
+            let args = args.iter().map(Value::to_string).collect::<Vec<_>>();
+            format!("{}({})", name, args.join(", "))
+        });
+        let value = match self.context {
+            Some(context) => context.call(&name, args, &self.options, &self.meter)?,
+            None => call_builtin(&name, args, &self.meter)?,
+        };
+        if let Some(key) = key {
+            self.record(key, value);
+        }
+        Ok(value)
+    }
+}
+
+// The frames of `Expr::compute_expr`, and how many of them stand for an
+// expression still being evaluated.
+#[derive(Default)]
+struct Stack {
+    frames: Vec<Frame>,
+    depth: usize,
+}
+
+impl Stack {
+    fn push(&mut self, frame: Frame) {
+        if frame.is_expr() {
+            self.depth += 1;
+        }
+        self.frames.push(frame);
+    }
+}
+
+// What `Expr::compute_expr` does next.
+enum Next {
+    // Evaluate an expression of operators binding at least this tightly.
+    Expr(i32),
+    // Hand this value to the frame on top of the stack.
+    Value(Value),
+}
+
+// Work waiting for the value of an operand, the state a recursive descent
+// would keep in its call frames.
+enum Frame {
+    // The first operand of an expression, to be followed by its operators.
+    Lhs {
+        min_prec: i32,
+    },
+    // The right operand of `lhs op`, with `op` at `at`; `modulus` is the
+    // one to restore after an exponent, which is evaluated without it.
+    Rhs {
+        min_prec: i32,
+        lhs: Value,
+        op: Token,
+        at: Span,
+        modulus: Option<i32>,
+    },
+    Neg,
+    Not,
+    // A custom prefix operator, the `index`th in the operator table.
+    Prefix {
+        spelling: &'static str,
+        index: usize,
+    },
+    IntervalLo,
+    IntervalHi {
+        lo: Value,
+    },
+    Paren,
+    // The next argument of a call.
+    Arg {
+        name: String,
+        args: Vec<Value>,
+    },
+    // The next item of an `in` list.
+    Item {
+        min_prec: i32,
+        needle: i32,
+        found: bool,
+    },
+}
+
+impl Frame {
+    // Whether the frame carries on an expression once it has its operand,
+    // as a call to `compute_expr` would have in the recursive version.
+    fn is_expr(&self) -> bool {
+        matches!(
+            self,
+            Frame::Lhs { .. } | Frame::Rhs { .. } | Frame::Item { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        parse, parse_with, split_assignment, Assoc, CancelToken, ExprCache, FixedEvaluator,
+        Function, LanguageLevel, NumberFormat, OperatorTable, PrecedenceTable,
+    };
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            Tokenizer::new("1 + 2 - 3")
+                .map(|token| token.value)
+                .collect::<Vec<_>>(),
+            vec![
+                Token::Number(1),
+                Token::Plus,
+                Token::Number(2),
+                Token::Minus,
+                Token::Number(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let src = "12 **  x_1 //é";
+        let spans = Tokenizer::new(src)
+            .map(|token| (token.value, &src[token.span.start..token.span.end]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                (Token::Number(12), "12"),
+                (Token::Power, "**"),
+                (Token::Ident("x_1".into()), "x_1"),
+                (Token::FloorDivide, "//"),
+                (Token::Ident("é".into()), "é"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_expr() {
+        let mut expr = Expr::new("1 + 2 - 3");
+        assert_eq!(expr.eval().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_eval_expr_with_precedence() {
+        let mut expr = Expr::new("1 + 2 * 3");
+        assert_eq!(expr.eval().unwrap(), 7);
+
+        let mut expr = Expr::new("1 + 2 * 3 - 4");
+        assert_eq!(expr.eval().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let mut expr = Expr::new("1 + 2 *");
+        assert_eq!(
+            expr.eval().unwrap_err().to_string(),
+            "Parse error: Expected number or parenthesis, found end of input"
+        );
+
+        let mut expr = Expr::new("1 + 2 / 0");
+        assert_eq!(expr.eval().unwrap_err().to_string(), "Division by zero");
+
+        let mut expr = Expr::new("1 + 2 * 3 -");
+        assert_eq!(
+            expr.eval().unwrap_err().to_string(),
+            "Parse error: Expected number or parenthesis, found end of input"
+        );
+
+        let mut expr = Expr::new("1 + 2 * 3 - 4 / 0");
+        assert_eq!(expr.eval().unwrap_err().to_string(), "Division by zero");
+    }
+
+    #[test]
+    fn test_parentheses() {
+        let mut expr = Expr::new("(2 + 3) * 4");
+        assert_eq!(expr.eval().unwrap(), 20);
+    }
+
+    #[test]
+    fn test_power() {
+        let mut expr = Expr::new("2 ^ 3");
+        assert_eq!(expr.eval().unwrap(), 8);
+    }
+
+    #[test]
+    fn test_complex_expressions() {
+        assert_eq!(Expr::new("2 + 3 * 4").eval().unwrap(), 14);
+        assert_eq!(Expr::new("(2 + 3) * 4").eval().unwrap(), 20);
+        assert_eq!(Expr::new("2 ^ 3 ^ 2").eval().unwrap(), 512);
+        assert_eq!(Expr::new("2 * (3 + 4) ^ 2").eval().unwrap(), 98);
+        assert_eq!(Expr::new("2 ^ (1 ^ 4)").eval().unwrap(), 2);
+        assert_eq!(Expr::new("(2 ^ 1) ^ 4").eval().unwrap(), 16);
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(
+            Expr::new("2147483647 + 1").eval().unwrap_err(),
+            ExprError::Overflow {
+                op: "+",
+                lhs: 2147483647,
+                rhs: 1
+            }
+        );
+        assert_eq!(
+            Expr::new("65536 * 65536").eval().unwrap_err().to_string(),
+            "Overflow: 65536 * 65536"
+        );
+        assert_eq!(
+            Expr::new("2 ^ 31").eval().unwrap_err().to_string(),
+            "Overflow: 2 ^ 31"
+        );
+        assert_eq!(Expr::new("2 ^ 30").eval().unwrap(), 1 << 30);
+
+        let out_of_range = |start, end| ExprError::InvalidNumber {
+            span: Some(Span { start, end }),
+        };
+        assert_eq!(Expr::new("2147483648").eval(), Err(out_of_range(0, 10)));
+        assert_eq!(
+            Expr::new("1 + 99999999999999999999").eval(),
+            Err(out_of_range(4, 24))
+        );
+        assert_eq!(crate::parse("2147483648"), Err(out_of_range(0, 10)));
+        assert_eq!(
+            FixedEvaluator::<8, 8>::new().eval("2 * 2147483648", &EvalOptions::default()),
+            Err(out_of_range(4, 14))
+        );
+        assert_eq!(Expr::new("-2147483648").eval().unwrap(), i32::MIN);
+        assert_eq!(
+            Expr::new("(- 2147483648) / 2").eval().unwrap(),
+            i32::MIN / 2
+        );
+        assert_eq!(
+            crate::parse("-2147483648").unwrap().eval().unwrap(),
+            i32::MIN
+        );
+        // Subtracting it, or raising it to a power first, needs it alone.
+        assert_eq!(Expr::new("0 -2147483648").eval(), Err(out_of_range(3, 13)));
+        assert_eq!(
+            Expr::new("-2147483648 ^ 1").eval(),
+            Err(out_of_range(1, 11))
+        );
+    }
+
+    #[test]
+    fn test_multi_char_operators() {
+        assert_eq!(
+            Tokenizer::new("2 ** 3*4")
+                .map(|token| token.value)
+                .collect::<Vec<_>>(),
+            vec![
+                Token::Number(2),
+                Token::Power,
+                Token::Number(3),
+                Token::Multiply,
+                Token::Number(4)
+            ]
+        );
+        assert_eq!(Expr::new("2 ** 3 ** 2").eval().unwrap(), 512);
+        assert_eq!(Expr::new("2**3 * 2").eval().unwrap(), 16);
+    }
+
+    #[test]
+    fn test_aliases() {
+        assert_eq!(Expr::new("7 // 2").eval().unwrap(), 3);
+        assert_eq!(Expr::new("(0 - 7) // 2").eval().unwrap(), -4);
+        assert_eq!(Expr::new("17 mod 5 + 1").eval().unwrap(), 3);
+        assert_eq!(Expr::new("2 * 3 mod 4").eval().unwrap(), 2);
+
+        let options =
+            EvalOptions::default().operators(OperatorTable::default().without("//").without("mod"));
+        assert!(Expr::with_options("7 // 2", options.clone())
+            .eval()
+            .is_err());
+        assert!(Expr::with_options("17 mod 5", options.clone())
+            .eval()
+            .is_err());
+        assert_eq!(Expr::with_options("2 ** 3", options).eval().unwrap(), 8);
+    }
+
+    #[test]
+    fn test_precedence_table() {
+        let options = EvalOptions::default().precedence(
+            PrecedenceTable::default()
+                .infix("**", 7, Assoc::Left)
+                .infix("+", 7, Assoc::Left)
+                .prefix("-", 8),
+        );
+        let eval = |src| Expr::with_options(src, options.clone()).eval().unwrap();
+        let fixed = |src| {
+            FixedEvaluator::<16, 8>::new()
+                .eval(src, &options)
+                .map(Value::Int)
+                .unwrap()
+        };
+        let parsed = |src| parse_with(src, &options).unwrap().to_string();
+
+        assert_eq!(eval("2 ^ 3 ^ 2"), 64);
+        assert_eq!(eval("2 * 3 + 4"), 14);
+        assert_eq!(eval("-2 ^ 2"), 4);
+        assert_eq!(eval("10 - 2 + 3"), 5);
+        for src in ["2 ^ 3 ^ 2", "2 * 3 + 4", "-2 ^ 2", "10 - 2 * 2"] {
+            assert_eq!(fixed(src), eval(src), "{}", src);
+        }
+        assert_eq!(parsed("2 ^ 3 ^ 2"), "(2 ^ 3) ^ 2");
+        assert_eq!(parsed("a * b + c"), "a * (b + c)");
+        assert_eq!(parsed("-a ^ 2"), "(-a) ^ 2");
+
+        let compiled = Expr::with_options("2 * 3 + 4", options.clone())
+            .compile()
+            .unwrap();
+        assert_eq!(compiled.eval(&Context::new()).unwrap(), 14);
+        assert_eq!(Expr::new("2 * 3 + 4").eval().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_custom_infix() {
+        fn gcd(a: Value, b: Value) -> Result<Value> {
+            let (mut a, mut b) = (a.expect_int("gcd")?, b.expect_int("gcd")?);
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            Ok(Value::Int(a.abs()))
+        }
+        let operators = OperatorTable::default()
+            .infix("..", 4, Assoc::Left, |lo, hi| {
+                Interval::new(lo.expect_int("..")?, hi.expect_int("..")?).map(Value::Interval)
+            })
+            .infix("gcd", 6, Assoc::Left, gcd)
+            .infix("<<", 7, Assoc::Right, |a, b| {
+                Ok(Value::Int(a.expect_int("<<")? << b.expect_int("<<")?))
+            });
+        let options = EvalOptions::default().operators(operators);
+        let eval = |src| Expr::with_options(src, options.clone()).eval();
+
+        assert_eq!(
+            eval("1 + 1 .. 2 * 3").unwrap(),
+            Value::Interval(Interval::new(2, 6).unwrap())
+        );
+        assert_eq!(eval("12 gcd 18 + 1").unwrap(), 7);
+        assert_eq!(eval("1 << 1 << 2").unwrap(), 16);
+        assert!(matches!(
+            eval("3 .. 1"),
+            Err(ExprError::InvalidInterval { .. })
+        ));
+
+        let mut expr = Expr::with_options("1 gcd 2 gcd 3", options.clone());
+        expr.eval().unwrap();
+        assert_eq!(expr.op_counts()["gcd"], 2);
+
+        for err in [
+            parse_with("1 gcd 2", &options).unwrap_err(),
+            Expr::with_options("1 .. 2", options.clone())
+                .compile()
+                .unwrap_err(),
+            FixedEvaluator::<8, 4>::new()
+                .eval("1 << 2", &options)
+                .unwrap_err(),
+        ] {
+            assert!(matches!(err, ExprError::Unsupported(_)), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn test_custom_prefix_postfix() {
+        fn factorial(n: Value) -> Result<Value> {
+            let n = n.expect_int("!")?;
+            (1..=n)
+                .try_fold(1i32, |acc, i| acc.checked_mul(i))
+                .map(Value::Int)
+                .ok_or(ExprError::Overflow {
+                    op: "!",
+                    lhs: n,
+                    rhs: 0,
+                })
+        }
+        let operators = OperatorTable::default()
+            .prefix("~", 7, |n| Ok(Value::Int(!n.expect_int("~")?)))
+            .postfix("!", 8, factorial)
+            .postfix("km", 1, |n| Ok(Value::Int(n.expect_int("km")? * 1000)));
+        let options = EvalOptions::default().operators(operators);
+        let eval = |src| Expr::with_options(src, options.clone()).eval().unwrap();
+
+        assert_eq!(eval("~0 + 3"), 2);
+        assert_eq!(eval("~2 ^ 2"), -5);
+        assert_eq!(eval("2 * 3!"), 12);
+        assert_eq!(eval("2 ^ 3!"), 64);
+        assert_eq!(eval("-3!"), -6);
+        assert_eq!(eval("3!! / 2"), 360);
+        assert_eq!(eval("~3!"), -7);
+        assert_eq!(eval("1 + 2 km"), 3000);
+        assert_eq!(eval("(1 km) + 2"), 1002);
+
+        let mut expr = Expr::with_options("~~1!", options.clone());
+        expr.eval().unwrap();
+        assert_eq!(expr.op_counts()["~"], 2);
+        assert!(Expr::with_options("13!", options.clone()).eval().is_err());
+        assert!(Expr::with_options("!3", options.clone()).eval().is_err());
+        // Only `Expr::eval` evaluates custom operators.
+        for (src, op) in [("~1", "~"), ("1!", "!"), ("2 km", "km")] {
+            let errors = [
+                parse_with(src, &options).unwrap_err(),
+                Expr::with_options(src, options.clone())
+                    .compile()
+                    .unwrap_err(),
+                ExprCache::with_options(1, options.clone())
+                    .get(src)
+                    .unwrap_err(),
+                FixedEvaluator::<8, 4>::new()
+                    .eval(src, &options)
+                    .unwrap_err(),
+            ];
+            for err in errors {
+                assert!(matches!(err, ExprError::Unsupported(_)), "{:?}", err);
+                assert!(err.to_string().contains(&format!("'{}'", op)), "{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn test_implicit_multiplication() {
+        let mut context = Context::new();
+        context.set("x", 3);
+        context.set("y", 4);
+        let options = EvalOptions::default().implicit_multiplication(true);
+        let eval = |src| {
+            Expr::with_options(src, options.clone())
+                .with_context(&context)
+                .eval()
+        };
+
+        assert_eq!(eval("2x").unwrap(), 6);
+        assert_eq!(eval("2x ^ 2").unwrap(), 18);
+        assert_eq!(eval("1 + 2(x + 1)").unwrap(), 9);
+        assert_eq!(eval("(x + y)(x - y)").unwrap(), -7);
+        assert_eq!(eval("x y - 1").unwrap(), 11);
+        assert_eq!(eval("-2x").unwrap(), -6);
+        assert!(eval("2 3").is_err());
+        assert!(Expr::new("2x").with_context(&context).eval().is_err());
+
+        assert_eq!(
+            parse_with("2(y + 1)x", &options).unwrap().to_string(),
+            "2 * (y + 1) * x"
+        );
+        for src in ["2(3 + 4)", "(1 + 1)(2)3"] {
+            let fixed = FixedEvaluator::<16, 8>::new().eval(src, &options);
+            let expr = Expr::with_options(src, options.clone()).eval();
+            assert_eq!(fixed.map(Value::Int), expr, "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_number_format() {
+        let eval = |src, locale| {
+            let options =
+                EvalOptions::default().number_format(NumberFormat::locale(locale).unwrap());
+            Expr::with_options(src, options)
+                .eval()
+                .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(eval("1.234.567 + 1", "de-DE"), Ok(Value::Int(1234568)));
+        assert_eq!(eval("1.234,00 * 2", "de"), Ok(Value::Int(2468)));
+        assert_eq!(
+            eval("1 234 567 - 7", "fr_FR.UTF-8"),
+            Ok(Value::Int(1234560))
+        );
+        assert_eq!(eval("1\u{202f}000 + 1 + 23", "fr"), Ok(Value::Int(1024)));
+        assert_eq!(eval("1'000'000 / 1'000", "de-CH"), Ok(Value::Int(1000)));
+        assert_eq!(eval("1,000,000.0 // 3", "en-US"), Ok(Value::Int(333333)));
+        assert_eq!(eval("(1,000)", "en"), Ok(Value::Int(1000)));
+        assert_eq!(
+            eval("1,5 * 2", "de"),
+            Err("Parse error: Expected whole number (a space after ',' separates arguments), found '1,5'".into())
+        );
+        assert_eq!(
+            eval("1.23 + 1", "de"),
+            Err(
+                "Parse error: Expected groups of three digits between separators, found '1.23'"
+                    .into()
+            )
+        );
+        assert_eq!(
+            eval("1234.567", "de"),
+            Err(
+                "Parse error: Expected groups of three digits between separators, found '1234.567'"
+                    .into()
+            )
+        );
+        assert_eq!(
+            eval("2.5", "en"),
+            Err("Parse error: Expected whole number, found '2.5'".into())
+        );
+        assert_eq!(
+            eval("1,000,000,000,000 + 1", "en"),
+            Err("Invalid number: out of the 32-bit range".into())
+        );
+        assert_eq!(eval("2.147.483.647,00", "de"), Ok(Value::Int(i32::MAX)));
+        assert!(NumberFormat::locale("tlh").is_none());
+
+        let options = EvalOptions::default().number_format(NumberFormat::new(',', Some('.')));
+        assert_eq!(
+            parse_with("1.000 * 2", &options).unwrap(),
+            parse("1000 * 2").unwrap()
+        );
+        assert!(FixedEvaluator::<8, 4>::new()
+            .eval("1.0000", &options)
+            .is_err());
+        assert!(Expr::new("1.000").eval().is_err());
+    }
+
+    #[test]
+    fn test_overflow_policy() {
+        let wrapping = EvalOptions::default().overflow(OverflowPolicy::Wrapping);
+        let saturating = EvalOptions::default().overflow(OverflowPolicy::Saturating);
+
+        let eval = |src, options: &EvalOptions| Expr::with_options(src, options.clone()).eval();
+
+        assert_eq!(eval("2147483647 + 1", &wrapping).unwrap(), i32::MIN);
+        assert_eq!(eval("2147483647 + 1", &saturating).unwrap(), i32::MAX);
+        assert_eq!(eval("0 - 2147483647 - 2", &wrapping).unwrap(), i32::MAX);
+        assert_eq!(eval("0 - 2147483647 - 2", &saturating).unwrap(), i32::MIN);
+        assert_eq!(eval("65536 * 65536", &wrapping).unwrap(), 0);
+        assert_eq!(eval("2 ^ 40", &saturating).unwrap(), i32::MAX);
+        assert_eq!(
+            eval("(0 - 2147483647 - 1) / (0 - 1)", &wrapping).unwrap(),
+            i32::MIN
+        );
+        assert_eq!(
+            eval("(0 - 2147483647 - 1) // (0 - 1)", &saturating).unwrap(),
+            i32::MAX
+        );
+        assert_eq!(
+            eval("(0 - 2147483647 - 1) mod (0 - 1)", &EvalOptions::default()).unwrap(),
+            0
+        );
+        assert!(eval("1 / 0", &wrapping).is_err());
+    }
+
+    #[test]
+    fn test_negation_and_negative_exponent() {
+        assert_eq!(Expr::new("-3 + 5").eval().unwrap(), 2);
+        assert_eq!(Expr::new("2 - -3").eval().unwrap(), 5);
+        assert_eq!(Expr::new("-(2 + 3) * 2").eval().unwrap(), -10);
+        assert_eq!(Expr::new("(-2) ^ 3").eval().unwrap(), -8);
+        assert_eq!(Expr::new("-2 ^ 3").eval().unwrap(), -8);
+        assert_eq!(Expr::new("(-2) ^ 2").eval().unwrap(), 4);
+        assert_eq!(Expr::new("-2 ^ 2").eval().unwrap(), -4);
+        assert_eq!(
+            Expr::new("2 ^ -1").eval().unwrap_err(),
+            ExprError::NegativeExponent {
+                base: 2,
+                exponent: -1
+            }
+        );
+        assert_eq!(
+            Expr::new("2 ^ (1 - 3)").eval().unwrap_err().to_string(),
+            "Negative exponent: 2 ^ -2"
+        );
+    }
+
+    #[test]
+    fn test_word_operators() {
+        assert_eq!(
+            Tokenizer::new("x and not y_2")
+                .map(|token| token.value)
+                .collect::<Vec<_>>(),
+            vec![
+                Token::Ident("x".into()),
+                Token::And,
+                Token::Not,
+                Token::Ident("y_2".into())
+            ]
+        );
+        assert_eq!(Expr::new("1 and 0").eval().unwrap(), 0);
+        assert_eq!(Expr::new("0 or 3").eval().unwrap(), 1);
+        assert_eq!(Expr::new("not 0 and 2").eval().unwrap(), 1);
+        assert_eq!(Expr::new("not 1 + 1").eval().unwrap(), 0);
+        assert_eq!(Expr::new("0 and 1 or 1").eval().unwrap(), 1);
+        assert_eq!(Expr::new("1 + 1 in (1, 2, 3)").eval().unwrap(), 1);
+        assert_eq!(Expr::new("not 5 in (1, 2)").eval().unwrap(), 1);
+        assert!(Expr::new("5 in 5").eval().is_err());
+        assert_eq!(
+            Expr::new("band + 1").eval().unwrap_err(),
+            ExprError::UnknownVariable("band".into())
+        );
+    }
+
+    #[test]
+    fn test_division_modes() {
+        let eval = |src, division| {
+            Expr::with_options(src, EvalOptions::default().division(division))
+                .eval()
+                .unwrap()
+        };
+
+        let cases = [
+            ("-7 / 2", [-3, -4, -4]),
+            ("-7 % 2", [-1, 1, 1]),
+            ("7 / -2", [-3, -4, -3]),
+            ("7 % -2", [1, -1, 1]),
+            ("-7 / -2", [3, 3, 4]),
+            ("-7 mod -2", [-1, -1, 1]),
+            ("-7 // 2", [-4, -4, -4]),
+        ];
+        let modes = [
+            DivisionMode::Truncating,
+            DivisionMode::Floor,
+            DivisionMode::Euclidean,
+        ];
+
+        for (src, expected) in cases {
+            for (mode, want) in modes.into_iter().zip(expected) {
+                assert_eq!(eval(src, mode), want, "{} in {:?} mode", src, mode);
+            }
+        }
+        assert_eq!(
+            Expr::new("7 % 0").eval().unwrap_err(),
+            ExprError::DivisionByZero
+        );
+    }
+
+    #[test]
+    fn test_language_level() {
+        let eval = |src, options: EvalOptions| Expr::with_options(src, options).eval().unwrap();
+        let v2 = EvalOptions::default().level(LanguageLevel::V2);
+
+        assert_eq!(eval("-7 % 2", EvalOptions::default()), Value::Int(-1));
+        assert_eq!(eval("-7 % 2", v2.clone()), Value::Int(1));
+        assert_eq!(eval("7 mod -2", v2.clone()), Value::Int(-1));
+        // `/` still follows the division mode; `%` pairs with `//`.
+        let euclid = v2.clone().division(DivisionMode::Euclidean);
+        assert_eq!(eval("7 / -2 * -2 + 7 % -2", euclid.clone()), Value::Int(5));
+        assert_eq!(eval("7 // -2 * -2 + 7 % -2", euclid), Value::Int(7));
+        assert_eq!(
+            eval("[-7, -5] % 3", v2.intervals(true)),
+            Value::Interval(Interval::new(0, 2).unwrap())
+        );
+        assert_eq!(LanguageLevel::default(), LanguageLevel::V1);
+    }
+
+    #[test]
+    fn test_fuel() {
+        let mut ctx = Context::new();
+        ctx.define_function("f0", &["x"], "x + 1").unwrap();
+        for i in 1..=20 {
+            let body = format!("f{0}(f{0}(x))", i - 1);
+            ctx.define_function(&format!("f{}", i), &["x"], &body)
+                .unwrap();
+        }
+
+        let fuel = EvalOptions::default().fuel(10_000);
+        let eval = |src| {
+            Expr::with_options(src, fuel.clone())
+                .with_context(&ctx)
+                .eval()
+        };
+        assert_eq!(eval("f5(0) * 2"), Ok(Value::Int(64)));
+        assert_eq!(
+            eval("f20(0)"),
+            Err(ExprError::BudgetExceeded { fuel: 10_000 })
+        );
+
+        let ast = parse("f20(0)").unwrap();
+        assert_eq!(
+            ast.eval_with(&fuel, &ctx),
+            Err(ExprError::BudgetExceeded { fuel: 10_000 })
+        );
+        assert_eq!(
+            ast.compile(&fuel).eval(&ctx),
+            Err(ExprError::BudgetExceeded { fuel: 10_000 })
+        );
+
+        // Every operator and call costs the same in each evaluator.
+        let src = "-f1(2) * 3 + not 0";
+        let exact = EvalOptions::default().fuel(9);
+        let short = EvalOptions::default().fuel(8);
+        assert_eq!(
+            Expr::with_options(src, exact.clone())
+                .with_context(&ctx)
+                .eval(),
+            Ok(Value::Int(-11))
+        );
+        for options in [exact, short] {
+            let expected = Expr::with_options(src, options.clone())
+                .with_context(&ctx)
+                .eval();
+            let ast = parse(src).unwrap();
+            assert_eq!(ast.eval_with(&options, &ctx), expected);
+            assert_eq!(ast.compile(&options).eval(&ctx), expected);
+        }
+
+        let timeout = EvalOptions::default().timeout(std::time::Duration::from_millis(10));
+        assert!(matches!(
+            Expr::with_options("f20(0)", timeout)
+                .with_context(&ctx)
+                .eval(),
+            Err(ExprError::BudgetExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let token = CancelToken::new();
+        let options = EvalOptions::default().cancel_token(token.clone());
+        let handle = std::thread::spawn(move || {
+            let mut ctx = Context::new();
+            ctx.define_function("f0", &["x"], "x + 1").unwrap();
+            for i in 1..=30 {
+                let body = format!("f{0}(f{0}(x))", i - 1);
+                ctx.define_function(&format!("f{}", i), &["x"], &body)
+                    .unwrap();
+            }
+            Expr::with_options("f30(0)", options)
+                .with_context(&ctx)
+                .eval()
+        });
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        token.cancel();
+        assert_eq!(handle.join().unwrap(), Err(ExprError::Cancelled));
+        assert_eq!(
+            Expr::with_options("1 + 1", EvalOptions::default().cancel_token(token)).eval(),
+            Err(ExprError::Cancelled)
+        );
+    }
+
+    #[test]
+    fn test_intervals() {
+        let eval = |src| Expr::with_options(src, EvalOptions::default().intervals(true)).eval();
+        let interval = |lo, hi| Value::Interval(Interval::new(lo, hi).unwrap());
+
+        assert_eq!(eval("[1, 2] * 3 + [0, 1]").unwrap(), interval(3, 7));
+        assert_eq!(eval("[-1, 2] * [-3, 1]").unwrap(), interval(-6, 3));
+        assert_eq!(eval("[1, 2] - [0, 1]").unwrap(), interval(0, 2));
+        assert_eq!(eval("-[1, 2]").unwrap(), interval(-2, -1));
+        assert_eq!(eval("10 / [2, 5]").unwrap(), interval(2, 5));
+        assert_eq!(eval("[-2, 3] ^ 2").unwrap(), interval(0, 9));
+        assert_eq!(eval("[2, 3] ^ [1, 3]").unwrap(), interval(2, 27));
+        assert_eq!(eval("[-5, 5] % 3").unwrap(), interval(-2, 2));
+        assert_eq!(eval("[7, 7] % [2, 2]").unwrap(), interval(1, 1));
+        assert_eq!(eval("[1 + 1, 2 * 2]").unwrap().to_string(), "[2, 4]");
+        assert_eq!(eval("1 + 2").unwrap(), 3);
+
+        assert_eq!(eval("1 / [-1, 1]").unwrap_err(), ExprError::DivisionByZero);
+        assert_eq!(
+            eval("[3, 1]").unwrap_err(),
+            ExprError::InvalidInterval { lo: 3, hi: 1 }
+        );
+        assert!(eval("[1, 2] and 1").is_err());
+        assert!(eval("[1, [2, 3]]").is_err());
+        assert!(Expr::new("[1, 2]").eval().is_err());
+    }
+
+    #[test]
+    fn test_modular_arithmetic() {
+        let eval = |src, p| Expr::with_options(src, EvalOptions::default().modulus(p)).eval();
+
+        assert_eq!(eval("3 + 5", 7).unwrap(), 1);
+        assert_eq!(eval("2 - 5", 7).unwrap(), 4);
+        assert_eq!(eval("-1", 7).unwrap(), 6);
+        assert_eq!(eval("10", 7).unwrap(), 3);
+        assert_eq!(eval("1 / 3", 7).unwrap(), 5);
+        assert_eq!(eval("3 ^ 10", 7).unwrap(), 4);
+        assert_eq!(eval("3 ^ (5 + 5)", 7).unwrap(), 4);
+        assert_eq!(eval("2 ^ -1", 7).unwrap(), 4);
+        assert_eq!(
+            eval("123456789 * 987654321", 1_000_000_007).unwrap(),
+            259106859
+        );
+
+        assert_eq!(eval("1 / 0", 7).unwrap_err(), ExprError::DivisionByZero);
+        assert_eq!(eval("1 / 7", 7).unwrap_err(), ExprError::DivisionByZero);
+        assert_eq!(
+            eval("1 / 4", 6).unwrap_err(),
+            ExprError::NotInvertible {
+                value: 4,
+                modulus: 6
+            }
+        );
+        assert_eq!(
+            eval("1 / 2", 6).unwrap_err().to_string(),
+            "2 has no inverse modulo 6"
+        );
+        assert!(eval("7 % 2", 7).is_err());
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut ctx = Context::new();
+        ctx.set("x", 3);
+        ctx.set("rate", 2);
+
+        assert_eq!(
+            Expr::new("x * rate + 1").with_context(&ctx).eval().unwrap(),
+            7
+        );
+        assert_eq!(
+            Expr::new("x + y").with_context(&ctx).eval().unwrap_err(),
+            ExprError::UnknownVariable("y".into())
+        );
+        assert_eq!(
+            split_assignment(" total = x + 1"),
+            Some(("total", " x + 1"))
+        );
+        assert_eq!(split_assignment("x + 1"), None);
+        assert_eq!(split_assignment("1 = 2"), None);
+        assert!(Expr::new("x = 1").with_context(&ctx).eval().is_err());
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut ctx = Context::new();
+        ctx.set("x", 10);
+        ctx.define_function("sq", &["x"], "x * x").unwrap();
+        ctx.define_function("area", &["w", "h"], "w * h + x")
+            .unwrap();
+        ctx.define_function("one", &[], "1").unwrap();
+
+        let eval = |src| Expr::new(src).with_context(&ctx).eval();
+        assert_eq!(eval("sq(3) + 1").unwrap(), 10);
+        assert_eq!(eval("sq(sq(2)) - x").unwrap(), 6);
+        assert_eq!(eval("area(2, 3) * one()").unwrap(), 16);
+        assert_eq!(
+            eval("sq(1, 2)").unwrap_err(),
+            ExprError::WrongArity {
+                name: "sq".into(),
+                expected: 1,
+                found: 2
+            }
+        );
+        assert_eq!(
+            eval("cube(2)").unwrap_err(),
+            ExprError::UnknownFunction("cube".into())
+        );
+        assert_eq!(
+            Expr::new("sq(2)").eval().unwrap_err().to_string(),
+            "Unknown function: sq"
+        );
+        assert!(eval("sq(2").is_err());
+        assert_eq!(ctx.function("area").unwrap().params(), ["w", "h"]);
+
+        assert!(ctx.define_function("f", &["n"], "f(n - 1)").is_err());
+        ctx.define_function("g", &["n"], "h(n) + 1").unwrap();
+        assert!(ctx.define_function("h", &["n"], "g(n) * 2").is_err());
+        assert!(ctx.define_function("h", &["n"], "sq(n) * 2").is_ok());
+        assert_eq!(Expr::new("g(3)").with_context(&ctx).eval().unwrap(), 19);
+        let h = ctx.remove_function("h").unwrap();
+        assert!(ctx.function("h").is_none());
+        assert!(ctx.set_function("h", h.clone()).unwrap().is_none());
+        let g = Function::new(&["n"], "h(n) - 1").unwrap();
+        assert_eq!(ctx.set_function("g", g).unwrap().unwrap().params(), ["n"]);
+        assert_eq!(Expr::new("g(3)").with_context(&ctx).eval().unwrap(), 17);
+        assert!(ctx.set_function("sq", h).is_err());
+        let f = Function::new(&["n"], "g(n)").unwrap();
+        assert!(ctx.set_function("h", f).is_err());
+
+        // A body sees the context's `y`, not the caller's parameter `y`.
+        let mut ctx = Context::new();
+        ctx.set("y", 100);
+        ctx.define_function("g", &["n"], "y + n").unwrap();
+        ctx.define_function("f", &["y"], "g(0)").unwrap();
+        let ast = crate::parse("f(5)").unwrap();
+        assert_eq!(
+            Expr::new("f(5)").with_context(&ctx).eval(),
+            Ok(Value::Int(100))
+        );
+        assert_eq!(
+            ast.eval_with(&EvalOptions::default(), &ctx),
+            Ok(Value::Int(100))
+        );
+        assert_eq!(
+            Expr::new("f(5)").compile().unwrap().eval(&ctx),
+            Ok(Value::Int(100))
+        );
+    }
+
+    #[test]
+    fn test_builtins() {
+        let mut expr = Expr::new("hex(255) + 1");
+        assert_eq!(expr.eval(), Ok(Value::Int(256)));
+        assert_eq!(expr.radix(), Some(16));
+        let mut expr = Expr::new("bin(oct(8)) * 2");
+        assert_eq!(expr.eval(), Ok(Value::Int(16)));
+        assert_eq!(expr.radix(), Some(2));
+        assert_eq!(Expr::new("1 + 1").radix(), None);
+        assert_eq!(
+            Expr::new("hex(1, 2)").eval(),
+            Err(ExprError::WrongArity {
+                name: "hex".into(),
+                expected: 1,
+                found: 2
+            })
+        );
+
+        let mut ctx = Context::new();
+        assert_eq!(
+            crate::parse("hex(2) * 3")
+                .unwrap()
+                .eval_with(&EvalOptions::default(), &ctx),
+            Ok(Value::Int(6))
+        );
+        assert_eq!(
+            Expr::new("oct(7) + 1").compile().unwrap().eval(&ctx),
+            Ok(Value::Int(8))
+        );
+        ctx.define_function("hex", &["n"], "n * 2").unwrap();
+        let mut expr = Expr::new("hex(4)").with_context(&ctx);
+        assert_eq!(expr.eval(), Ok(Value::Int(8)));
+        assert_eq!(expr.radix(), None);
+    }
+
+    #[test]
+    fn test_compile() {
+        let compiled = Expr::new("x * x - 2 * x + 1").compile().unwrap();
+        let f = compiled.clone().into_fn();
+        let mut ctx = Context::new();
+        for x in -5..=5 {
+            ctx.set("x", x);
+            let expected = Expr::new("x * x - 2 * x + 1").with_context(&ctx).eval();
+            assert_eq!(compiled.eval(&ctx), expected);
+            assert_eq!(f(&ctx), expected);
+        }
+
+        let options = EvalOptions::default().modulus(7);
+        let compiled = Expr::with_options("x ^ 3 ** 2 / 3", options.clone())
+            .compile()
+            .unwrap();
+        ctx.set("x", 10);
+        assert_eq!(
+            compiled.eval(&ctx),
+            Expr::with_options("x ^ 3 ** 2 / 3", options)
+                .with_context(&ctx)
+                .eval()
+        );
+
+        assert_eq!(
+            Expr::new("1 +").compile().unwrap_err(),
+            ExprError::UnexpectedToken {
+                found: None,
+                expected: "number or parenthesis",
+                span: Some(Span { start: 3, end: 3 })
+            }
+        );
+        assert_eq!(
+            Expr::new("y").compile().unwrap().eval(&ctx),
+            Err(ExprError::UnknownVariable("y".into()))
+        );
+    }
+
+    #[test]
+    fn test_on_reject() {
+        let rejected = Arc::new(Mutex::new(Vec::new()));
+        let log = rejected.clone();
+        let options = EvalOptions::default().on_reject(move |src, err| {
+            log.lock().unwrap().push(format!("{}: {}", src, err));
+        });
+
+        assert!(Expr::with_options("1 +", options.clone()).eval().is_err());
+        assert!(Expr::with_options("(2", options.clone()).compile().is_err());
+        assert!(Expr::with_options("2 3", options.clone()).eval().is_err());
+        assert!(Expr::with_options("1 / 0", options.clone()).eval().is_err());
+        assert!(Expr::with_options("x", options.clone()).eval().is_err());
+        assert_eq!(
+            Expr::with_options("1 + 1", options).eval(),
+            Ok(Value::Int(2))
+        );
+
+        assert_eq!(
+            *rejected.lock().unwrap(),
+            [
+                "1 +: Parse error: Expected number or parenthesis, found end of input",
+                "(2: Parse error: Unclosed parenthesis",
+                "2 3: Parse error: Expected operator or end of input, found '3'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_warnings() {
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let log = heard.clone();
+        let options = EvalOptions::default().on_warning(move |src, warning| {
+            log.lock()
+                .unwrap()
+                .push(format!("{}: {}", src, warning.message()));
+        });
+
+        let mut expr = Expr::with_options("2 ^ 3 ** 2 + 7 / 2 + 8 / 2", options.clone());
+        assert_eq!(expr.eval(), Ok(Value::Int(519)));
+        let spans = expr
+            .warnings()
+            .iter()
+            .map(|warning| (warning.kind(), warning.span()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                (WarningKind::PowerChain, Span { start: 6, end: 8 }),
+                (WarningKind::InexactDivision, Span { start: 15, end: 16 })
+            ]
+        );
+        assert_eq!(
+            *heard.lock().unwrap(),
+            [
+                "2 ^ 3 ** 2 + 7 / 2 + 8 / 2: '^' groups to the right, so 'a ^ b ^ c' is 'a ^ (b ^ c)'",
+                "2 ^ 3 ** 2 + 7 / 2 + 8 / 2: '7 / 2' discards a remainder",
+            ]
+        );
+
+        for quiet in ["(2 ^ 3) ^ 2", "-2 ^ 2", "2 ^ (3 ^ 2)", "7 // 2", "8 / 2"] {
+            let mut expr = Expr::new(quiet);
+            expr.eval().unwrap();
+            assert!(expr.warnings().is_empty(), "{}", quiet);
+        }
+        // Modular division is exact.
+        let mut expr = Expr::with_options("3 / 2", EvalOptions::default().modulus(7));
+        expr.eval().unwrap();
+        assert!(expr.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let parens = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(Expr::new(&parens).eval(), Ok(Value::Int(1)));
+
+        let unclosed = format!("{}1", "(".repeat(100_000));
+        assert_eq!(
+            Expr::new(&unclosed).eval(),
+            Err(ExprError::UnclosedParen {
+                span: Some(Span {
+                    start: 100_001,
+                    end: 100_001
+                })
+            })
+        );
+
+        let negations = format!("{}7", "- not ".repeat(50_000));
+        assert_eq!(Expr::new(&negations).eval(), Ok(Value::Int(-1)));
+
+        let powers = format!("{}1", "1 ^ ".repeat(100_000));
+        assert_eq!(
+            Expr::with_options(&powers, EvalOptions::default().modulus(5)).eval(),
+            Ok(Value::Int(1))
+        );
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let limited = |src| {
+            let options = EvalOptions::default().intervals(true).max_depth(3);
+            let expr = Expr::with_options(src, options.clone()).eval();
+            let compiled = Expr::with_options(src, options.clone())
+                .compile()
+                .and_then(|compiled| compiled.eval(&Context::new()));
+            let tree = crate::parse(src).and_then(|ast| ast.eval_with(&options, &Context::new()));
+            assert_eq!(expr, compiled, "{}", src);
+            assert_eq!(expr, tree, "{}", src);
+            expr
+        };
+        let too_deep = Err(ExprError::DepthLimitExceeded { limit: 3 });
+
+        assert_eq!(limited("(1 + 2) * 3"), Ok(Value::Int(9)));
+        assert_eq!(limited("1 + 2 + 3 + 4 + 5 + 6"), Ok(Value::Int(21)));
+        assert_eq!(limited("2 ^ 2 ^ 2"), Ok(Value::Int(16)));
+        assert_eq!(limited("((1 + 2)) * 3"), too_deep);
+        assert_eq!(limited("2 ^ 2 ^ 2 ^ 2"), too_deep);
+        assert_eq!(limited("- - - 1"), too_deep);
+        assert_eq!(limited("1 in (1, (2))"), Ok(Value::Int(1)));
+        assert_eq!(limited("1 in (1, ((2)))"), too_deep);
+        assert_eq!(
+            limited("[1, (2)] + 1"),
+            Ok(Value::Interval(Interval::new(2, 3).unwrap()))
+        );
+        assert_eq!(limited("[1, ((2))]"), too_deep);
+
+        let deep = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        let options = EvalOptions::default().max_depth(100);
+        assert_eq!(
+            Expr::with_options(&deep, options.clone()).eval(),
+            Err(ExprError::DepthLimitExceeded { limit: 100 })
+        );
+        assert_eq!(
+            Expr::with_options(&deep, options).compile().unwrap_err(),
+            ExprError::DepthLimitExceeded { limit: 100 }
+        );
+    }
+
+    #[test]
+    fn test_trace_vars() {
+        let mut ctx = Context::new();
+        ctx.set("base", 1000);
+        ctx.set("rate", 7);
+        ctx.define_function("pct", &["n", "p"], "n * p / 100")
+            .unwrap();
+
+        let mut expr = Expr::with_options(
+            "base + pct(base, rate) + rate",
+            EvalOptions::default().trace_vars(true),
+        )
+        .with_context(&ctx);
+        assert_eq!(expr.eval().unwrap(), 1077);
+        assert_eq!(
+            expr.trace(),
+            [
+                ("base".to_string(), Value::Int(1000)),
+                ("rate".to_string(), Value::Int(7)),
+                ("pct(1000, 7)".to_string(), Value::Int(70)),
+            ]
+        );
+
+        let mut expr = Expr::new("base * rate").with_context(&ctx);
+        expr.eval().unwrap();
+        assert!(expr.trace().is_empty());
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{
+    meter::Meter,
+    token::{Spanned, Token, Tokenizer},
+    EvalOptions, ExprError, PrecedenceTable, Result, Span,
+};
+
+/// An evaluator for microcontrollers and other places without a heap to
+/// spare: it works in fixed buffers of `TOKENS` tokens and `STACK` pending
+/// operators and parentheses, which live wherever the caller puts the
+/// evaluator, and fails with [`ExprError::CapacityExceeded`] when input
+/// needs more.
+///
+/// Evaluating a well-formed expression allocates nothing, provided the
+/// operator table in the options is the default one or otherwise set up
+/// beforehand; errors may carry a message that does. Only integer
+/// arithmetic is supported: there are no variables or functions to resolve
+/// identifiers against, and intervals, `in`, modular arithmetic and custom
+/// operators, see [`OperatorTable::infix`](crate::OperatorTable::infix), are
+/// rejected with [`ExprError::Unsupported`].
+#[derive(Debug)]
+pub struct FixedEvaluator<const TOKENS: usize, const STACK: usize> {
+    tokens: [Option<Spanned<Token>>; TOKENS],
+    stack: [Option<Slot>; STACK],
+}
+
+// An operation waiting for the operand being evaluated.
+#[derive(Debug)]
+enum Slot {
+    Binary { lhs: i32, op: Token },
+    // `-` or `not`.
+    Prefix(Token),
+    Open,
+}
+
+impl Slot {
+    // How tightly an operator must bind to belong in the pending operand.
+    fn min_prec(&self, table: &PrecedenceTable) -> i32 {
+        match self {
+            Slot::Binary { op, .. } => table.rhs_precedence(op),
+            Slot::Prefix(op) => table.operand_precedence(op),
+            Slot::Open => 0,
+        }
+    }
+}
+
+impl<const TOKENS: usize, const STACK: usize> Default for FixedEvaluator<TOKENS, STACK> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const TOKENS: usize, const STACK: usize> FixedEvaluator<TOKENS, STACK> {
+    pub const fn new() -> Self {
+        Self {
+            tokens: [const { None }; TOKENS],
+            stack: [const { None }; STACK],
+        }
+    }
+
+    /// Evaluates `src` under `options`, giving the same result as
+    /// [`Expr::eval`](crate::Expr::eval) would for input within capacity.
+    pub fn eval(&mut self, src: &str, options: &EvalOptions) -> Result<i32> {
+        let result = self.tokenize(src, options).and_then(|len| {
+            let meter = Meter::new(options);
+            Run {
+                src,
+                tokens: &self.tokens[..len],
+                pos: 0,
+                stack: &mut self.stack,
+                depth: 0,
+                options,
+                meter: &meter,
+            }
+            .eval()
+        });
+        options.report(src, result)
+    }
+
+    fn tokenize(&mut self, src: &str, options: &EvalOptions) -> Result<usize> {
+        if options.modulus.is_some() {
+            return Err(ExprError::Unsupported(
+                "modular arithmetic in fixed-capacity mode".into(),
+            ));
+        }
+
+        let mut len = 0;
+        for token in Tokenizer::borrowing(src, &options.operators).with_numbers(options.numbers) {
+            if let Token::Custom { spelling, .. } = token.value {
+                return Err(ExprError::Unsupported(format!(
+                    "custom operator '{}' in fixed-capacity mode",
+                    spelling
+                )));
+            }
+            let slot = self
+                .tokens
+                .get_mut(len)
+                .ok_or(ExprError::CapacityExceeded {
+                    what: "tokens",
+                    limit: TOKENS,
+                })?;
+            *slot = Some(token);
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+// One evaluation over the tokenized input.
+struct Run<'a, const STACK: usize> {
+    src: &'a str,
+    tokens: &'a [Option<Spanned<Token>>],
+    pos: usize,
+    stack: &'a mut [Option<Slot>; STACK],
+    depth: usize,
+    options: &'a EvalOptions,
+    meter: &'a Meter,
+}
+
+impl<const STACK: usize> Run<'_, STACK> {
+    fn token(&self, pos: usize) -> Option<&Spanned<Token>> {
+        self.tokens.get(pos).and_then(Option::as_ref)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.token(self.pos).map(|token| &token.value)
+    }
+
+    // Whether an operand follows with an implied `*` before it.
+    fn implicit(&self) -> bool {
+        self.options.implicit_mul && self.peek().is_some_and(Token::follows_implicitly)
+    }
+
+    // Parse errors at the next token, or right after the last one.
+    fn unexpected(&self, expected: &'static str) -> ExprError {
+        ExprError::unexpected(self.src, self.here(), expected)
+    }
+
+    fn unclosed(&self) -> ExprError {
+        ExprError::unclosed(self.here())
+    }
+
+    fn here(&self) -> Span {
+        match (self.token(self.pos), self.pos.checked_sub(1)) {
+            (Some(token), _) => token.span,
+            (None, Some(last)) => {
+                let end = self.token(last).map_or(0, |token| token.span.end);
+                Span { start: end, end }
+            }
+            (None, None) => Span::default(),
+        }
+    }
+
+    fn push(&mut self, slot: Slot) -> Result<()> {
+        let top = self
+            .stack
+            .get_mut(self.depth)
+            .ok_or(ExprError::CapacityExceeded {
+                what: "stack slots",
+                limit: STACK,
+            })?;
+        *top = Some(slot);
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn top(&self) -> Option<&Slot> {
+        self.stack[..self.depth].last().and_then(Option::as_ref)
+    }
+
+    fn pop(&mut self) -> Option<Slot> {
+        self.depth = self.depth.checked_sub(1)?;
+        self.stack[self.depth].take()
+    }
+
+    // Alternates between reading an operand and the operator after it,
+    // keeping unfinished operations on the stack, like `Expr::compute_expr`.
+    fn eval(mut self) -> Result<i32> {
+        loop {
+            let mut value = self.operand()?;
+            loop {
+                let next = match self.peek() {
+                    Some(token) if token.is_operator() => self.options.precedence.precedence(token),
+                    _ if self.implicit() => self.options.precedence.precedence(&Token::Multiply),
+                    _ => 0,
+                };
+                value = self.reduce(value, next)?;
+
+                match self.peek() {
+                    Some(Token::In) => {
+                        return Err(ExprError::Unsupported("'in' in fixed-capacity mode".into()))
+                    }
+                    Some(token) if token.is_operator() => {
+                        let op = token.clone();
+                        self.pos += 1;
+                        self.push(Slot::Binary { lhs: value, op })?;
+                        break;
+                    }
+                    _ if self.implicit() => {
+                        let op = Token::Multiply;
+                        self.push(Slot::Binary { lhs: value, op })?;
+                        break;
+                    }
+                    _ if self.depth == 0 => {
+                        if self.peek().is_some() {
+                            return Err(self.unexpected("operator or end of input"));
+                        }
+                        return Ok(value);
+                    }
+                    Some(Token::RightParen) => {
+                        self.pos += 1;
+                        self.pop();
+                    }
+                    _ => return Err(self.unclosed()),
+                }
+            }
+        }
+    }
+
+    // Reads up to the end of an operand, pushing any prefix operators and
+    // parentheses on the way.
+    fn operand(&mut self) -> Result<i32> {
+        loop {
+            let token = self.peek();
+            let slot = match token {
+                Some(&Token::Number(n)) => {
+                    self.pos += 1;
+                    return Ok(n);
+                }
+                Some(Token::Minus | Token::Not) => Slot::Prefix(token.cloned().unwrap()),
+                Some(Token::LeftParen) => Slot::Open,
+                Some(Token::Ident(name)) => {
+                    return Err(match self.token(self.pos + 1).map(|token| &token.value) {
+                        Some(Token::LeftParen) => ExprError::UnknownFunction(name.clone()),
+                        _ => ExprError::UnknownVariable(name.clone()),
+                    })
+                }
+                Some(Token::LeftBracket) if self.options.intervals => {
+                    return Err(ExprError::Unsupported(
+                        "intervals in fixed-capacity mode".into(),
+                    ))
+                }
+                token => return Err(Token::operand_error(token, self.src, self.here())),
+            };
+            self.pos += 1;
+            self.push(slot)?;
+        }
+    }
+
+    // Applies the pending operations that `value` completes, those whose
+    // operand an operator of precedence `next` cannot extend.
+    fn reduce(&mut self, mut value: i32, next: i32) -> Result<i32> {
+        while let Some(top) = self.top() {
+            if matches!(top, Slot::Open) || next >= top.min_prec(&self.options.precedence) {
+                break;
+            }
+            self.meter.burn()?;
+            value = match self.pop() {
+                Some(Slot::Binary { lhs, op }) => op.compute(lhs, value, self.options)?,
+                Some(Slot::Prefix(Token::Minus)) => Token::Minus.compute(0, value, self.options)?,
+                _ => (value == 0) as i32,
+            };
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DivisionMode, Expr, LanguageLevel, OverflowPolicy, Value};
+
+    #[test]
+    fn test_matches_expr() {
+        let mut fixed = FixedEvaluator::<64, 16>::new();
+        for options in [
+            EvalOptions::default(),
+            EvalOptions::default()
+                .overflow(OverflowPolicy::Wrapping)
+                .division(DivisionMode::Floor)
+                .level(LanguageLevel::V2),
+        ] {
+            for src in [
+                "1 + 2 * 3",
+                "(1 + 2) * 3",
+                "-2 ^ 2",
+                "2 ^ 3 ^ 2",
+                "1 - 2 - 3",
+                "-7 / 2 + -7 % 2 - 7 // 2",
+                "not 1 + 1 or - not 0",
+                "2 ** 31",
+                "((4))",
+                "1 / 0",
+                "1 +",
+                "(1 + 2",
+                "1 + 2)",
+                "1 2",
+                "x + 1",
+                "f(2)",
+                "2 ^ -1",
+            ] {
+                let expected = Expr::with_options(src, options.clone())
+                    .eval()
+                    .map(|value| match value {
+                        Value::Int(n) => n,
+                        other => panic!("{}", other),
+                    });
+                assert_eq!(fixed.eval(src, &options), expected, "{}", src);
+            }
+        }
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut fixed = FixedEvaluator::<8, 2>::new();
+        let options = EvalOptions::default();
+        assert_eq!(fixed.eval("1 + 2 * 3 - 4", &options), Ok(3));
+        assert_eq!(
+            fixed.eval("1 + 2 + 3 + 4 + 5", &options),
+            Err(ExprError::CapacityExceeded {
+                what: "tokens",
+                limit: 8
+            })
+        );
+        assert_eq!(fixed.eval("-(1)", &options), Ok(-1));
+        assert_eq!(
+            fixed.eval("((-1))", &options),
+            Err(ExprError::CapacityExceeded {
+                what: "stack slots",
+                limit: 2
+            })
+        );
+        assert_eq!(
+            fixed.eval("1 in (1)", &options),
+            Err(ExprError::Unsupported("'in' in fixed-capacity mode".into()))
+        );
+        assert_eq!(
+            fixed.eval("[1, 2]", &options.clone().intervals(true)),
+            Err(ExprError::Unsupported(
+                "intervals in fixed-capacity mode".into()
+            ))
+        );
+        assert_eq!(
+            fixed.eval("1 + 2", &options.modulus(7)),
+            Err(ExprError::Unsupported(
+                "modular arithmetic in fixed-capacity mode".into()
+            ))
+        );
+    }
+}
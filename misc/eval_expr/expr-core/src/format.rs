@@ -0,0 +1,460 @@
+use crate::{Fraction, Value};
+
+/// How [`format_value`] writes a value. The default writes it as
+/// [`Display`](std::fmt::Display) does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValueFormat {
+    grouping: Option<char>,
+    radix: Option<u32>,
+    notation: Notation,
+    precision: Option<Precision>,
+    mixed: bool,
+}
+
+/// How [`format_value`] lays out the digits of a decimal number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Notation {
+    /// All the digits: `123456789`.
+    #[default]
+    Fixed,
+    /// One digit before the point and a power of ten: `1.23456789e8`.
+    Scientific,
+    /// Like scientific, but with an exponent that is a multiple of three, so
+    /// that it matches an SI prefix: `123.456789e6`.
+    Engineering,
+}
+
+/// How many digits [`format_value`] shows of a decimal number, rounding
+/// halves away from zero. Counts beyond [`PRECISION_LIMIT`] are taken as
+/// the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    /// Exactly this many digits after the point: 3 places make 1234
+    /// `1234.000`, or `1.234e3` in scientific notation.
+    Decimals(usize),
+    /// This many significant digits, at least one: 3 make 123456
+    /// `123000`, or `1.23e5` in scientific notation, and 7 `7.00`.
+    Significant(usize),
+}
+
+/// The most decimal places or significant digits a [`Precision`] shows. An
+/// `i32` has at most 10 digits, so any more are only zeros.
+pub const PRECISION_LIMIT: usize = 64;
+
+impl ValueFormat {
+    /// Separates the digits of each integer into groups of three with
+    /// `separator`, e.g. `109,876,463` or, with a space, `109 876 463`.
+    ///
+    /// So that the result reads back one way, digits in other bases are
+    /// grouped with `_` whatever `separator` is, in fours for hexadecimal
+    /// and binary, e.g. `0x8000_0000`; and the bounds of an interval are
+    /// grouped with a space when `separator` is `,`, e.g. `[-12 345, 100]`.
+    pub fn grouping(mut self, separator: char) -> Self {
+        self.grouping = Some(separator);
+        self
+    }
+
+    /// Lays out decimal numbers as `notation` says. Trailing zeros after the
+    /// point are dropped, so 1000 is `1e3`; numbers in other bases are
+    /// always written in full.
+    pub fn notation(mut self, notation: Notation) -> Self {
+        self.notation = notation;
+        self
+    }
+
+    /// Shows decimal numbers to `precision`, keeping trailing zeros.
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = Some(match precision {
+            Precision::Decimals(n) => Precision::Decimals(n.min(PRECISION_LIMIT)),
+            Precision::Significant(n) => Precision::Significant(n.clamp(1, PRECISION_LIMIT)),
+        });
+        self
+    }
+
+    /// Writes fractions greater than one as mixed numbers in
+    /// [`format_fraction`], e.g. `2 1/3` rather than `7/3`.
+    pub fn mixed_fractions(mut self) -> Self {
+        self.mixed = true;
+        self
+    }
+
+    /// Writes integers in base `radix`, 2, 8, 10 or 16, with a `0b`, `0o`
+    /// or `0x` prefix other than in base 10, e.g. `0x100` for 256 and
+    /// `-0b11` for -3.
+    ///
+    /// # Panics
+    ///
+    /// If `radix` is not one of those.
+    pub fn radix(mut self, radix: u32) -> Self {
+        assert!(
+            matches!(radix, 2 | 8 | 10 | 16),
+            "radix must be 2, 8, 10 or 16"
+        );
+        self.radix = Some(radix).filter(|&radix| radix != 10);
+        self
+    }
+}
+
+/// Writes `value` as `format` says, e.g. `109,876,463` for `1234567 * 89`
+/// with [`ValueFormat::grouping`]. Intervals are written as `[lo, hi]` with
+/// each bound formatted.
+pub fn format_value(value: &Value, format: &ValueFormat) -> String {
+    match value {
+        Value::Int(n) => format_int(*n, format),
+        Value::Interval(i) => {
+            // Only the comma between the bounds may be one.
+            let mut format = format.clone();
+            if format.grouping == Some(',') {
+                format.grouping = Some(' ');
+            }
+            format!(
+                "[{}, {}]",
+                format_int(i.lo(), &format),
+                format_int(i.hi(), &format)
+            )
+        }
+    }
+}
+
+/// Writes `fraction` in lowest terms, e.g. `7/3`, or `2 1/3` with
+/// [`ValueFormat::mixed_fractions`], with the digits of each number grouped
+/// as [`ValueFormat::grouping`] says. The other settings do not apply.
+pub fn format_fraction(fraction: &Fraction, format: &ValueFormat) -> String {
+    let mut out = String::from(if fraction.numer() < 0 { "-" } else { "" });
+    let numer = fraction.numer().unsigned_abs();
+    let denom = fraction.denom().unsigned_abs();
+    let (whole, numer) = match format.mixed {
+        true if numer > denom && denom > 1 => (Some(numer / denom), numer % denom),
+        _ => (None, numer),
+    };
+    if let Some(whole) = whole {
+        group_digits(&mut out, &whole.to_string(), 3, format.grouping);
+        out.push(' ');
+    }
+    group_digits(&mut out, &numer.to_string(), 3, format.grouping);
+    if denom > 1 {
+        out.push('/');
+        group_digits(&mut out, &denom.to_string(), 3, format.grouping);
+    }
+    out
+}
+
+/// Writes `n` as a programmer's calculator shows it, on four lines: in
+/// decimal, then as the two's complement bit pattern of a `bits`-bit word in
+/// hexadecimal, binary in groups of four and octal, each padded to the
+/// width of the word, e.g. `ff`, `1111 1111` and `377` for -1 in 8 bits.
+///
+/// A value that does not fit the word wraps, keeping its low `bits` bits,
+/// and the decimal line gives the value wrapped as well as the original.
+///
+/// # Panics
+///
+/// If `bits` is not 8, 16 or 32.
+pub fn format_programmer(n: i32, bits: u32) -> String {
+    assert!(matches!(bits, 8 | 16 | 32), "word size must be 8, 16 or 32");
+    let word = n as u32 & (u32::MAX >> (32 - bits));
+    // Sign-extended back from the top bit of the word.
+    let wrapped = (word << (32 - bits)) as i32 >> (32 - bits);
+
+    let width = bits as usize;
+    let mut binary = String::new();
+    group_digits(&mut binary, &format!("{:0width$b}", word), 4, Some(' '));
+    let decimal = match wrapped == n {
+        true => n.to_string(),
+        false => format!("{} (wrapped from {})", wrapped, n),
+    };
+    format!(
+        "dec {}\nhex {:0hex$x}\nbin {}\noct {:0oct$o}",
+        decimal,
+        word,
+        binary,
+        word,
+        hex = width / 4,
+        oct = width.div_ceil(3),
+    )
+}
+
+fn format_int(n: i32, format: &ValueFormat) -> String {
+    let mut out = String::from(if n < 0 { "-" } else { "" });
+    let magnitude = u64::from(n.unsigned_abs());
+    let (prefix, digits, group) = match format.radix {
+        Some(2) => ("0b", format!("{:b}", magnitude), 4),
+        Some(8) => ("0o", format!("{:o}", magnitude), 3),
+        Some(16) => ("0x", format!("{:x}", magnitude), 4),
+        _ if format.notation != Notation::Fixed => {
+            out.push_str(&exponential(magnitude, format.notation, format.precision));
+            return out;
+        }
+        _ => {
+            let (magnitude, decimals) = match format.precision {
+                Some(Precision::Significant(n)) => {
+                    let rounded = round_significant(magnitude, n);
+                    (rounded, n.saturating_sub(digit_count(rounded)))
+                }
+                Some(Precision::Decimals(decimals)) => (magnitude, decimals),
+                None => (magnitude, 0),
+            };
+            group_digits(&mut out, &magnitude.to_string(), 3, format.grouping);
+            if decimals > 0 {
+                out.push('.');
+                out.push_str(&"0".repeat(decimals));
+            }
+            return out;
+        }
+    };
+
+    out.push_str(prefix);
+    group_digits(&mut out, &digits, group, format.grouping.map(|_| '_'));
+    out
+}
+
+// Appends `digits` to `out`, with `separator` between groups of `group`
+// counted from the right.
+fn group_digits(out: &mut String, digits: &str, group: usize, separator: Option<char>) {
+    for (i, digit) in digits.chars().enumerate() {
+        if let Some(separator) = separator {
+            if i > 0 && (digits.len() - i).is_multiple_of(group) {
+                out.push(separator);
+            }
+        }
+        out.push(digit);
+    }
+}
+
+fn digit_count(n: u64) -> usize {
+    n.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+// `n` rounded to `significant` digits, halves away from zero; rounding up
+// may carry into a new digit, as 999 to 1000.
+fn round_significant(n: u64, significant: usize) -> u64 {
+    let dropped = digit_count(n).saturating_sub(significant.max(1));
+    let unit = 10u64.pow(dropped as u32);
+    let rounded = n / unit + u64::from(n % unit * 2 >= unit && dropped > 0);
+    rounded * unit
+}
+
+// `n` as a mantissa and a power of ten, as `notation` says, to `precision`
+// or else exactly, without trailing zeros.
+fn exponential(n: u64, notation: Notation, precision: Option<Precision>) -> String {
+    let exponent = |n: u64| {
+        let exponent = digit_count(n) - 1;
+        match notation {
+            Notation::Engineering => exponent - exponent % 3,
+            _ => exponent,
+        }
+    };
+    // The number of digits before the point.
+    let whole = |n: u64| digit_count(n) - exponent(n);
+
+    let (n, shown) = match precision {
+        Some(Precision::Significant(significant)) => {
+            let rounded = round_significant(n, significant);
+            (rounded, significant.max(whole(rounded)))
+        }
+        Some(Precision::Decimals(decimals)) => {
+            let rounded = round_significant(n, whole(n) + decimals);
+            (rounded, whole(rounded) + decimals)
+        }
+        None => (n, 0),
+    };
+
+    let mut digits = n.to_string();
+    if precision.is_some() {
+        digits.truncate(shown);
+        digits.extend(std::iter::repeat_n('0', shown.saturating_sub(digits.len())));
+    }
+    let (whole, fraction) = digits.split_at(whole(n).min(digits.len()));
+    let fraction = match precision {
+        Some(_) => fraction,
+        None => fraction.trim_end_matches('0'),
+    };
+    match fraction {
+        "" => format!("{}e{}", whole, exponent(n)),
+        _ => format!("{}.{}e{}", whole, fraction, exponent(n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval, Interval};
+
+    #[test]
+    fn test_radix() {
+        let format = |n, radix| format_value(&Value::Int(n), &ValueFormat::default().radix(radix));
+
+        assert_eq!(format(256, 16), "0x100");
+        assert_eq!(format(255, 2), "0b11111111");
+        assert_eq!(format(8, 8), "0o10");
+        assert_eq!(format(-3, 2), "-0b11");
+        assert_eq!(format(i32::MIN, 16), "-0x80000000");
+        assert_eq!(format(42, 10), "42");
+        assert_eq!(
+            format_value(
+                &Value::Int(0xbeef),
+                &ValueFormat::default().radix(2).grouping('_')
+            ),
+            "0b1011_1110_1110_1111"
+        );
+    }
+
+    #[test]
+    fn test_notation() {
+        let format =
+            |n, notation| format_value(&Value::Int(n), &ValueFormat::default().notation(notation));
+
+        assert_eq!(format(123456789, Notation::Scientific), "1.23456789e8");
+        assert_eq!(format(123456789, Notation::Engineering), "123.456789e6");
+        assert_eq!(format(123456789, Notation::Fixed), "123456789");
+        assert_eq!(format(-1500, Notation::Scientific), "-1.5e3");
+        assert_eq!(format(-1500, Notation::Engineering), "-1.5e3");
+        assert_eq!(format(15000, Notation::Engineering), "15e3");
+        assert_eq!(format(7, Notation::Scientific), "7e0");
+        assert_eq!(format(0, Notation::Engineering), "0e0");
+        assert_eq!(
+            format_value(
+                &Value::Int(4096),
+                &ValueFormat::default()
+                    .notation(Notation::Scientific)
+                    .radix(16)
+            ),
+            "0x1000"
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let format = |n, notation, precision| {
+            let format = ValueFormat::default()
+                .notation(notation)
+                .precision(precision);
+            format_value(&Value::Int(n), &format)
+        };
+        use Notation::*;
+        use Precision::*;
+
+        assert_eq!(format(123456789, Scientific, Significant(5)), "1.2346e8");
+        assert_eq!(format(123456789, Scientific, Decimals(2)), "1.23e8");
+        assert_eq!(format(123456789, Engineering, Significant(5)), "123.46e6");
+        assert_eq!(format(123456789, Engineering, Significant(2)), "120e6");
+        assert_eq!(format(123456789, Engineering, Decimals(1)), "123.5e6");
+        assert_eq!(format(999999, Scientific, Significant(3)), "1.00e6");
+        assert_eq!(format(999999, Engineering, Decimals(1)), "1.0e6");
+        assert_eq!(format(-1500, Scientific, Decimals(0)), "-2e3");
+        assert_eq!(format(1000, Scientific, Decimals(3)), "1.000e3");
+        assert_eq!(format(7, Scientific, Significant(3)), "7.00e0");
+        assert_eq!(format(0, Scientific, Significant(2)), "0.0e0");
+        assert_eq!(format(1234, Fixed, Decimals(2)), "1234.00");
+        assert_eq!(format(123456, Fixed, Significant(3)), "123000");
+        assert_eq!(format(-125, Fixed, Significant(2)), "-130");
+        assert_eq!(format(7, Fixed, Significant(3)), "7.00");
+        assert_eq!(format(i32::MAX, Fixed, Significant(1)), "2000000000");
+        assert_eq!(format(12, Fixed, Significant(0)), "10");
+        assert_eq!(
+            format(1, Fixed, Decimals(usize::MAX)),
+            format!("1.{}", "0".repeat(PRECISION_LIMIT))
+        );
+        assert_eq!(
+            format(15, Scientific, Decimals(usize::MAX)),
+            format!("1.5{}e1", "0".repeat(PRECISION_LIMIT - 1))
+        );
+        assert_eq!(
+            format(15, Engineering, Significant(usize::MAX)),
+            format!("15.{}e0", "0".repeat(PRECISION_LIMIT - 2))
+        );
+        assert_eq!(
+            format_value(
+                &Value::Int(1234567),
+                &ValueFormat::default()
+                    .grouping(',')
+                    .precision(Significant(2))
+            ),
+            "1,200,000"
+        );
+    }
+
+    #[test]
+    fn test_fraction() {
+        let fraction = |numer, denom| Fraction::new(numer, denom).unwrap();
+        let mixed = ValueFormat::default().mixed_fractions();
+
+        assert_eq!(
+            format_fraction(&fraction(7, 3), &ValueFormat::default()),
+            "7/3"
+        );
+        assert_eq!(format_fraction(&fraction(7, 3), &mixed), "2 1/3");
+        assert_eq!(format_fraction(&fraction(-7, 3), &mixed), "-2 1/3");
+        assert_eq!(format_fraction(&fraction(-2, 3), &mixed), "-2/3");
+        assert_eq!(format_fraction(&fraction(6, 3), &mixed), "2");
+        assert_eq!(
+            format_fraction(&fraction(1000001, 1000), &mixed.grouping(',')),
+            "1,000 1/1,000"
+        );
+    }
+
+    #[test]
+    fn test_programmer() {
+        assert_eq!(
+            format_programmer(255, 16),
+            "dec 255\nhex 00ff\nbin 0000 0000 1111 1111\noct 000377"
+        );
+        assert_eq!(
+            format_programmer(-1, 8),
+            "dec -1\nhex ff\nbin 1111 1111\noct 377"
+        );
+        assert_eq!(
+            format_programmer(200, 8),
+            "dec -56 (wrapped from 200)\nhex c8\nbin 1100 1000\noct 310"
+        );
+        assert_eq!(
+            format_programmer(i32::MIN, 32),
+            "dec -2147483648\nhex 80000000\nbin 1000 0000 0000 0000 0000 0000 0000 0000\noct 20000000000"
+        );
+    }
+
+    #[test]
+    fn test_grouping() {
+        let commas = ValueFormat::default().grouping(',');
+        let format = |value: Value, format: &ValueFormat| format_value(&value, format);
+
+        assert_eq!(
+            format(eval("1234567 * 89").unwrap(), &commas),
+            "109,876,463"
+        );
+        assert_eq!(
+            format(Value::Int(109876463), &ValueFormat::default().grouping(' ')),
+            "109 876 463"
+        );
+        assert_eq!(format(Value::Int(-1000), &commas), "-1,000");
+        assert_eq!(format(Value::Int(999), &commas), "999");
+        assert_eq!(format(Value::Int(i32::MIN), &commas), "-2,147,483,648");
+        assert_eq!(
+            format(
+                Value::Interval(Interval::new(-12345, 100).unwrap()),
+                &commas
+            ),
+            "[-12 345, 100]"
+        );
+        assert_eq!(
+            format(
+                Value::Interval(Interval::new(-12345, 100).unwrap()),
+                &ValueFormat::default().grouping('.')
+            ),
+            "[-12.345, 100]"
+        );
+        assert_eq!(
+            format(Value::Int(i32::MIN), &commas.clone().radix(16)),
+            "-0x8000_0000"
+        );
+        assert_eq!(format(Value::Int(-8), &commas.clone().radix(8)), "-0o10");
+        assert_eq!(
+            format(Value::Int(4096), &commas.clone().radix(8)),
+            "0o10_000"
+        );
+        assert_eq!(
+            format(Value::Int(1234567), &ValueFormat::default()),
+            "1234567"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::fmt;
+
+use crate::{Ast, BinaryOp, Context, EvalOptions, ExprError, Result, UnaryOp, Value};
+
+/// An exact quotient of integers, always in lowest terms with a positive
+/// denominator, as [`Ast::eval_fraction`] gives: `7/3` rather than the `2`
+/// that integer division truncates it to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fraction {
+    numer: i64,
+    denom: i64,
+}
+
+impl Fraction {
+    /// `numer / denom` in lowest terms, failing if `denom` is zero.
+    pub fn new(numer: i64, denom: i64) -> Result<Self> {
+        if denom == 0 {
+            return Err(ExprError::DivisionByZero);
+        }
+        let gcd = gcd(numer.unsigned_abs(), denom.unsigned_abs());
+        let sign = if denom < 0 { -1 } else { 1 };
+        let reduce = |n: i64| i64::try_from(n.unsigned_abs() / gcd).map(|m| m * n.signum() * sign);
+        match (reduce(numer), reduce(denom)) {
+            (Ok(numer), Ok(denom)) => Ok(Fraction { numer, denom }),
+            _ => Err(too_large()),
+        }
+    }
+
+    /// The numerator, which carries the sign.
+    pub fn numer(&self) -> i64 {
+        self.numer
+    }
+
+    /// The denominator, always positive.
+    pub fn denom(&self) -> i64 {
+        self.denom
+    }
+
+    /// Whether the denominator is 1.
+    pub fn is_integer(&self) -> bool {
+        self.denom == 1
+    }
+
+    fn integer(n: i64) -> Self {
+        Fraction { numer: n, denom: 1 }
+    }
+
+    fn add(self, other: Self) -> Result<Self> {
+        let numer = checked(
+            self.numer
+                .checked_mul(other.denom)
+                .zip(other.numer.checked_mul(self.denom))
+                .and_then(|(a, b)| a.checked_add(b)),
+        )?;
+        Fraction::new(numer, checked(self.denom.checked_mul(other.denom))?)
+    }
+
+    fn neg(self) -> Result<Self> {
+        Ok(Fraction {
+            numer: checked(self.numer.checked_neg())?,
+            denom: self.denom,
+        })
+    }
+
+    fn mul(self, other: Self) -> Result<Self> {
+        Fraction::new(
+            checked(self.numer.checked_mul(other.numer))?,
+            checked(self.denom.checked_mul(other.denom))?,
+        )
+    }
+
+    fn recip(self) -> Result<Self> {
+        Fraction::new(self.denom, self.numer)
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self> {
+        if !exponent.is_integer() {
+            return Err(ExprError::Unsupported(
+                "fractional exponent in an exact fraction".into(),
+            ));
+        }
+        let power = u32::try_from(exponent.numer.unsigned_abs()).map_err(|_| too_large())?;
+        let base = Fraction::new(
+            checked(self.numer.checked_pow(power))?,
+            checked(self.denom.checked_pow(power))?,
+        )?;
+        if exponent.numer < 0 {
+            base.recip()
+        } else {
+            Ok(base)
+        }
+    }
+
+    // The integer this is, for operators that only take integers.
+    fn expect_int(self, what: &'static str) -> Result<i32> {
+        match i32::try_from(self.numer) {
+            Ok(n) if self.is_integer() => Ok(n),
+            _ => Err(ExprError::Unsupported(format!(
+                "{} of the fraction {}",
+                what, self
+            ))),
+        }
+    }
+}
+
+/// `7/3`, or `7` when the denominator is 1.
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.denom {
+            1 => write!(f, "{}", self.numer),
+            denom => write!(f, "{}/{}", self.numer, denom),
+        }
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+fn checked(n: Option<i64>) -> Result<i64> {
+    n.ok_or_else(too_large)
+}
+
+fn too_large() -> ExprError {
+    ExprError::Unsupported("fraction with a term beyond 64 bits".into())
+}
+
+impl Ast {
+    /// Evaluates the tree exactly, resolving identifiers against `context`:
+    /// `/` divides without truncating, so `7 / 3 + 1` is `10/3`, and `^`
+    /// takes negative exponents, so `2 ^ -2` is `1/4`.
+    ///
+    /// The other operators, `in` and function calls take and give integers
+    /// as in [`Ast::eval`], failing with [`ExprError::Unsupported`] on an
+    /// operand that is not one; so do intervals, and fractions with a term
+    /// beyond 64 bits.
+    pub fn eval_fraction(&self, context: &Context) -> Result<Fraction> {
+        match self {
+            Ast::Number(n) => Ok(Fraction::integer((*n).into())),
+            Ast::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => operand.eval_fraction(context)?.neg(),
+            Ast::Unary { op, operand } => {
+                let operand = operand.eval_fraction(context)?.expect_int(op.symbol())?;
+                Ok(Fraction::integer((operand == 0).into()))
+            }
+            Ast::Binary { op, lhs, rhs } => {
+                let lhs = lhs.eval_fraction(context)?;
+                let rhs = rhs.eval_fraction(context)?;
+                match op {
+                    BinaryOp::Add => lhs.add(rhs),
+                    BinaryOp::Sub => lhs.add(rhs.neg()?),
+                    BinaryOp::Mul => lhs.mul(rhs),
+                    BinaryOp::Div => lhs.mul(rhs.recip()?),
+                    BinaryOp::Pow => lhs.pow(rhs),
+                    op => {
+                        let lhs = Value::Int(lhs.expect_int(op.symbol())?);
+                        let rhs = Value::Int(rhs.expect_int(op.symbol())?);
+                        let value = op.token().apply(lhs, rhs, &EvalOptions::default())?;
+                        Ok(Fraction::integer(value.expect_int(op.symbol())?.into()))
+                    }
+                }
+            }
+            Ast::Paren(inner) => inner.eval_fraction(context),
+            Ast::Var(_) | Ast::Interval { .. } | Ast::In { .. } | Ast::Call { .. } => {
+                let value = self.eval_with(&EvalOptions::default(), context)?;
+                Ok(Fraction::integer(value.expect_int("fraction")?.into()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_fraction() {
+        let eval = |src: &str| {
+            let mut context = Context::new();
+            context.set("x", 6);
+            crate::parse(src)
+                .unwrap()
+                .eval_fraction(&context)
+                .map(|f| f.to_string())
+        };
+
+        assert_eq!(eval("7 / 3").unwrap(), "7/3");
+        assert_eq!(eval("7 / 3 + 1").unwrap(), "10/3");
+        assert_eq!(eval("1/3 + 1/6").unwrap(), "1/2");
+        assert_eq!(eval("x / 4 * 2").unwrap(), "3");
+        assert_eq!(eval("-6 / 4").unwrap(), "-3/2");
+        assert_eq!(eval("3 / -6").unwrap(), "-1/2");
+        assert_eq!(eval("(2/3) ^ 2").unwrap(), "4/9");
+        assert_eq!(eval("2 ^ -2").unwrap(), "1/4");
+        assert_eq!(eval("7 % 3 + 1/2").unwrap(), "3/2");
+        assert_eq!(eval("1 / (1/2 - 1/2)"), Err(ExprError::DivisionByZero));
+        assert!(matches!(eval("(1/2) % 2"), Err(ExprError::Unsupported(_))));
+        assert!(matches!(eval("2 ^ (1/2)"), Err(ExprError::Unsupported(_))));
+        assert!(matches!(eval("10 ^ 30"), Err(ExprError::Unsupported(_))));
+        assert_eq!(Fraction::new(4, -8).unwrap(), Fraction::new(-1, 2).unwrap());
+    }
+}
@@ -0,0 +1,478 @@
+use crate::{Ast, BinaryOp, ExprError, Result, UnaryOp, Value};
+
+/// The version of the schema [`Ast::to_json`] writes. Bumped whenever the
+/// schema changes in a way older readers would misread; [`Ast::from_json`]
+/// reads every version up to it.
+const SCHEMA_VERSION: u32 = 1;
+
+impl Ast {
+    /// The tree as JSON, for tools that build or consume expressions without
+    /// going through source text. The document is
+    /// `{"version":1,"expr":NODE}`, where each `NODE` is an object whose
+    /// `type` says which of the other fields it has:
+    ///
+    /// ```text
+    /// {"type":"number","value":2}
+    /// {"type":"var","name":"x"}
+    /// {"type":"interval","lo":NODE,"hi":NODE}
+    /// {"type":"unary","op":"neg","operand":NODE}
+    /// {"type":"binary","op":"+","lhs":NODE,"rhs":NODE}
+    /// {"type":"in","needle":NODE,"list":[NODE, ...]}
+    /// {"type":"call","name":"f","args":[NODE, ...]}
+    /// {"type":"paren","inner":NODE}
+    /// ```
+    ///
+    /// Unary operators are named as in [`UnaryOp::symbol`] and binary ones
+    /// as in [`BinaryOp::symbol`]. [`Ast::from_json`] gives back an equal
+    /// tree.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"version\":{},\"expr\":{}}}",
+            SCHEMA_VERSION,
+            node_json(self)
+        )
+    }
+
+    /// Reads a tree written by [`Ast::to_json`], or by another tool that
+    /// follows its schema. Fields the schema does not mention are ignored.
+    /// Fails with [`ExprError::InvalidJson`] if `json` is not JSON, is from
+    /// a newer schema version, or does not describe an expression.
+    pub fn from_json(json: &str) -> Result<Ast> {
+        let mut parser = JsonParser { src: json, pos: 0 };
+        let document = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < json.len() {
+            return Err(parser.error("end of input"));
+        }
+
+        let version = document.field("version")?.int()?;
+        if !(1..=SCHEMA_VERSION as i64).contains(&version) {
+            return Err(invalid(format!(
+                "schema version {} is not 1 to {}",
+                version, SCHEMA_VERSION
+            )));
+        }
+        document.field("expr")?.to_ast()
+    }
+}
+
+impl Value {
+    /// The value as JSON for programs consuming results, with a `type` of
+    /// `int` or `interval`: `{"value":7,"type":"int"}`, or
+    /// `{"value":[1,3],"type":"interval"}` for `[1, 3]`.
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Int(n) => format!("{{\"value\":{},\"type\":\"int\"}}", n),
+            Value::Interval(i) => format!(
+                "{{\"value\":[{},{}],\"type\":\"interval\"}}",
+                i.lo(),
+                i.hi()
+            ),
+        }
+    }
+}
+
+fn node_json(ast: &Ast) -> String {
+    let list = |items: &[Ast]| {
+        let items = items.iter().map(node_json).collect::<Vec<_>>();
+        format!("[{}]", items.join(","))
+    };
+    match ast {
+        Ast::Number(n) => format!("{{\"type\":\"number\",\"value\":{}}}", n),
+        Ast::Var(name) => format!("{{\"type\":\"var\",\"name\":{}}}", json_string(name)),
+        Ast::Interval { lo, hi } => format!(
+            "{{\"type\":\"interval\",\"lo\":{},\"hi\":{}}}",
+            node_json(lo),
+            node_json(hi)
+        ),
+        Ast::Unary { op, operand } => format!(
+            "{{\"type\":\"unary\",\"op\":{},\"operand\":{}}}",
+            json_string(op.symbol()),
+            node_json(operand)
+        ),
+        Ast::Binary { op, lhs, rhs } => format!(
+            "{{\"type\":\"binary\",\"op\":{},\"lhs\":{},\"rhs\":{}}}",
+            json_string(op.symbol()),
+            node_json(lhs),
+            node_json(rhs)
+        ),
+        Ast::In {
+            needle,
+            list: items,
+        } => format!(
+            "{{\"type\":\"in\",\"needle\":{},\"list\":{}}}",
+            node_json(needle),
+            list(items)
+        ),
+        Ast::Call { name, args } => format!(
+            "{{\"type\":\"call\",\"name\":{},\"args\":{}}}",
+            json_string(name),
+            list(args)
+        ),
+        Ast::Paren(inner) => format!("{{\"type\":\"paren\",\"inner\":{}}}", node_json(inner)),
+    }
+}
+
+// `s` as a JSON string literal.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn invalid(reason: String) -> ExprError {
+    ExprError::InvalidJson(reason)
+}
+
+/// A parsed JSON value. Numbers keep their text, since the schema only has
+/// integers and `f64` would round large ones; booleans are never read, so
+/// which one was given is not kept.
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool,
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn describe(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool => "a boolean",
+            Json::Number(_) => "a number",
+            Json::String(_) => "a string",
+            Json::Array(_) => "an array",
+            Json::Object(_) => "an object",
+        }
+    }
+
+    fn field(&self, name: &str) -> Result<&Json> {
+        let Json::Object(fields) = self else {
+            return Err(invalid(format!(
+                "expected an object, found {}",
+                self.describe()
+            )));
+        };
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| invalid(format!("missing field '{}'", name)))
+    }
+
+    fn int(&self) -> Result<i64> {
+        match self {
+            Json::Number(text) => text
+                .parse()
+                .map_err(|_| invalid(format!("{} is not a 32-bit integer", text))),
+            other => Err(invalid(format!(
+                "expected a number, found {}",
+                other.describe()
+            ))),
+        }
+    }
+
+    fn str(&self) -> Result<&str> {
+        match self {
+            Json::String(s) => Ok(s),
+            other => Err(invalid(format!(
+                "expected a string, found {}",
+                other.describe()
+            ))),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<Ast>> {
+        match self {
+            Json::Array(items) => items.iter().map(Json::to_ast).collect(),
+            other => Err(invalid(format!(
+                "expected an array, found {}",
+                other.describe()
+            ))),
+        }
+    }
+
+    fn to_ast(&self) -> Result<Ast> {
+        let node = |name| self.field(name)?.to_ast().map(Box::new);
+        let ast = match self.field("type")?.str()? {
+            "number" => {
+                let value = self.field("value")?.int()?;
+                Ast::Number(
+                    i32::try_from(value)
+                        .map_err(|_| invalid(format!("{} is not a 32-bit integer", value)))?,
+                )
+            }
+            "var" => Ast::Var(self.field("name")?.str()?.to_string()),
+            "interval" => Ast::Interval {
+                lo: node("lo")?,
+                hi: node("hi")?,
+            },
+            "unary" => {
+                let symbol = self.field("op")?.str()?;
+                Ast::Unary {
+                    op: UnaryOp::from_symbol(symbol)
+                        .ok_or_else(|| invalid(format!("unknown unary operator '{}'", symbol)))?,
+                    operand: node("operand")?,
+                }
+            }
+            "binary" => {
+                let symbol = self.field("op")?.str()?;
+                Ast::Binary {
+                    op: BinaryOp::from_symbol(symbol)
+                        .ok_or_else(|| invalid(format!("unknown binary operator '{}'", symbol)))?,
+                    lhs: node("lhs")?,
+                    rhs: node("rhs")?,
+                }
+            }
+            "in" => Ast::In {
+                needle: node("needle")?,
+                list: self.field("list")?.list()?,
+            },
+            "call" => Ast::Call {
+                name: self.field("name")?.str()?.to_string(),
+                args: self.field("args")?.list()?,
+            },
+            "paren" => Ast::Paren(node("inner")?),
+            other => return Err(invalid(format!("unknown node type '{}'", other))),
+        };
+        Ok(ast)
+    }
+}
+
+struct JsonParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, expected: &str) -> ExprError {
+        invalid(format!("expected {} at byte {}", expected, self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.src[self.pos..].starts_with(text);
+        if found {
+            self.pos += text.len();
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Json> {
+        self.skip_whitespace();
+        let rest = &self.src[self.pos..];
+        match rest.chars().next() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Json::String),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ if self.eat("null") => Ok(Json::Null),
+            _ if self.eat("true") => Ok(Json::Bool),
+            _ if self.eat("false") => Ok(Json::Bool),
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json> {
+        self.pos += 1;
+        let mut fields = Vec::new();
+        if self.eat("}") {
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            if !self.src[self.pos..].starts_with('"') {
+                return Err(self.error("a field name"));
+            }
+            let key = self.string()?;
+            if !self.eat(":") {
+                return Err(self.error("':'"));
+            }
+            fields.push((key, self.value()?));
+            if self.eat("}") {
+                return Ok(Json::Object(fields));
+            }
+            if !self.eat(",") {
+                return Err(self.error("',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        if self.eat("]") {
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.eat("]") {
+                return Ok(Json::Array(items));
+            }
+            if !self.eat(",") {
+                return Err(self.error("',' or ']'"));
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json> {
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        Ok(Json::Number(rest[..len].to_string()))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.src[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut code = hex4(&mut chars);
+                            // A high surrogate must be followed by a low one.
+                            if let Some(high @ 0xd800..=0xdbff) = code {
+                                let low = match (chars.next(), chars.next()) {
+                                    (Some((_, '\\')), Some((_, 'u'))) => hex4(&mut chars),
+                                    _ => None,
+                                };
+                                code = low
+                                    .filter(|low| (0xdc00..=0xdfff).contains(low))
+                                    .map(|low| 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00));
+                            }
+                            match code.and_then(char::from_u32) {
+                                Some(c) => c,
+                                None => {
+                                    self.pos += i;
+                                    return Err(self.error("a valid \\u escape"));
+                                }
+                            }
+                        }
+                        _ => {
+                            self.pos += i;
+                            return Err(self.error("an escape sequence"));
+                        }
+                    };
+                    out.push(escaped);
+                }
+                c => out.push(c),
+            }
+        }
+        self.pos = self.src.len();
+        Err(self.error("'\"'"))
+    }
+}
+
+// The next four hex digits of `chars` as a number.
+fn hex4(chars: &mut std::str::CharIndices) -> Option<u32> {
+    (0..4).try_fold(0, |code, _| {
+        let digit = chars.next()?.1.to_digit(16)?;
+        Some(code * 16 + digit)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_value_to_json() {
+        assert_eq!(Value::Int(-7).to_json(), r#"{"value":-7,"type":"int"}"#);
+        assert_eq!(
+            Value::Interval(crate::Interval::new(1, 3).unwrap()).to_json(),
+            r#"{"value":[1,3],"type":"interval"}"#
+        );
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        for src in [
+            "1 + 2 * 3",
+            "-(x ^ 2) in ([0, 4], f(y, 3), not z)",
+            "(a // b) % -2147483647 or 7",
+            "g()",
+        ] {
+            let ast = parse(src).unwrap();
+            assert_eq!(Ast::from_json(&ast.to_json()), Ok(ast), "{}", src);
+        }
+
+        let ast = Ast::Var("quote\" \\ \u{1} é 😀".into());
+        assert_eq!(Ast::from_json(&ast.to_json()), Ok(ast));
+
+        assert_eq!(
+            parse("-x + 1").unwrap().to_json(),
+            r#"{"version":1,"expr":{"type":"binary","op":"+","lhs":{"type":"unary","op":"neg","operand":{"type":"var","name":"x"}},"rhs":{"type":"number","value":1}}}"#
+        );
+        assert_eq!(
+            Ast::from_json(
+                r#" { "expr" : { "type":"var", "name":"😀\n", "note":[1.5, null] },
+                     "version" : 1 } "#
+            ),
+            Ok(Ast::Var("😀\n".into()))
+        );
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let invalid = |json: &str| match Ast::from_json(json) {
+            Err(ExprError::InvalidJson(reason)) => reason,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(invalid("{\"version\":1"), "expected ',' or '}' at byte 12");
+        assert_eq!(invalid("[] []"), "expected end of input at byte 3");
+        assert_eq!(
+            invalid(r#"{"version":2,"expr":null}"#),
+            "schema version 2 is not 1 to 1"
+        );
+        assert_eq!(
+            invalid(r#"{"version":1,"expr":{"type":"number","value":2147483648}}"#),
+            "2147483648 is not a 32-bit integer"
+        );
+        assert_eq!(
+            invalid(r#"{"version":1,"expr":{"type":"binary","op":"**","lhs":1}}"#),
+            "unknown binary operator '**'"
+        );
+        assert_eq!(
+            invalid(r#"{"version":1,"expr":{"type":"paren"}}"#),
+            "missing field 'inner'"
+        );
+        assert_eq!(
+            invalid(r#"{"version":1,"expr":{"type":"var","name":"\ud800"}}"#),
+            "expected a valid \\u escape at byte 42"
+        );
+        assert_eq!(Ast::from_json("").unwrap_err().code(), "E018");
+    }
+}
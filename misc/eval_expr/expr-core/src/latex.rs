@@ -0,0 +1,112 @@
+use crate::{Ast, BinaryOp, UnaryOp};
+
+impl Ast {
+    /// Writes the expression as LaTeX math, e.g. `\frac{a}{b}` for `a / b`
+    /// and `x^{2}` for `x ^ 2`, for pasting into documents and notebooks.
+    /// Grouping is `\left( ... \right)` and appears only where precedence
+    /// needs it; a fraction or exponent groups its parts by itself.
+    /// Names longer than one letter are set upright with `\mathrm`, and
+    /// calls with `\operatorname`.
+    pub fn to_latex(&self) -> String {
+        latex(&self.strip_parens().parenthesize())
+    }
+}
+
+fn latex(ast: &Ast) -> String {
+    match ast {
+        Ast::Number(n) => n.to_string(),
+        Ast::Var(name) => name_latex(name, "mathrm"),
+        Ast::Interval { lo, hi } => format!("[{}, {}]", latex(lo), latex(hi)),
+        Ast::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => format!("-{}", latex(operand)),
+        Ast::Unary {
+            op: UnaryOp::Not,
+            operand,
+        } => format!("\\lnot {}", latex(operand)),
+        Ast::Binary { op, lhs, rhs } => match op {
+            BinaryOp::Div => format!("\\frac{{{}}}{{{}}}", bare(lhs), bare(rhs)),
+            BinaryOp::FloorDiv => format!(
+                "\\left\\lfloor \\frac{{{}}}{{{}}} \\right\\rfloor",
+                bare(lhs),
+                bare(rhs)
+            ),
+            BinaryOp::Pow => format!("{}^{{{}}}", latex(lhs), bare(rhs)),
+            _ => {
+                let symbol = match op {
+                    BinaryOp::Mul => "\\cdot",
+                    BinaryOp::Mod => "\\bmod",
+                    BinaryOp::And => "\\land",
+                    BinaryOp::Or => "\\lor",
+                    op => op.symbol(),
+                };
+                format!("{} {} {}", latex(lhs), symbol, latex(rhs))
+            }
+        },
+        Ast::In { needle, list } => {
+            format!(
+                "{} \\in \\left\\{{{}\\right\\}}",
+                latex(needle),
+                list_latex(list)
+            )
+        }
+        Ast::Call { name, args } => format!(
+            "{}\\left({}\\right)",
+            name_latex(name, "operatorname"),
+            list_latex(args)
+        ),
+        Ast::Paren(inner) => format!("\\left({}\\right)", latex(inner)),
+    }
+}
+
+// `ast` without the grouping `Ast::parenthesize` gave it, for places such as
+// a numerator that are grouped already.
+fn bare(ast: &Ast) -> String {
+    match ast {
+        Ast::Paren(inner) => latex(inner),
+        ast => latex(ast),
+    }
+}
+
+fn list_latex(items: &[Ast]) -> String {
+    items.iter().map(bare).collect::<Vec<_>>().join(", ")
+}
+
+// A one-letter name as it is, else the name set with `command`, e.g.
+// `\mathrm{rate}`.
+fn name_latex(name: &str, command: &str) -> String {
+    let escaped = name.replace('_', "\\_");
+    if name.chars().count() == 1 && name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        escaped
+    } else {
+        format!("\\{}{{{}}}", command, escaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    #[test]
+    fn test_to_latex() {
+        let latex = |src| parse(src).unwrap().to_latex();
+        assert_eq!(latex("(a + b) / 2"), "\\frac{a + b}{2}");
+        assert_eq!(latex("x ^ (n - 1)"), "x^{n - 1}");
+        assert_eq!(latex("(x + 1) ^ 2"), "\\left(x + 1\\right)^{2}");
+        assert_eq!(latex("((2 * 3))"), "2 \\cdot 3");
+        assert_eq!(latex("2 * (3 - y)"), "2 \\cdot \\left(3 - y\\right)");
+        assert_eq!(
+            latex("a // b % 3"),
+            "\\left\\lfloor \\frac{a}{b} \\right\\rfloor \\bmod 3"
+        );
+        assert_eq!(
+            latex("not max_rate(x, (1)) in (1, [0, 2])"),
+            "\\lnot \\operatorname{max\\_rate}\\left(x, 1\\right) \\in \\left\\{1, [0, 2]\\right\\}"
+        );
+        assert_eq!(
+            latex("-rate and b or c"),
+            "-\\mathrm{rate} \\land b \\lor c"
+        );
+    }
+}
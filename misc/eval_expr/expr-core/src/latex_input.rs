@@ -0,0 +1,284 @@
+use crate::{parse, Ast, ExprError, Result, Span};
+
+impl Ast {
+    /// Parses a subset of LaTeX math, such as `\frac{1}{2} + 3^{2}`, into
+    /// the tree the same formula written as infix gives, without
+    /// parentheses like [`Ast::strip_parens`]. Besides plain infix it reads
+    /// `\frac`, `\lfloor \frac{a}{b} \rfloor`, exponents in braces,
+    /// `\cdot`, `\times`, `\div`, `\bmod`, `\land`, `\lor`, `\lnot`, `\in`,
+    /// `\left` and `\right`, `\{` and `\}` around `\in` lists, names in
+    /// `\mathrm`, `\operatorname` or `\text`, and the spacing commands.
+    /// Everything [`Ast::to_latex`] writes reads back.
+    ///
+    /// Errors have spans into `src`.
+    pub fn from_latex(src: &str) -> Result<Ast> {
+        let mut reader = Reader {
+            src,
+            pos: 0,
+            infix: String::new(),
+            spans: Vec::new(),
+        };
+        reader.until(None)?;
+        parse(&reader.infix)
+            .map(|ast| ast.strip_parens())
+            .map_err(|err| reader.remap(err))
+    }
+}
+
+// Rewrites LaTeX as infix, remembering which part of the LaTeX each byte
+// of the infix came from.
+struct Reader<'a> {
+    src: &'a str,
+    pos: usize,
+    infix: String,
+    spans: Vec<Span>,
+}
+
+impl Reader<'_> {
+    fn emit(&mut self, text: &str, from: Span) {
+        self.infix.push_str(text);
+        self.spans.extend(std::iter::repeat_n(from, text.len()));
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span {
+            start,
+            end: self.pos,
+        }
+    }
+
+    // The character at `pos`, or the empty span at the end of the input.
+    fn next_span(&self) -> Span {
+        Span {
+            start: self.pos,
+            end: self.pos + self.peek().map_or(0, char::len_utf8),
+        }
+    }
+
+    fn error(&self, span: Span, expected: &'static str) -> ExprError {
+        ExprError::unexpected(self.src, span, expected)
+    }
+
+    // The command at `pos`, which is just past a backslash: a run of
+    // letters, or a single other character such as the `{` of `\{`.
+    fn command(&mut self) -> &str {
+        let rest = &self.src[self.pos..];
+        let len = match rest.find(|c: char| !c.is_ascii_alphabetic()) {
+            Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+            Some(len) => len,
+            None => rest.len(),
+        };
+        self.pos += len;
+        &rest[..len]
+    }
+
+    // Rewrites input up to the `close` character, or the end of the input
+    // if `close` is `None`, consuming the closing character.
+    fn until(&mut self, close: Option<char>) -> Result<()> {
+        loop {
+            let start = self.pos;
+            let Some(c) = self.peek() else {
+                return match close {
+                    Some(_) => Err(ExprError::unclosed(self.span_from(start))),
+                    None => Ok(()),
+                };
+            };
+            self.pos += c.len_utf8();
+            match c {
+                _ if Some(c) == close => return Ok(()),
+                '}' => return Err(self.error(self.span_from(start), "expression")),
+                '{' => self.group(self.span_from(start))?,
+                '\\' => self.backslash(start)?,
+                _ => self.emit(c.encode_utf8(&mut [0; 4]), self.span_from(start)),
+            }
+        }
+    }
+
+    // Rewrites a `{ ... }` group whose `{` has been read as a parenthesized
+    // one.
+    fn group(&mut self, from: Span) -> Result<()> {
+        self.emit("(", from);
+        self.until(Some('}'))?;
+        self.emit(")", self.span_from(self.pos - 1));
+        Ok(())
+    }
+
+    // Reads a `{`, after any spaces, and rewrites the group it opens.
+    fn braced(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if self.peek() != Some('{') {
+            return Err(self.error(self.next_span(), "'{'"));
+        }
+        self.pos += 1;
+        self.group(self.span_from(start))
+    }
+
+    // Rewrites the text of a `{ ... }` name group as it is.
+    fn name(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let rest = &self.src[self.pos..];
+        let Some(name) = rest.strip_prefix('{').and_then(|rest| rest.split_once('}')) else {
+            return Err(self.error(self.next_span(), "'{' and '}'"));
+        };
+        self.pos += name.0.len() + 2;
+        let text = name.0.replace("\\_", "_");
+        self.emit(&text, self.span_from(start));
+        Ok(())
+    }
+
+    fn backslash(&mut self, start: usize) -> Result<()> {
+        let command = self.command().to_string();
+        let from = self.span_from(start);
+        let text = match command.as_str() {
+            "frac" => return self.frac("/"),
+            "lfloor" => {
+                // Only a floored fraction has an infix spelling.
+                self.skip_whitespace();
+                if !self.src[self.pos..].starts_with("\\frac") {
+                    return Err(self.error(from, "'\\frac' after '\\lfloor'"));
+                }
+                self.pos += "\\frac".len();
+                self.frac("//")?;
+                self.skip_whitespace();
+                if self.src[self.pos..].starts_with("\\right") {
+                    self.pos += "\\right".len();
+                    self.skip_whitespace();
+                }
+                if !self.src[self.pos..].starts_with("\\rfloor") {
+                    return Err(self.error(self.next_span(), "'\\rfloor'"));
+                }
+                self.pos += "\\rfloor".len();
+                return Ok(());
+            }
+            "mathrm" | "operatorname" | "text" => return self.name(),
+            "left" | "right" => "",
+            "cdot" | "times" => "*",
+            "div" => "/",
+            "bmod" => " % ",
+            "land" | "wedge" => " and ",
+            "lor" | "vee" => " or ",
+            "lnot" | "neg" => " not ",
+            "in" => " in ",
+            "{" => "(",
+            "}" => ")",
+            "_" => "_",
+            "," | ";" | ":" | "!" | " " | "quad" | "qquad" => " ",
+            _ => return Err(self.error(from, "supported LaTeX command")),
+        };
+        self.emit(text, from);
+        Ok(())
+    }
+
+    // Rewrites the `{a}{b}` of a `\frac` as `((a) op (b))`.
+    fn frac(&mut self, op: &str) -> Result<()> {
+        let from = Span {
+            start: self.pos,
+            end: self.pos,
+        };
+        self.emit("(", from);
+        self.braced()?;
+        self.emit(&format!(" {} ", op), from);
+        self.braced()?;
+        self.emit(")", from);
+        Ok(())
+    }
+
+    // `err` with its span, and the text it quotes, moved from the infix to
+    // the LaTeX it was rewritten from.
+    fn remap(&self, err: ExprError) -> ExprError {
+        let Some(span) = err.span() else {
+            return err;
+        };
+        let end_of_input = Span {
+            start: self.src.len(),
+            end: self.src.len(),
+        };
+        let start = self.spans.get(span.start).copied().unwrap_or(end_of_input);
+        let span = if span.end > span.start {
+            Span {
+                start: start.start,
+                end: self.spans[span.end - 1].end,
+            }
+        } else {
+            Span {
+                start: start.start,
+                end: start.start,
+            }
+        };
+        match err {
+            ExprError::UnexpectedToken { expected, .. } => {
+                ExprError::unexpected(self.src, span, expected)
+            }
+            ExprError::UnclosedParen { .. } => ExprError::unclosed(span),
+            err => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    #[test]
+    fn test_from_latex() {
+        let ast = Ast::from_latex("\\frac{1}{2} + 3^{2}").unwrap();
+        assert_eq!(ast, parse("1 / 2 + 3 ^ 2").unwrap());
+        assert_eq!(
+            Ast::from_latex("2 \\cdot \\left( \\frac{10}{x_1} - 1 \\right)"),
+            Ok(parse("2 * (10 / x_1 - 1)").unwrap().strip_parens())
+        );
+        assert_eq!(
+            Ast::from_latex("2^{3^{2}} \\times \\lfloor \\frac{7}{2} \\rfloor")
+                .unwrap()
+                .eval(),
+            Ok(Value::Int(1536))
+        );
+        for src in [
+            "(a + b) / 2 ^ (n - 1)",
+            "-(x ^ 2) in ([0, 4], f(y, 3), not z)",
+            "a // b % 3 or max_rate and 1",
+        ] {
+            let ast = parse(src).unwrap();
+            assert_eq!(
+                Ast::from_latex(&ast.to_latex()),
+                Ok(ast.strip_parens()),
+                "{}",
+                src
+            );
+        }
+
+        let error = |src| Ast::from_latex(src).unwrap_err();
+        let err = error("1 + \\sqrt{2}");
+        assert_eq!(
+            err.to_string(),
+            "Parse error: Expected supported LaTeX command, found '\\sqrt'"
+        );
+        assert_eq!(
+            error("\\frac{1}{2 +}").render("\\frac{1}{2 +}"),
+            [
+                "Parse error: Expected number or parenthesis, found '}'",
+                " --> 1:13",
+                "  |",
+                "1 | \\frac{1}{2 +}",
+                "  |             ^",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            error("\\frac{1}{2"),
+            ExprError::unclosed(Span { start: 10, end: 10 })
+        );
+    }
+}
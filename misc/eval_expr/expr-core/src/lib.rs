@@ -0,0 +1,399 @@
+//! Parses and evaluates integer arithmetic expressions such as `2 * (3 + 4)`.
+//!
+//! [`eval`] covers the common case; [`Expr`] adds [`EvalOptions`], variables
+//! from a [`Context`] and operator counts. [`parse`] returns the syntax tree
+//! instead, which can be inspected, transformed and evaluated any number of
+//! times with [`Ast::eval_with`].
+//!
+//! The library never writes to stdout or stderr, so it can run inside
+//! servers and test harnesses that own those streams. Anything worth
+//! reporting is returned instead: errors as [`ExprError`], evaluation
+//! details from [`Expr::trace`] and [`Expr::usage`], and rejected input
+//! through the [`EvalOptions::on_reject`] hook.
+
+mod ast;
+mod cache;
+mod compile;
+mod context;
+#[cfg(feature = "diagnostics")]
+mod diagnostic;
+mod error;
+mod expr;
+mod fixed;
+mod format;
+mod fraction;
+mod json;
+mod latex;
+mod latex_input;
+mod mathml;
+mod meter;
+mod migrate;
+mod monte_carlo;
+mod options;
+mod prefix;
+mod pretty;
+mod preview;
+mod rng;
+mod rpn;
+mod script;
+mod sensitivity;
+mod sexpr;
+mod solve;
+mod token;
+mod usage;
+mod value;
+mod view;
+pub mod visit;
+mod warning;
+
+pub use ast::{Ast, BinaryOp, UnaryOp, EQUIVALENCE_TRIALS, INLINE_LIMIT, TREE_DEPTH_LIMIT};
+pub use cache::ExprCache;
+pub use compile::CompiledExpr;
+pub use context::{set_default_context, with_default_context, Context, Function, BUILTINS};
+pub use error::{ErrorKind, ExprError, Result};
+pub use expr::Expr;
+pub use fixed::FixedEvaluator;
+pub use format::{
+    format_fraction, format_programmer, format_value, Notation, Precision, ValueFormat,
+    PRECISION_LIMIT,
+};
+pub use fraction::Fraction;
+pub use meter::CancelToken;
+pub use migrate::{migrate, Note};
+pub use monte_carlo::{Distribution, Summary};
+pub use options::{DivisionMode, EvalOptions, LanguageLevel, OverflowPolicy};
+pub use preview::{preview, Preview};
+pub use script::{Script, ScriptError};
+pub use sensitivity::Sensitivity;
+pub use token::{Assoc, NumberFormat, OperatorTable, PrecedenceTable, Span};
+pub use usage::UsageStats;
+pub use value::{Interval, Value};
+pub use view::AstView;
+pub use visit::{Folder, Visitor};
+pub use warning::{Warning, WarningKind};
+
+use token::{Spanned, Token, Tokenizer};
+
+/// Evaluates `src` with the default options, e.g. `eval("1 + 2") == Ok(3)`.
+/// Identifiers resolve against the default context, see
+/// [`set_default_context`] and [`with_default_context`].
+pub fn eval(src: &str) -> Result<Value> {
+    match context::default_context() {
+        Some(context) => Expr::new(src).with_context(&context).eval(),
+        None => Expr::new(src).eval(),
+    }
+}
+
+/// Evaluates the longest prefix of `src` that is a complete expression,
+/// returning its value and its length in bytes, so a calculator can show a
+/// live result while the user is still typing: `eval_partial("2 * (3 + 4) -")`
+/// gives `(Ok(14), 11)`. If `src` is complete the length is `src.len()`; if
+/// no prefix is, the result is the error for the whole of `src` and the
+/// length is 0.
+pub fn eval_partial(src: &str) -> (Result<Value>, usize) {
+    let err = match parse(src) {
+        // `eval` builds no syntax tree, so it takes input too deep for one.
+        Ok(_) | Err(ExprError::DepthLimitExceeded { .. }) => return (eval(src), src.len()),
+        Err(err) => err,
+    };
+    // A prefix can only end before the token the parser stopped at.
+    let stop = err.span().map_or(src.len(), |span| span.start);
+    let ends = Tokenizer::new(src)
+        .map(|token| token.span.end)
+        .take_while(|&end| end <= stop)
+        .collect::<Vec<_>>();
+    ends.into_iter()
+        .rev()
+        .find(|&end| parse(&src[..end]).is_ok())
+        .map_or((Err(err), 0), |end| (eval(&src[..end]), end))
+}
+
+/// Whether `src` could be an expression as it stands, as opposed to the
+/// start of one that more input would finish, such as `2 * (3 +` or `1 +`.
+/// Input with an error before its end, like `1 + * 2`, is complete: no more
+/// input would fix it. So is blank input.
+pub fn is_complete(src: &str) -> bool {
+    if src.trim().is_empty() {
+        return true;
+    }
+    match parse(src) {
+        Ok(_) => true,
+        Err(err) => err
+            .span()
+            .is_none_or(|span| span.start < src.trim_end().len()),
+    }
+}
+
+/// Parses `src` into a syntax tree with the default operator table.
+pub fn parse(src: &str) -> Result<Ast> {
+    ast::Parser::new(src, OperatorTable::default()).parse()
+}
+
+/// Parses `src` into a syntax tree with the operators, precedences, depth
+/// limit and implicit multiplication of `options`. Syntax trees have no
+/// node for the custom operators of an [`OperatorTable`], so input using one
+/// fails with [`ExprError::Unsupported`]; only [`Expr::eval`] evaluates it.
+pub fn parse_with(src: &str, options: &EvalOptions) -> Result<Ast> {
+    let tokens = Tokenizer::with_operators(src, options.operators.clone())
+        .with_numbers(options.numbers)
+        .peekable();
+    ast::Parser::from_tokens(src, tokens)
+        .with_options(options)
+        .parse()
+}
+
+/// Parses `src` like [`parse`] but carries on past errors, returning a
+/// best-effort tree with `0` for missing operands along with every error
+/// found, e.g. three for `1 + * 2 ) + (3 +`. Valid input gives no errors.
+pub fn parse_recovering(src: &str) -> (Ast, Vec<ExprError>) {
+    ast::Parser::new(src, OperatorTable::default()).recover()
+}
+
+/// Splits `src` into the tokens the parser reads, each with its span and
+/// kind: `number`, `identifier`, `unknown` for a character that starts no
+/// token, or the operator's canonical symbol, such as `^` for `**`.
+pub fn tokenize(src: &str) -> Vec<(Span, &'static str)> {
+    Tokenizer::new(src)
+        .map(|token| (token.span, token.value.symbol()))
+        .collect()
+}
+
+/// Splits an assignment statement `name = expr` into the name and the
+/// expression source, or returns `None` if `src` is a plain expression.
+pub fn split_assignment(src: &str) -> Option<(&str, &str)> {
+    let mut tokens = Tokenizer::new(src);
+    let Some(Spanned {
+        value: Token::Ident(_),
+        span,
+    }) = tokens.next()
+    else {
+        return None;
+    };
+    let name = src[..span.end].trim();
+    match tokens.next() {
+        Some(Spanned {
+            value: Token::Assign,
+            span,
+        }) => Some((name, &src[span.end..])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_and_parse() {
+        assert_eq!(eval("2 * (3 + 4)"), Ok(Value::Int(14)));
+        assert_eq!(parse("2 * (3 + 4)").unwrap().to_rpn(), "2 3 4 + *");
+        assert_eq!(
+            parse("1 +").unwrap_err(),
+            ExprError::UnexpectedToken {
+                found: None,
+                expected: "number or parenthesis",
+                span: Some(Span { start: 3, end: 3 })
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        let too_deep = || ExprError::DepthLimitExceeded {
+            limit: TREE_DEPTH_LIMIT,
+        };
+        let parens = format!("{}1{}", "(".repeat(50_000), ")".repeat(50_000));
+        assert_eq!(parse(&parens), Err(too_deep()));
+        assert_eq!(parse(&vec!["1"; 50_000].join(" + ")), Err(too_deep()));
+        assert_eq!(parse(&format!("{}1", "- ".repeat(50_000))), Err(too_deep()));
+        let calls = format!("{}1{}", "f(".repeat(50_000), ")".repeat(50_000));
+        assert_eq!(parse(&calls), Err(too_deep()));
+        assert_eq!(parse_recovering(&parens).1, [too_deep()]);
+        assert!(is_complete(&parens));
+        assert_eq!(eval_partial(&parens), (Ok(Value::Int(1)), parens.len()));
+
+        // A chain of n operands is n levels tall.
+        let tallest = vec!["1"; TREE_DEPTH_LIMIT].join(" + ");
+        let ast = parse(&tallest).unwrap();
+        assert_eq!(ast.eval(), Ok(Value::Int(256)));
+        assert_eq!(parse(&ast.to_string()), Ok(ast));
+        assert_eq!(parse(&format!("{} + 1", tallest)), Err(too_deep()));
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let (ast, errors) = parse_recovering("1 + * 2 ) + (3 +");
+        let errors = errors
+            .iter()
+            .map(|err| (err.to_string(), err.span().unwrap()))
+            .collect::<Vec<_>>();
+        let error =
+            |message: &str, start, end| (format!("Parse error: {}", message), Span { start, end });
+        assert_eq!(
+            errors,
+            [
+                error("Expected number or parenthesis, found '*'", 4, 5),
+                error("Expected operator or end of input, found ')'", 8, 9),
+                error("Expected number or parenthesis, found end of input", 16, 16),
+            ]
+        );
+        assert_eq!(ast.to_string(), "1 + 2 + (3 + 0)");
+
+        let (ast, errors) = parse_recovering("f(1,, [2 3) in 4");
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert_eq!(ast.to_string(), "f(1, 0, [2, 3]) in ()");
+
+        assert_eq!(
+            parse_recovering("2 * (3 + 4)"),
+            (parse("2 * (3 + 4)").unwrap(), vec![])
+        );
+    }
+
+    #[test]
+    fn test_eval_partial() {
+        assert_eq!(eval_partial("2 * (3 + 4) -"), (Ok(Value::Int(14)), 11));
+        assert_eq!(eval_partial("1 + 2 * 3 ^"), (Ok(Value::Int(7)), 9));
+        assert_eq!(eval_partial("1 + 2 ) * 3"), (Ok(Value::Int(3)), 5));
+        assert_eq!(eval_partial(" 5 "), (Ok(Value::Int(5)), 3));
+        assert_eq!(
+            eval_partial("x + 1 +"),
+            (Err(ExprError::UnknownVariable("x".into())), 5)
+        );
+        assert_eq!(
+            eval_partial("* 2"),
+            (parse("* 2").map(|_| Value::Int(0)), 0)
+        );
+    }
+
+    #[test]
+    fn test_is_complete() {
+        for src in ["1 + 2", "", "  ", "1 + * 2", "1 )", "f(x, 2)"] {
+            assert!(is_complete(src), "{:?}", src);
+        }
+        for src in ["1 +", "2 * (3 + 4", "f(1,", "x in (1, 2", "(1 + 2) ^ "] {
+            assert!(!is_complete(src), "{:?}", src);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let err = parse("(1 + 2 * 3").unwrap_err();
+        assert_eq!(err.span(), Some(Span { start: 10, end: 10 }));
+        assert_eq!(
+            err.render("(1 + 2 * 3"),
+            "Parse error: Unclosed parenthesis\n --> 1:11\n  |\n1 | (1 + 2 * 3\n  |           ^"
+        );
+
+        let src = "1 +\n2 +\n\n\n\n\n\n\n\nx ** ** é";
+        let err = parse(src).unwrap_err();
+        assert_eq!(
+            err.render(src).lines().collect::<Vec<_>>(),
+            [
+                "Parse error: Expected number or parenthesis, found '**'",
+                "  --> 10:6",
+                "   |",
+                "10 | x ** ** é",
+                "   |      ^^",
+            ]
+        );
+
+        assert_eq!(
+            eval("1 / 0").unwrap_err().render("1 / 0"),
+            "Division by zero"
+        );
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let kinds = tokenize("x ** 2 mod 10 $")
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            ["identifier", "^", "number", "%", "number", "unknown"]
+        );
+        assert_eq!(tokenize(" 12")[0].0, Span { start: 1, end: 3 });
+    }
+
+    #[test]
+    fn test_no_output() {
+        // Every module, as declared above; the binary in `expr-cli` prints by
+        // design.
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/");
+        let lib = std::fs::read_to_string(format!("{}lib.rs", dir)).unwrap();
+        let modules = lib
+            .lines()
+            .filter_map(|line| line.trim_start_matches("pub ").strip_prefix("mod "))
+            .filter_map(|name| name.strip_suffix(';'))
+            .map(|name| format!("{}.rs", name))
+            .chain(["lib.rs".to_string()]);
+
+        let writes = ["print!(", "println!(", "dbg!(", "stdout()", "stderr()"];
+        let mut checked = 0;
+        for module in modules {
+            let src = std::fs::read_to_string(format!("{}{}", dir, module)).unwrap();
+            let code = src.split("#[cfg(test)]").next().unwrap();
+            for write in writes {
+                assert!(!code.contains(write), "{} calls {}", module, write);
+            }
+            checked += 1;
+        }
+        assert!(checked > 10, "only found {} modules", checked);
+    }
+
+    #[test]
+    fn test_default_context() {
+        let constants = |value| {
+            let mut ctx = Context::new();
+            ctx.set("answer_default", value);
+            ctx
+        };
+        let unknown = Err(ExprError::UnknownVariable("answer_default".into()));
+
+        // Tests share the process, so only this thread's default is changed.
+        assert_eq!(eval("answer_default"), unknown);
+        let inner = with_default_context(constants(42), || {
+            let nested = with_default_context(constants(7), || eval("answer_default * 2"));
+            (nested, eval("answer_default"))
+        });
+        assert_eq!(inner, (Ok(Value::Int(14)), Ok(Value::Int(42))));
+        assert_eq!(eval("answer_default"), unknown);
+
+        let panicked = std::panic::catch_unwind(|| {
+            with_default_context(constants(1), || panic!("in scope"));
+        });
+        assert!(panicked.is_err());
+        assert_eq!(eval("answer_default"), unknown);
+
+        // A fresh thread sees the process-wide default.
+        let process = std::thread::spawn(move || {
+            set_default_context(constants(5));
+            let value = eval("answer_default + 1");
+            set_default_context(Context::new());
+            value
+        });
+        assert_eq!(process.join().unwrap(), Ok(Value::Int(6)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let ast = parse("f(x, 2) in ([1, 3], -4)").unwrap();
+        let json = serde_json::to_string(&ast).unwrap();
+        assert_eq!(serde_json::from_str::<Ast>(&json).unwrap(), ast);
+
+        let value = Value::Interval(Interval::new(-1, 2).unwrap());
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"Interval":{"lo":-1,"hi":2}}"#);
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+        assert!(serde_json::from_str::<Value>(r#"{"Interval":{"lo":2,"hi":1}}"#).is_err());
+
+        let tokens = Tokenizer::new("1 + y").collect::<Vec<_>>();
+        let json = serde_json::to_string(&tokens).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<Spanned<Token>>>(&json).unwrap(),
+            tokens
+        );
+    }
+}
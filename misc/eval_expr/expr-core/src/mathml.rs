@@ -0,0 +1,138 @@
+use crate::{Ast, BinaryOp, UnaryOp};
+
+impl Ast {
+    /// Writes the expression as Presentation MathML, a `<math>` element
+    /// that browsers render natively: `a / b` becomes an `<mfrac>`,
+    /// `x ^ 2` an `<msup>`, and the other operators `<mo>` elements such
+    /// as `⋅` and `mod`. Like [`Ast::to_latex`], it groups with parentheses
+    /// only where precedence needs them.
+    pub fn to_mathml(&self) -> String {
+        format!(
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\">{}</math>",
+            mathml(&self.strip_parens().parenthesize())
+        )
+    }
+}
+
+fn mathml(ast: &Ast) -> String {
+    match ast {
+        Ast::Number(n) if *n < 0 => format!("<mrow><mo>-</mo><mn>{}</mn></mrow>", n.unsigned_abs()),
+        Ast::Number(n) => format!("<mn>{}</mn>", n),
+        Ast::Var(name) => format!("<mi>{}</mi>", escape(name)),
+        Ast::Interval { lo, hi } => {
+            fenced("[", &format!("{}<mo>,</mo>{}", bare(lo), bare(hi)), "]")
+        }
+        Ast::Unary { op, operand } => {
+            let symbol = match op {
+                UnaryOp::Neg => "-",
+                UnaryOp::Not => "¬",
+            };
+            format!("<mrow><mo>{}</mo>{}</mrow>", symbol, mathml(operand))
+        }
+        Ast::Binary { op, lhs, rhs } => match op {
+            BinaryOp::Div => format!("<mfrac>{}{}</mfrac>", bare(lhs), bare(rhs)),
+            BinaryOp::FloorDiv => fenced(
+                "⌊",
+                &format!("<mfrac>{}{}</mfrac>", bare(lhs), bare(rhs)),
+                "⌋",
+            ),
+            BinaryOp::Pow => format!("<msup>{}{}</msup>", mathml(lhs), bare(rhs)),
+            _ => {
+                let symbol = match op {
+                    BinaryOp::Mul => "⋅",
+                    BinaryOp::Mod => "mod",
+                    BinaryOp::And => "∧",
+                    BinaryOp::Or => "∨",
+                    op => op.symbol(),
+                };
+                format!(
+                    "<mrow>{}<mo>{}</mo>{}</mrow>",
+                    mathml(lhs),
+                    symbol,
+                    mathml(rhs)
+                )
+            }
+        },
+        Ast::In { needle, list } => format!(
+            "<mrow>{}<mo>∈</mo>{}</mrow>",
+            mathml(needle),
+            fenced("{", &list_mathml(list), "}")
+        ),
+        // U+2061 is the invisible function application operator.
+        Ast::Call { name, args } => format!(
+            "<mrow><mi>{}</mi><mo>\u{2061}</mo>{}</mrow>",
+            escape(name),
+            fenced("(", &list_mathml(args), ")")
+        ),
+        Ast::Paren(inner) => fenced("(", &mathml(inner), ")"),
+    }
+}
+
+// `ast` without the grouping `Ast::parenthesize` gave it, for places such as
+// a numerator or a function argument that are set apart already.
+fn bare(ast: &Ast) -> String {
+    match ast {
+        Ast::Paren(inner) => mathml(inner),
+        ast => mathml(ast),
+    }
+}
+
+fn list_mathml(items: &[Ast]) -> String {
+    items
+        .iter()
+        .map(bare)
+        .collect::<Vec<_>>()
+        .join("<mo>,</mo>")
+}
+
+// `inner` between the delimiters `open` and `close`.
+fn fenced(open: &str, inner: &str, close: &str) -> String {
+    format!("<mrow><mo>{}</mo>{}<mo>{}</mo></mrow>", open, inner, close)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, Ast};
+
+    #[test]
+    fn test_to_mathml() {
+        let mathml = |src| {
+            let math = parse(src).unwrap().to_mathml();
+            math.strip_prefix("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">")
+                .and_then(|math| math.strip_suffix("</math>"))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            mathml("(a + 1) / 2"),
+            "<mfrac><mrow><mi>a</mi><mo>+</mo><mn>1</mn></mrow><mn>2</mn></mfrac>"
+        );
+        assert_eq!(
+            mathml("(x * 2) ^ (n - 1)"),
+            "<msup><mrow><mo>(</mo><mrow><mi>x</mi><mo>⋅</mo><mn>2</mn></mrow><mo>)</mo></mrow>\
+             <mrow><mi>n</mi><mo>-</mo><mn>1</mn></mrow></msup>"
+        );
+        assert_eq!(
+            mathml("not f(x, (1)) in (1, [0, 2])"),
+            "<mrow><mo>¬</mo><mrow><mrow><mi>f</mi><mo>\u{2061}</mo>\
+             <mrow><mo>(</mo><mi>x</mi><mo>,</mo><mn>1</mn><mo>)</mo></mrow></mrow>\
+             <mo>∈</mo><mrow><mo>{</mo><mn>1</mn><mo>,</mo>\
+             <mrow><mo>[</mo><mn>0</mn><mo>,</mo><mn>2</mn><mo>]</mo></mrow><mo>}</mo></mrow></mrow></mrow>"
+        );
+        assert_eq!(
+            mathml("a // b % 3"),
+            "<mrow><mrow><mo>⌊</mo><mfrac><mi>a</mi><mi>b</mi></mfrac><mo>⌋</mo></mrow>\
+             <mo>mod</mo><mn>3</mn></mrow>"
+        );
+        assert_eq!(
+            Ast::Var("a<b".into()).to_mathml(),
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mi>a&lt;b</mi></math>"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{EvalOptions, ExprError, Result};
+
+/// How many units of fuel are burnt between looks at the clock, which costs
+/// more than the operations it would be timing.
+const CLOCK_INTERVAL: u64 = 64;
+
+/// A handle for stopping evaluations from another thread, e.g. when the
+/// client that asked for them disconnects. Pass a clone to
+/// [`EvalOptions::cancel_token`] and call [`CancelToken::cancel`] on the
+/// original; evaluations under those options then fail with
+/// [`ExprError::Cancelled`] at their next operator or function call.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every evaluation using this token, now and from now on.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Tracks the fuel one evaluation burns against the budget set with
+/// [`EvalOptions::fuel`] and [`EvalOptions::timeout`], and watches for
+/// cancellation. Functions called during the evaluation burn from the same
+/// meter, and record on it the base a `hex`, `bin` or `oct` call asks for.
+#[derive(Debug, Default)]
+pub(crate) struct Meter {
+    spent: Cell<u64>,
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    cancel: Option<CancelToken>,
+    radix: Cell<Option<u32>>,
+}
+
+impl Meter {
+    /// A meter for an evaluation starting now.
+    pub(crate) fn new(options: &EvalOptions) -> Self {
+        Self {
+            spent: Cell::new(0),
+            fuel: options.fuel,
+            deadline: options
+                .timeout
+                .and_then(|timeout: Duration| Instant::now().checked_add(timeout)),
+            cancel: options.cancel.clone(),
+            radix: Cell::new(None),
+        }
+    }
+
+    /// The base the last `hex`, `bin` or `oct` call asked for the value to
+    /// be written in, if any.
+    pub(crate) fn radix(&self) -> Option<u32> {
+        self.radix.get()
+    }
+
+    pub(crate) fn set_radix(&self, radix: u32) {
+        self.radix.set(Some(radix));
+    }
+
+    /// Burns one unit, failing once the budget is exhausted or the
+    /// evaluation was cancelled.
+    pub(crate) fn burn(&self) -> Result<()> {
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(ExprError::Cancelled);
+        }
+        let spent = self.spent.get() + 1;
+        self.spent.set(spent);
+        let out_of_fuel = self.fuel.is_some_and(|fuel| spent > fuel);
+        let out_of_time = spent.is_multiple_of(CLOCK_INTERVAL)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+        if out_of_fuel || out_of_time {
+            return Err(ExprError::BudgetExceeded { fuel: spent - 1 });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burn() {
+        let meter = Meter::new(&EvalOptions::default().fuel(2));
+        assert_eq!(meter.burn(), Ok(()));
+        assert_eq!(meter.burn(), Ok(()));
+        assert_eq!(meter.burn(), Err(ExprError::BudgetExceeded { fuel: 2 }));
+
+        let meter = Meter::new(&EvalOptions::default().timeout(Duration::ZERO));
+        let spent = (0..).take_while(|_| meter.burn().is_ok()).count();
+        assert_eq!(spent as u64, CLOCK_INTERVAL - 1);
+        assert!(Meter::default().burn().is_ok());
+
+        let token = CancelToken::new();
+        let meter = Meter::new(&EvalOptions::default().cancel_token(token.clone()));
+        assert_eq!(meter.burn(), Ok(()));
+        token.cancel();
+        assert_eq!(meter.burn(), Err(ExprError::Cancelled));
+    }
+}
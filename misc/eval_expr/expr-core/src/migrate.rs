@@ -0,0 +1,176 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use crate::{
+    parse,
+    visit::{walk, Visitor},
+    Ast, BinaryOp, Folder, LanguageLevel,
+};
+
+/// Something [`migrate`] could not carry over with certainty, for a person to
+/// review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    message: String,
+}
+
+impl Note {
+    fn new(message: String) -> Self {
+        Self { message }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Note {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Rewrites a formula written for language level `from` so that it means the
+/// same at level `to`, along with notes on anything that needs review.
+///
+/// `%` changed meaning at [`LanguageLevel::V2`], so going up `a % b` becomes
+/// `a - a / b * b` and going down it becomes `a - a // b * b`; both are exact
+/// for integers in every [`DivisionMode`](crate::DivisionMode). A formula
+/// that needs no rewriting is returned as it was, otherwise it is
+/// reformatted. Formulas that fail to parse are returned unchanged with a
+/// note.
+pub fn migrate(src: &str, from: LanguageLevel, to: LanguageLevel) -> (String, Vec<Note>) {
+    let ast = match parse(src) {
+        Ok(ast) => ast,
+        Err(err) => return (src.to_string(), vec![Note::new(err.to_string())]),
+    };
+
+    let mut notes = Vec::new();
+    let mut calls = Calls::default();
+    calls.visit(&ast);
+    for name in calls.names {
+        notes.push(Note::new(format!(
+            "calls '{}', whose definition must be migrated separately",
+            name
+        )));
+    }
+
+    let quotient = match (from, to) {
+        (LanguageLevel::V1, LanguageLevel::V2) => BinaryOp::Div,
+        (LanguageLevel::V2, LanguageLevel::V1) => BinaryOp::FloorDiv,
+        _ => return (src.to_string(), notes),
+    };
+    let mut remainders = Remainders {
+        quotient,
+        rewrites: 0,
+        notes: &mut notes,
+    };
+    let migrated = remainders.fold(ast);
+    match remainders.rewrites {
+        0 => (src.to_string(), notes),
+        _ => (migrated.to_string(), notes),
+    }
+}
+
+// Collects the names of the functions a tree calls.
+#[derive(Default)]
+struct Calls {
+    names: BTreeSet<String>,
+}
+
+impl Visitor for Calls {
+    fn visit_call(&mut self, name: &str, _args: usize) {
+        self.names.insert(name.to_string());
+    }
+}
+
+// Whether a tree contains an interval literal.
+#[derive(Default)]
+struct HasInterval(bool);
+
+impl Visitor for HasInterval {
+    fn visit(&mut self, ast: &Ast) {
+        self.0 |= matches!(ast, Ast::Interval { .. });
+        walk(self, ast);
+    }
+}
+
+// Spells out `a % b` as `a - a <quotient> b * b`.
+struct Remainders<'a> {
+    quotient: BinaryOp,
+    rewrites: usize,
+    notes: &'a mut Vec<Note>,
+}
+
+impl Folder for Remainders<'_> {
+    fn fold_binary(&mut self, op: BinaryOp, lhs: Ast, rhs: Ast) -> Ast {
+        let binary = |op, lhs, rhs| Ast::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        if op != BinaryOp::Mod {
+            return binary(op, lhs, rhs);
+        }
+
+        self.rewrites += 1;
+        let quotient = binary(self.quotient, lhs.clone(), rhs.clone());
+        let rewritten = binary(BinaryOp::Sub, lhs, binary(BinaryOp::Mul, quotient, rhs));
+        let mut intervals = HasInterval::default();
+        intervals.visit(&rewritten);
+        if intervals.0 {
+            self.notes.push(Note::new(format!(
+                "'{}' uses its operands twice, so its bounds are wider in interval mode",
+                rewritten
+            )));
+        }
+        rewritten
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, EvalOptions, Expr};
+
+    #[test]
+    fn test_migrate() {
+        let (v2, notes) = migrate("-7 % 2 + (x mod -3)", LanguageLevel::V1, LanguageLevel::V2);
+        assert_eq!(v2, "-7 - -7 / 2 * 2 + (x - x / -3 * -3)");
+        assert!(notes.is_empty());
+
+        let mut ctx = Context::new();
+        ctx.set("x", 8);
+        let eval = |src, level| {
+            Expr::with_options(src, EvalOptions::default().level(level))
+                .with_context(&ctx)
+                .eval()
+        };
+        assert_eq!(
+            eval(&v2, LanguageLevel::V2),
+            eval("-7 % 2 + (x mod -3)", LanguageLevel::V1)
+        );
+
+        let (v1, _) = migrate(&v2, LanguageLevel::V2, LanguageLevel::V1);
+        assert_eq!(v1, v2);
+        let (v1, _) = migrate("x % 3", LanguageLevel::V2, LanguageLevel::V1);
+        assert_eq!(v1, "x - x // 3 * 3");
+
+        let untouched = "f(x)  *  2";
+        let (same, notes) = migrate(untouched, LanguageLevel::V1, LanguageLevel::V2);
+        assert_eq!(same, untouched);
+        let notes = notes.iter().map(Note::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            notes,
+            ["calls 'f', whose definition must be migrated separately"]
+        );
+
+        let (_, notes) = migrate("[1, 2] % 3", LanguageLevel::V1, LanguageLevel::V2);
+        assert_eq!(
+            notes[0].message(),
+            "'[1, 2] - [1, 2] / 3 * 3' uses its operands twice, so its bounds are wider in interval mode"
+        );
+
+        let (same, notes) = migrate("1 +", LanguageLevel::V1, LanguageLevel::V2);
+        assert_eq!((same.as_str(), notes.len()), ("1 +", 1));
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt::Display;
+
+use crate::{rng::Rng, CompiledExpr, Context, Value};
+
+/// The seed [`CompiledExpr::monte_carlo`] starts from, so that repeated runs
+/// report the same statistics.
+const SEED: u64 = 0x5eed;
+
+/// How [`CompiledExpr::monte_carlo`] draws a variable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    /// Every integer in `lo..=hi` equally likely.
+    Uniform { lo: i32, hi: i32 },
+    /// Normally distributed, rounded to the nearest integer and clamped to
+    /// the `i32` range.
+    Normal { mean: f64, std_dev: f64 },
+}
+
+impl Distribution {
+    fn sample(&self, rng: &mut Rng) -> i32 {
+        match *self {
+            Distribution::Uniform { lo, hi } => rng.range(lo.min(hi), lo.max(hi)),
+            Distribution::Normal { mean, std_dev } => {
+                (mean + std_dev * rng.normal()).round() as i32
+            }
+        }
+    }
+}
+
+impl CompiledExpr {
+    /// Evaluates the expression `n` times, each time drawing the variables in
+    /// `distributions` afresh and taking the rest from `context`, and
+    /// summarizes the results. Draws that fail to evaluate, or give an
+    /// interval, are counted in [`Summary::failures`].
+    ///
+    /// The draws are pseudo-random from a fixed seed, so the same call always
+    /// gives the same summary.
+    pub fn monte_carlo(
+        &self,
+        context: &Context,
+        distributions: &[(&str, Distribution)],
+        n: usize,
+    ) -> Summary {
+        let mut rng = Rng::new(SEED);
+        let mut scope = context.clone();
+        let mut values = Vec::with_capacity(n);
+        let mut failures = 0;
+        for _ in 0..n {
+            for (name, distribution) in distributions {
+                scope.set(*name, distribution.sample(&mut rng));
+            }
+            match self.eval(&scope) {
+                Ok(Value::Int(value)) => values.push(value),
+                _ => failures += 1,
+            }
+        }
+
+        values.sort_unstable();
+        Summary { values, failures }
+    }
+}
+
+/// Statistics over the results of [`CompiledExpr::monte_carlo`]. Every
+/// statistic is `None` when no draw evaluated successfully.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    // Sorted, for the percentiles.
+    values: Vec<i32>,
+    failures: usize,
+}
+
+impl Summary {
+    /// How many draws evaluated successfully.
+    pub fn samples(&self) -> usize {
+        self.values.len()
+    }
+
+    /// How many draws failed to evaluate.
+    pub fn failures(&self) -> usize {
+        self.failures
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.values.is_empty() {
+            return None;
+        }
+        let sum = self.values.iter().map(|&n| n as f64).sum::<f64>();
+        Some(sum / self.values.len() as f64)
+    }
+
+    /// The population standard deviation.
+    pub fn std_dev(&self) -> Option<f64> {
+        let mean = self.mean()?;
+        let squares = self.values.iter().map(|&n| (n as f64 - mean).powi(2));
+        Some((squares.sum::<f64>() / self.values.len() as f64).sqrt())
+    }
+
+    pub fn min(&self) -> Option<i32> {
+        self.values.first().copied()
+    }
+
+    pub fn max(&self) -> Option<i32> {
+        self.values.last().copied()
+    }
+
+    /// The smallest result at least `p` percent of the results are at or
+    /// below, by the nearest-rank method: `percentile(50.0)` is the median.
+    pub fn percentile(&self, p: f64) -> Option<i32> {
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.values.len() as f64).ceil() as usize;
+        self.values.get(rank.max(1) - 1).copied()
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (Some(mean), Some(std_dev), Some(min), Some(max)) =
+            (self.mean(), self.std_dev(), self.min(), self.max())
+        else {
+            return write!(f, "no successful samples, {} failed", self.failures);
+        };
+
+        write!(
+            f,
+            "n={} mean={:.2} sd={:.2} min={} p5={} p50={} p95={} max={}",
+            self.samples(),
+            mean,
+            std_dev,
+            min,
+            self.percentile(5.0).unwrap_or(min),
+            self.percentile(50.0).unwrap_or(min),
+            self.percentile(95.0).unwrap_or(max),
+            max
+        )?;
+        if self.failures > 0 {
+            write!(f, " failed={}", self.failures)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, EvalOptions};
+
+    fn compile(src: &str) -> CompiledExpr {
+        parse(src).unwrap().compile(&EvalOptions::default())
+    }
+
+    #[test]
+    fn test_monte_carlo() {
+        let mut ctx = Context::new();
+        ctx.set("base", 1000);
+
+        let total = compile("base + base * rate / 100");
+        let rate = [("rate", Distribution::Uniform { lo: 5, hi: 9 })];
+        let summary = total.monte_carlo(&ctx, &rate, 2000);
+        assert_eq!((summary.samples(), summary.failures()), (2000, 0));
+        assert_eq!((summary.min(), summary.max()), (Some(1050), Some(1090)));
+        assert_eq!(summary.percentile(50.0), Some(1070));
+        assert!((summary.mean().unwrap() - 1070.0).abs() < 1.0);
+        assert_eq!(summary, total.monte_carlo(&ctx, &rate, 2000));
+
+        let normal = [(
+            "x",
+            Distribution::Normal {
+                mean: 100.0,
+                std_dev: 15.0,
+            },
+        )];
+        let summary = compile("x").monte_carlo(&ctx, &normal, 2000);
+        assert!((summary.mean().unwrap() - 100.0).abs() < 1.5);
+        assert!((summary.std_dev().unwrap() - 15.0).abs() < 1.5);
+
+        let summary = compile("1 / x").monte_carlo(
+            &ctx,
+            &[("x", Distribution::Uniform { lo: 0, hi: 1 })],
+            100,
+        );
+        assert_eq!(summary.samples() + summary.failures(), 100);
+        assert!(summary.failures() > 0);
+        assert_eq!(summary.max(), Some(1));
+
+        let summary = compile("y").monte_carlo(&ctx, &rate, 10);
+        assert_eq!((summary.samples(), summary.mean()), (0, None));
+        assert_eq!(summary.to_string(), "no successful samples, 10 failed");
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary {
+            values: vec![1, 2, 3, 4, 10],
+            failures: 1,
+        };
+        assert_eq!(summary.mean(), Some(4.0));
+        assert_eq!(summary.percentile(0.0), Some(1));
+        assert_eq!(summary.percentile(40.0), Some(2));
+        assert_eq!(summary.percentile(100.0), Some(10));
+        assert_eq!(
+            summary.to_string(),
+            "n=5 mean=4.00 sd=3.16 min=1 p5=1 p50=3 p95=10 max=10 failed=1"
+        );
+    }
+}
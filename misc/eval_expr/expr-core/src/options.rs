@@ -0,0 +1,313 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use crate::{
+    CancelToken, ExprError, NumberFormat, OperatorTable, PrecedenceTable, Result, Warning,
+};
+
+/// What to do when an integer operation does not fit in an `i32`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Fail with [`ExprError::Overflow`](crate::ExprError::Overflow).
+    #[default]
+    Checked,
+    /// Wrap around in two's complement, like C's unsigned arithmetic.
+    Wrapping,
+    /// Clamp to `i32::MIN` or `i32::MAX`.
+    Saturating,
+}
+
+/// How `/` rounds its quotient; at [`LanguageLevel::V1`], `%` returns the
+/// matching remainder so that `(a / b) * b + a % b == a` always holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DivisionMode {
+    /// Round toward zero, like Rust and C: `-7 / 2 == -3`, `-7 % 2 == -1`.
+    #[default]
+    Truncating,
+    /// Round toward negative infinity, like Python: `-7 / 2 == -4`, `-7 % 2 == 1`.
+    Floor,
+    /// Keep the remainder non-negative: `7 / -2 == -3`, `7 % -2 == 1`.
+    Euclidean,
+}
+
+impl DivisionMode {
+    pub(crate) fn quotient(self, l: i32, r: i32) -> Option<i32> {
+        match self {
+            DivisionMode::Truncating => l.checked_div(r),
+            DivisionMode::Floor => l.checked_div(r).map(|q| {
+                if l % r != 0 && (l < 0) != (r < 0) {
+                    q - 1
+                } else {
+                    q
+                }
+            }),
+            DivisionMode::Euclidean => l.checked_div_euclid(r),
+        }
+    }
+
+    // Only `i32::MIN % -1` trips the hardware, and its true value is 0.
+    pub(crate) fn remainder(self, l: i32, r: i32) -> i32 {
+        match self {
+            DivisionMode::Truncating => l.wrapping_rem(r),
+            DivisionMode::Floor => {
+                let rem = l.wrapping_rem(r);
+                if rem != 0 && (rem < 0) != (r < 0) {
+                    rem + r
+                } else {
+                    rem
+                }
+            }
+            DivisionMode::Euclidean => l.wrapping_rem_euclid(r),
+        }
+    }
+}
+
+/// A frozen version of the expression language. Formulas stored long-term
+/// should be evaluated at the level they were written for, so that upgrading
+/// this crate never silently changes what they mean; new levels only take
+/// effect when asked for.
+///
+/// The default is [`LanguageLevel::V1`], the language as first released.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum LanguageLevel {
+    #[default]
+    V1,
+    /// `%` is the remainder of `//`, whatever the [`DivisionMode`], so that
+    /// `(a // b) * b + a % b == a` always holds: `-7 % 2 == 1` and
+    /// `7 % -2 == -1`. At `V1` it is the remainder of `/`.
+    V2,
+}
+
+impl LanguageLevel {
+    /// The newest level, for formulas written from now on.
+    pub const LATEST: LanguageLevel = LanguageLevel::V2;
+}
+
+/// Evaluation settings for [`Expr::with_options`](crate::Expr::with_options),
+/// built up from the defaults one setting at a time, e.g.
+/// `EvalOptions::default().implicit_multiplication(true).max_depth(64)`.
+///
+/// The settings are grouped by what they change:
+///
+/// - the dialect read: [`operators`](EvalOptions::operators),
+///   [`precedence`](EvalOptions::precedence),
+///   [`implicit_multiplication`](EvalOptions::implicit_multiplication),
+///   [`number_format`](EvalOptions::number_format),
+///   [`level`](EvalOptions::level) and
+///   [`intervals`](EvalOptions::intervals);
+/// - the arithmetic: [`overflow`](EvalOptions::overflow),
+///   [`division`](EvalOptions::division) and
+///   [`modulus`](EvalOptions::modulus);
+/// - limits on untrusted input: [`max_depth`](EvalOptions::max_depth),
+///   [`fuel`](EvalOptions::fuel), [`timeout`](EvalOptions::timeout) and
+///   [`cancel_token`](EvalOptions::cancel_token);
+/// - observing evaluation: [`trace_vars`](EvalOptions::trace_vars),
+///   [`on_reject`](EvalOptions::on_reject) and
+///   [`on_warning`](EvalOptions::on_warning).
+///
+/// New settings always default to the behaviour before they existed, so
+/// options built today keep their meaning as the crate grows.
+#[derive(Debug, Clone, Default)]
+pub struct EvalOptions {
+    pub(crate) operators: OperatorTable,
+    pub(crate) precedence: PrecedenceTable,
+    pub(crate) implicit_mul: bool,
+    pub(crate) numbers: Option<NumberFormat>,
+    pub(crate) overflow: OverflowPolicy,
+    pub(crate) division: DivisionMode,
+    pub(crate) intervals: bool,
+    pub(crate) modulus: Option<i32>,
+    pub(crate) trace_vars: bool,
+    pub(crate) on_reject: Option<RejectHook>,
+    pub(crate) on_warning: Option<WarningHook>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) level: LanguageLevel,
+    pub(crate) fuel: Option<u64>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancel: Option<CancelToken>,
+}
+
+type RejectFn = dyn Fn(&str, &ExprError) + Send + Sync;
+
+/// The callback set with [`EvalOptions::on_reject`].
+#[derive(Clone)]
+pub(crate) struct RejectHook(Arc<RejectFn>);
+
+impl Debug for RejectHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RejectHook")
+    }
+}
+
+type WarningFn = dyn Fn(&str, &Warning) + Send + Sync;
+
+/// The callback set with [`EvalOptions::on_warning`].
+#[derive(Clone)]
+pub(crate) struct WarningHook(pub(crate) Arc<WarningFn>);
+
+impl Debug for WarningHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WarningHook")
+    }
+}
+
+impl EvalOptions {
+    /// Sets the operator spellings the tokenizer accepts.
+    pub fn operators(mut self, operators: OperatorTable) -> Self {
+        self.operators = operators;
+        self
+    }
+
+    /// Sets how tightly each operator binds.
+    pub fn precedence(mut self, precedence: PrecedenceTable) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Reads an operand straight after another, as in `2x`, `2(x + 1)` or
+    /// `(a + b)(a - b)`, as multiplying it, at the precedence of `*`. A
+    /// name followed by `(` is still a call, and a number after an operand
+    /// is still an error.
+    pub fn implicit_multiplication(mut self, enabled: bool) -> Self {
+        self.implicit_mul = enabled;
+        self
+    }
+
+    /// Reads numbers with the separators of `format`, e.g.
+    /// `NumberFormat::locale("de")` for `1.234.567`, instead of as plain
+    /// digits.
+    pub fn number_format(mut self, format: NumberFormat) -> Self {
+        self.numbers = Some(format);
+        self
+    }
+
+    /// Sets how integer overflow is handled.
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets how `/` and `%` round.
+    pub fn division(mut self, division: DivisionMode) -> Self {
+        self.division = division;
+        self
+    }
+
+    /// Sets the version of the language to evaluate.
+    pub fn level(mut self, level: LanguageLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// How `%` rounds at the current level.
+    pub(crate) fn remainder(&self) -> DivisionMode {
+        match self.level {
+            LanguageLevel::V1 => self.division,
+            LanguageLevel::V2 => DivisionMode::Floor,
+        }
+    }
+
+    /// Enables interval mode, where `[lo, hi]` literals stand for any value in
+    /// that range and results are [`Value::Interval`](crate::Value::Interval)s
+    /// bounding every outcome.
+    pub fn intervals(mut self, enabled: bool) -> Self {
+        self.intervals = enabled;
+        self
+    }
+
+    /// Records every variable read and function call result during
+    /// [`Expr::eval`](crate::Expr::eval), available afterwards from
+    /// [`Expr::trace`](crate::Expr::trace).
+    pub fn trace_vars(mut self, enabled: bool) -> Self {
+        self.trace_vars = enabled;
+        self
+    }
+
+    /// Rejects input nested more than `limit` levels deep with
+    /// [`ExprError::DepthLimitExceeded`], as a safety valve for untrusted
+    /// input. Each operand on the right of an operator, parenthesized
+    /// expression, argument or list item opens a level, so `(1 + 2) * 3` is
+    /// three deep; left-to-right chains like `1 + 2 + 3` stay at one.
+    ///
+    /// [`Expr::eval`](crate::Expr::eval) and
+    /// [`Expr::compile`](crate::Expr::compile) stop as soon as the limit is
+    /// passed; [`Ast::eval_with`](crate::Ast::eval_with) checks the whole
+    /// tree first.
+    pub fn max_depth(mut self, limit: usize) -> Self {
+        self.max_depth = Some(limit);
+        self
+    }
+
+    /// Stops evaluation with [`ExprError::BudgetExceeded`] once it has burnt
+    /// `limit` units of fuel, so that pathological input cannot pin a CPU.
+    /// Each operator applied and each function call burns one unit, counting
+    /// those inside the functions called; a function defined as `f1(f1(x))`
+    /// twenty levels deep is cheap to write but over a million calls to run.
+    ///
+    /// A [`CompiledExpr`](crate::CompiledExpr) computes a repeated subtree
+    /// once and burns fuel for it once, so it can finish within a limit that
+    /// [`Ast::eval_with`](crate::Ast::eval_with) and
+    /// [`Expr::eval`](crate::Expr::eval) exceed on the same input:
+    /// `(a * b) + (a * b)` burns 2 units compiled and 3 otherwise.
+    pub fn fuel(mut self, limit: u64) -> Self {
+        self.fuel = Some(limit);
+        self
+    }
+
+    /// Stops evaluation with [`ExprError::BudgetExceeded`] once it has run
+    /// for `timeout`. The clock is only read every few dozen operations, so
+    /// the limit can be overshot slightly.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Lets evaluations be stopped early through `token`, see
+    /// [`CancelToken`].
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Calls `hook` with the source and the error whenever
+    /// [`Expr::eval`](crate::Expr::eval) or
+    /// [`Expr::compile`](crate::Expr::compile) rejects an expression as
+    /// malformed, e.g. to log what syntax users attempt. Evaluation errors
+    /// such as overflow are not reported.
+    pub fn on_reject(mut self, hook: impl Fn(&str, &ExprError) + Send + Sync + 'static) -> Self {
+        self.on_reject = Some(RejectHook(Arc::new(hook)));
+        self
+    }
+
+    /// Calls `hook` with the source and the warning for each [`Warning`]
+    /// [`Expr::eval`](crate::Expr::eval) finds, as it finds them; they are
+    /// also collected by [`Expr::warnings`](crate::Expr::warnings).
+    pub fn on_warning(mut self, hook: impl Fn(&str, &Warning) + Send + Sync + 'static) -> Self {
+        self.on_warning = Some(WarningHook(Arc::new(hook)));
+        self
+    }
+
+    /// Passes `result` through, reporting it to the [`EvalOptions::on_reject`]
+    /// hook first if `src` failed to parse.
+    pub(crate) fn report<T>(&self, src: &str, result: Result<T>) -> Result<T> {
+        if let (Some(RejectHook(hook)), Err(err)) = (&self.on_reject, &result) {
+            if err.is_parse() {
+                hook(src, err);
+            }
+        }
+        result
+    }
+
+    /// Performs all arithmetic modulo `p`: literals and results are reduced
+    /// into `0..p` and `/` multiplies by the modular inverse. Exponents are
+    /// ordinary integers, so `3 ^ 10` is 3 to the tenth power, not the third.
+    ///
+    /// # Panics
+    ///
+    /// If `p` is not positive.
+    pub fn modulus(mut self, p: i32) -> Self {
+        assert!(p > 0, "modulus must be positive");
+        self.modulus = Some(p);
+        self
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{
+    rpn::{is_name, operand, words},
+    Ast, BinaryOp, ExprError, Result, Span, UnaryOp,
+};
+
+impl Ast {
+    /// Writes the expression in prefix (Polish) order, e.g. `+ 1 * 2 3`,
+    /// with the same spellings as [`Ast::to_rpn`]: `neg`, `interval`,
+    /// `in/N` and `name/N` each come before their operands.
+    pub fn to_prefix(&self) -> String {
+        let mut out = Vec::new();
+        self.write_prefix(&mut out);
+        out.join(" ")
+    }
+
+    fn write_prefix(&self, out: &mut Vec<String>) {
+        match self {
+            Ast::Number(n) => out.push(n.to_string()),
+            Ast::Var(name) => out.push(name.clone()),
+            Ast::Interval { lo, hi } => {
+                out.push("interval".into());
+                lo.write_prefix(out);
+                hi.write_prefix(out);
+            }
+            Ast::Unary { op, operand } => {
+                out.push(op.symbol().into());
+                operand.write_prefix(out);
+            }
+            Ast::Binary { op, lhs, rhs } => {
+                out.push(op.symbol().into());
+                lhs.write_prefix(out);
+                rhs.write_prefix(out);
+            }
+            Ast::In { needle, list } => {
+                out.push(format!("in/{}", list.len() + 1));
+                needle.write_prefix(out);
+                for item in list {
+                    item.write_prefix(out);
+                }
+            }
+            Ast::Call { name, args } => {
+                out.push(format!("{}/{}", name, args.len()));
+                for arg in args {
+                    arg.write_prefix(out);
+                }
+            }
+            Ast::Paren(inner) => inner.write_prefix(out),
+        }
+    }
+
+    /// Parses prefix input in the notation [`Ast::to_prefix`] writes. Like
+    /// [`Ast::from_rpn`], it gives back the tree without its parentheses.
+    pub fn from_prefix(src: &str) -> Result<Ast> {
+        let mut reader = Reader {
+            src,
+            words: words(src),
+        };
+        let ast = reader.expr()?;
+        match reader.words.next() {
+            Some(span) => Err(ExprError::unexpected(src, span, "end of input")),
+            None => Ok(ast),
+        }
+    }
+}
+
+struct Reader<'a, I> {
+    src: &'a str,
+    words: I,
+}
+
+impl<I: Iterator<Item = Span>> Reader<'_, I> {
+    fn expr(&mut self) -> Result<Ast> {
+        let Some(span) = self.words.next() else {
+            let end = Span {
+                start: self.src.len(),
+                end: self.src.len(),
+            };
+            return Err(ExprError::unexpected(self.src, end, "operand"));
+        };
+        let word = &self.src[span.start..span.end];
+        let ast = if let Some(op) = UnaryOp::from_symbol(word) {
+            Ast::Unary {
+                op,
+                operand: Box::new(self.expr()?),
+            }
+        } else if let Some(op) = BinaryOp::from_symbol(word) {
+            Ast::Binary {
+                op,
+                lhs: Box::new(self.expr()?),
+                rhs: Box::new(self.expr()?),
+            }
+        } else if word == "interval" {
+            Ast::Interval {
+                lo: Box::new(self.expr()?),
+                hi: Box::new(self.expr()?),
+            }
+        } else if let Some((name, count)) = word.rsplit_once('/') {
+            let unexpected = |expected| ExprError::unexpected(self.src, span, expected);
+            let count = count
+                .parse()
+                .map_err(|_| unexpected("operand or operator"))?;
+            match name {
+                "in" if count >= 2 => Ast::In {
+                    needle: Box::new(self.expr()?),
+                    list: self.exprs(count - 1)?,
+                },
+                "in" => return Err(unexpected("in/N with N of 2 or more")),
+                _ if is_name(name) => Ast::Call {
+                    name: name.to_string(),
+                    args: self.exprs(count)?,
+                },
+                _ => return Err(unexpected("operand or operator")),
+            }
+        } else {
+            operand(word)
+                .ok_or_else(|| ExprError::unexpected(self.src, span, "operand or operator"))?
+        };
+        Ok(ast)
+    }
+
+    fn exprs(&mut self, count: usize) -> Result<Vec<Ast>> {
+        (0..count).map(|_| self.expr()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Value};
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(parse("1 + 2 * 3").unwrap().to_prefix(), "+ 1 * 2 3");
+        assert_eq!(
+            parse("-x in ([0, 4], f(y, 3))").unwrap().to_prefix(),
+            "in/3 neg x interval 0 4 f/2 y 3"
+        );
+        assert_eq!(
+            Ast::from_prefix("* + 3 4 2").unwrap().eval(),
+            Ok(Value::Int(14))
+        );
+        for src in [
+            "1 + 2 * 3",
+            "-(x ^ 2) in ([0, 4], f(y, 3), g(), not z)",
+            "(a // b) % 7 or c and 1",
+        ] {
+            let ast = parse(src).unwrap();
+            assert_eq!(
+                Ast::from_prefix(&ast.to_prefix()),
+                Ok(ast.strip_parens()),
+                "{}",
+                src
+            );
+        }
+
+        let error = |src| Ast::from_prefix(src).unwrap_err().to_string();
+        assert_eq!(
+            error("+ 1"),
+            "Parse error: Expected operand, found end of input"
+        );
+        assert_eq!(
+            error("+ 1 2 3"),
+            "Parse error: Expected end of input, found '3'"
+        );
+        assert_eq!(
+            error("** 1 2"),
+            "Parse error: Expected operand or operator, found '**'"
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    ast::Infix,
+    token::{Token, ASSOC_RIGHT},
+    Ast, BinaryOp, UnaryOp,
+};
+
+/// How far continuation lines are indented past the line they continue.
+const INDENT: usize = 4;
+
+impl Ast {
+    /// Prints the expression like [`Display`](std::fmt::Display), but breaks
+    /// any part that does not fit in `max_width` columns across lines: a chain
+    /// of operators puts each further operand on its own indented line, led by
+    /// its operator, and long argument or `in` lists put one item per line.
+    ///
+    /// Parts that cannot be broken, such as a long name, may still overflow.
+    pub fn to_wrapped_string(&self, max_width: usize) -> String {
+        Wrapper { max_width }.wrap(&self.strip_parens().parenthesize(), 0)
+    }
+
+    /// Draws the tree itself, one node per line under its parent, the way
+    /// `cargo tree` does, to show how an expression groups:
+    ///
+    /// ```text
+    /// ^
+    /// ├── 2
+    /// └── ^
+    ///     ├── 3
+    ///     └── 2
+    /// ```
+    ///
+    /// Operators are labelled with their [`symbol`](BinaryOp::symbol), and
+    /// parentheses from the source appear as `()` nodes.
+    pub fn to_tree_string(&self) -> String {
+        let mut lines = vec![tree_label(self)];
+        tree_children(self, "", &mut lines);
+        lines.join("\n")
+    }
+}
+
+fn tree_label(ast: &Ast) -> String {
+    match ast {
+        Ast::Number(n) => n.to_string(),
+        Ast::Var(name) => name.clone(),
+        Ast::Interval { .. } => "[]".to_string(),
+        Ast::Unary { op, .. } => op.symbol().to_string(),
+        Ast::Binary { op, .. } => op.symbol().to_string(),
+        Ast::In { .. } => "in".to_string(),
+        Ast::Call { name, .. } => format!("{}()", name),
+        Ast::Paren(_) => "()".to_string(),
+    }
+}
+
+// Adds a line for each child of `ast`, and theirs in turn, after `prefix`.
+fn tree_children(ast: &Ast, prefix: &str, lines: &mut Vec<String>) {
+    let children: Vec<&Ast> = match ast {
+        Ast::Number(_) | Ast::Var(_) => Vec::new(),
+        Ast::Interval { lo, hi } => vec![lo, hi],
+        Ast::Unary { operand, .. } => vec![operand],
+        Ast::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+        Ast::In { needle, list } => std::iter::once(&**needle).chain(list).collect(),
+        Ast::Call { args, .. } => args.iter().collect(),
+        Ast::Paren(inner) => vec![inner],
+    };
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        lines.push(format!("{}{}{}", prefix, branch, tree_label(child)));
+        tree_children(child, &format!("{}{}", prefix, indent), lines);
+    }
+}
+
+struct Wrapper {
+    max_width: usize,
+}
+
+impl Wrapper {
+    // Lays out `ast` starting at column `indent`; lines after the first carry
+    // their own leading spaces.
+    fn wrap(&self, ast: &Ast, indent: usize) -> String {
+        let flat = Infix(ast).to_string();
+        if indent + flat.len() <= self.max_width {
+            return flat;
+        }
+
+        match ast {
+            Ast::Binary { op, .. } => {
+                let (first, rest) = chain(ast, *op);
+                let mut out = self.wrap(first, indent);
+                let indent = indent + INDENT;
+                for (op, operand) in rest {
+                    let symbol = op.symbol();
+                    out += &format!("\n{}{} ", spaces(indent), symbol);
+                    out += &self.wrap(operand, indent + symbol.len() + 1);
+                }
+                out
+            }
+            Ast::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => format!("-{}", self.wrap(operand, indent + 1)),
+            Ast::Unary {
+                op: UnaryOp::Not,
+                operand,
+            } => format!("not {}", self.wrap(operand, indent + 4)),
+            Ast::Interval { lo, hi } => self.wrap_list("[", &[&**lo, &**hi], "]", indent, indent),
+            Ast::In { needle, list } => {
+                let needle = self.wrap(needle, indent);
+                let (column, base) = match needle.rfind('\n') {
+                    Some(i) => {
+                        let last = &needle[i + 1..];
+                        (last.len(), last.len() - last.trim_start().len())
+                    }
+                    None => (indent + needle.len(), indent),
+                };
+                let list = list.iter().collect::<Vec<_>>();
+                needle + &self.wrap_list(" in (", &list, ")", column, base)
+            }
+            Ast::Call { name, args } => {
+                let args = args.iter().collect::<Vec<_>>();
+                self.wrap_list(&format!("{}(", name), &args, ")", indent, indent)
+            }
+            Ast::Paren(inner) => format!(
+                "(\n{}{}\n{})",
+                spaces(indent + INDENT),
+                self.wrap(inner, indent + INDENT),
+                spaces(indent)
+            ),
+            Ast::Number(_) | Ast::Var(_) => flat,
+        }
+    }
+
+    // Puts each item on its own line between `open` and `close`, unless the
+    // whole list fits after `open`, which starts at `column` of a line
+    // indented by `base`.
+    fn wrap_list(
+        &self,
+        open: &str,
+        items: &[&Ast],
+        close: &str,
+        column: usize,
+        base: usize,
+    ) -> String {
+        let flat = items
+            .iter()
+            .map(|item| Infix(item).to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if column + open.len() + flat.len() + close.len() <= self.max_width {
+            return format!("{}{}{}", open, flat, close);
+        }
+
+        let mut out = open.to_string();
+        for (i, item) in items.iter().enumerate() {
+            let comma = if i + 1 < items.len() { "," } else { "" };
+            out += &format!(
+                "\n{}{}{}",
+                spaces(base + INDENT),
+                self.wrap(item, base + INDENT),
+                comma
+            );
+        }
+        out + &format!("\n{}{}", spaces(base), close)
+    }
+}
+
+// Splits a run of operators of equal precedence, such as `a + b - c` or
+// `a ^ b ^ c`, into its first operand and the operator/operand pairs after it.
+fn chain(ast: &Ast, op: BinaryOp) -> (&Ast, Vec<(BinaryOp, &Ast)>) {
+    let prec = Token::precedence(&op.token());
+    let same_prec = |op: &BinaryOp| Token::precedence(&op.token()) == prec;
+
+    match ast {
+        Ast::Binary { op, lhs, rhs } if same_prec(op) && op.token().assoc() == ASSOC_RIGHT => {
+            let (mut op, mut next, mut rest) = (*op, &**rhs, Vec::new());
+            while let Ast::Binary {
+                op: inner,
+                lhs,
+                rhs,
+            } = next
+            {
+                if !same_prec(inner) {
+                    break;
+                }
+                rest.push((op, &**lhs));
+                (op, next) = (*inner, &**rhs);
+            }
+            rest.push((op, next));
+            (lhs, rest)
+        }
+        Ast::Binary { op, lhs, rhs } if same_prec(op) => {
+            let (first, mut rest) = chain(lhs, *op);
+            rest.push((*op, rhs));
+            (first, rest)
+        }
+        _ => (ast, Vec::new()),
+    }
+}
+
+fn spaces(n: usize) -> String {
+    " ".repeat(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    #[test]
+    fn test_to_wrapped_string() {
+        let wrap = |src, width| parse(src).unwrap().to_wrapped_string(width);
+
+        assert_eq!(wrap("1 + 2 * 3", 80), "1 + 2 * 3");
+        assert_eq!(
+            wrap("alpha * beta + gamma * delta - epsilon", 20),
+            "alpha * beta\n    + gamma * delta\n    - epsilon"
+        );
+        assert_eq!(
+            wrap("(alpha + beta) * gamma", 16),
+            "(alpha + beta)\n    * gamma"
+        );
+        assert_eq!(
+            wrap("(alpha + beta) * gamma", 12),
+            "(\n    alpha\n        + beta\n)\n    * gamma"
+        );
+        assert_eq!(
+            wrap("base ^ exponent ^ 2", 12),
+            "base\n    ^ exponent\n    ^ 2"
+        );
+        assert_eq!(
+            wrap("total(first, second) + 1", 16),
+            "total(\n    first,\n    second\n)\n    + 1"
+        );
+        assert_eq!(
+            wrap("code in (100, 200, 300)", 16),
+            "code in (\n    100,\n    200,\n    300\n)"
+        );
+        assert_eq!(wrap("averyveryverylongname", 8), "averyveryverylongname");
+
+        let src = "f(alpha * beta + gamma, -(delta - epsilon)) * (zeta + eta ^ theta) - not iota in (1, 2)";
+        for width in [10, 20, 40, 80] {
+            let wrapped = parse(src).unwrap().to_wrapped_string(width);
+            let flat = wrapped.split_whitespace().collect::<Vec<_>>().join(" ");
+            assert_eq!(
+                parse(&flat).unwrap().strip_parens(),
+                parse(src).unwrap().strip_parens()
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_tree_string() {
+        let tree = |src| parse(src).unwrap().to_tree_string();
+        assert_eq!(tree("2 ^ 3 ^ 2"), "^\n├── 2\n└── ^\n    ├── 3\n    └── 2");
+        assert_eq!(
+            tree("-(x + 1) in ([0, 2], f(y))"),
+            [
+                "in",
+                "├── neg",
+                "│   └── ()",
+                "│       └── +",
+                "│           ├── x",
+                "│           └── 1",
+                "├── []",
+                "│   ├── 0",
+                "│   └── 2",
+                "└── f()",
+                "    └── y",
+            ]
+            .join("\n")
+        );
+        assert_eq!(tree("x"), "x");
+    }
+}
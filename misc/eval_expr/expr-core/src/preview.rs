@@ -0,0 +1,85 @@
+use crate::{eval, parse, Result, Token, Tokenizer, Value};
+
+/// The provisional value of an expression that is still being typed, from
+/// [`preview`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preview {
+    value: Value,
+    incomplete: bool,
+}
+
+impl Preview {
+    pub fn value(&self) -> Value {
+        self.value
+    }
+
+    /// Whether the value is for a completed version of the input rather than
+    /// the input as written.
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+}
+
+/// Evaluates `src` as if its unclosed parentheses and brackets were closed,
+/// for calculator frontends that show a result as the user types:
+/// `preview("2 * (3 + 4")` is 14, flagged as incomplete. Anything after the
+/// longest prefix that can be completed this way, such as a trailing
+/// operator, is ignored. Fails with the error for `src` as written if no
+/// prefix can be completed.
+pub fn preview(src: &str) -> Result<Preview> {
+    let mut ends = Tokenizer::new(src)
+        .map(|token| token.span.end)
+        .collect::<Vec<_>>();
+    ends.push(src.len());
+
+    for &end in ends.iter().rev() {
+        let closed = format!("{}{}", &src[..end], closers(&src[..end]));
+        if parse(&closed).is_ok() {
+            return Ok(Preview {
+                value: eval(&closed)?,
+                incomplete: closed != src,
+            });
+        }
+    }
+    eval(src).map(|value| Preview {
+        value,
+        incomplete: false,
+    })
+}
+
+// The brackets that would close everything left open in `src`, innermost
+// first.
+fn closers(src: &str) -> String {
+    let mut open = Vec::new();
+    for token in Tokenizer::new(src) {
+        match token.value {
+            Token::LeftParen => open.push(')'),
+            Token::LeftBracket => open.push(']'),
+            Token::RightParen | Token::RightBracket => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    open.iter().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExprError;
+
+    #[test]
+    fn test_preview() {
+        let value = |src| preview(src).map(|preview| (preview.value(), preview.is_incomplete()));
+        assert_eq!(value("2 * (3 + 4)"), Ok((Value::Int(14), false)));
+        assert_eq!(value("2 * (3 + 4"), Ok((Value::Int(14), true)));
+        assert_eq!(value("2 * ((3 + 4) * (1 +"), Ok((Value::Int(14), true)));
+        assert_eq!(
+            value("max(1, 2"),
+            Err(ExprError::UnknownFunction("max".into()))
+        );
+        assert_eq!(value("1 + 2 )"), Ok((Value::Int(3), true)));
+        assert!(value("* 2").is_err());
+    }
+}
@@ -0,0 +1,66 @@
+/// A small deterministic pseudo-random generator (xorshift64*), enough to
+/// pick sample points without pulling in a dependency. The same seed always
+/// gives the same sequence, so results built on it are reproducible.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // Xorshift never leaves the all-zero state.
+        Rng(seed.max(1))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A value in `0.0..1.0`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A value from the standard normal distribution, by Box-Muller.
+    pub(crate) fn normal(&mut self) -> f64 {
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+
+    /// A value in `lo..=hi`.
+    pub(crate) fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        let span = (hi as i64 - lo as i64 + 1) as u64;
+        (lo as i64 + (self.next_u64() % span) as i64) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range() {
+        let mut rng = Rng::new(7);
+        let samples = (0..1000).map(|_| rng.range(-3, 3)).collect::<Vec<_>>();
+        assert!(samples.iter().all(|n| (-3..=3).contains(n)));
+        assert!((-3..=3).all(|n| samples.contains(&n)));
+
+        let mut again = Rng::new(7);
+        assert_eq!(again.range(-3, 3), samples[0]);
+        assert_eq!(
+            Rng::new(0).range(i32::MIN, i32::MAX),
+            Rng::new(1).range(i32::MIN, i32::MAX)
+        );
+    }
+
+    #[test]
+    fn test_normal() {
+        let mut rng = Rng::new(7);
+        let samples = (0..1000).map(|_| rng.normal()).collect::<Vec<_>>();
+        let mean = samples.iter().sum::<f64>() / 1000.0;
+        let var = samples.iter().map(|z| (z - mean).powi(2)).sum::<f64>() / 1000.0;
+        assert!(mean.abs() < 0.1, "{}", mean);
+        assert!((var - 1.0).abs() < 0.1, "{}", var);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{Ast, BinaryOp, ExprError, Result, Span, UnaryOp};
+
+impl Ast {
+    /// Parses postfix input such as `3 4 + 2 *`, in the notation
+    /// [`Ast::to_rpn`] writes: operands are numbers and names, operators
+    /// are spelled as in [`UnaryOp::symbol`] and [`BinaryOp::symbol`], and
+    /// `interval`, `in/N` and `name/N` take their operands from the stack.
+    /// Parsing what `to_rpn` wrote gives back the tree without its
+    /// parentheses, like [`Ast::strip_parens`].
+    pub fn from_rpn(src: &str) -> Result<Ast> {
+        let mut stack = Vec::new();
+        for span in words(src) {
+            let word = &src[span.start..span.end];
+            let take = |stack: &mut Vec<Ast>, n: usize| {
+                if stack.len() < n {
+                    return Err(ExprError::unexpected(src, span, "operand"));
+                }
+                Ok(stack.split_off(stack.len() - n))
+            };
+            let ast = if let Some(op) = UnaryOp::from_symbol(word) {
+                let [operand] = <[Ast; 1]>::try_from(take(&mut stack, 1)?).unwrap();
+                Ast::Unary {
+                    op,
+                    operand: Box::new(operand),
+                }
+            } else if let Some(op) = BinaryOp::from_symbol(word) {
+                let [lhs, rhs] = <[Ast; 2]>::try_from(take(&mut stack, 2)?).unwrap();
+                Ast::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                }
+            } else if word == "interval" {
+                let [lo, hi] = <[Ast; 2]>::try_from(take(&mut stack, 2)?).unwrap();
+                Ast::Interval {
+                    lo: Box::new(lo),
+                    hi: Box::new(hi),
+                }
+            } else if let Some((name, count)) = word.rsplit_once('/') {
+                let count = count
+                    .parse()
+                    .map_err(|_| ExprError::unexpected(src, span, "operand or operator"))?;
+                let mut operands = take(&mut stack, count)?;
+                match name {
+                    "in" if count >= 2 => Ast::In {
+                        needle: Box::new(operands.remove(0)),
+                        list: operands,
+                    },
+                    "in" => {
+                        return Err(ExprError::unexpected(src, span, "in/N with N of 2 or more"))
+                    }
+                    _ if is_name(name) => Ast::Call {
+                        name: name.to_string(),
+                        args: operands,
+                    },
+                    _ => return Err(ExprError::unexpected(src, span, "operand or operator")),
+                }
+            } else {
+                operand(word)
+                    .ok_or_else(|| ExprError::unexpected(src, span, "operand or operator"))?
+            };
+            stack.push(ast);
+        }
+
+        let end = Span {
+            start: src.len(),
+            end: src.len(),
+        };
+        match stack.len() {
+            0 => Err(ExprError::unexpected(src, end, "operand")),
+            1 => Ok(stack.pop().unwrap()),
+            _ => Err(ExprError::unexpected(src, end, "operator")),
+        }
+    }
+}
+
+/// The spans of the whitespace-separated words of `src`.
+pub(crate) fn words(src: &str) -> impl Iterator<Item = Span> + '_ {
+    src.split_whitespace().map(move |word| {
+        let start = word.as_ptr() as usize - src.as_ptr() as usize;
+        Span {
+            start,
+            end: start + word.len(),
+        }
+    })
+}
+
+/// The number or variable `word` stands for, if it is one.
+pub(crate) fn operand(word: &str) -> Option<Ast> {
+    if let Ok(n) = word.parse() {
+        Some(Ast::Number(n))
+    } else if is_name(word) {
+        Some(Ast::Var(word.to_string()))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn is_name(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && word.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Value};
+
+    #[test]
+    fn test_from_rpn() {
+        assert_eq!(
+            Ast::from_rpn("3 4 + 2 *").unwrap().eval(),
+            Ok(Value::Int(14))
+        );
+        assert_eq!(
+            Ast::from_rpn("3 4 + 2 *").unwrap().to_string(),
+            "(3 + 4) * 2"
+        );
+        assert_eq!(
+            Ast::from_rpn(" -5\tneg ").unwrap().eval(),
+            Ok(Value::Int(5))
+        );
+        for src in [
+            "1 + 2 * 3",
+            "-(x ^ 2) in ([0, 4], f(y, 3), g(), not z)",
+            "(a // b) % 7 or c and 1",
+        ] {
+            let ast = parse(src).unwrap();
+            assert_eq!(
+                Ast::from_rpn(&ast.to_rpn()),
+                Ok(ast.strip_parens()),
+                "{}",
+                src
+            );
+        }
+
+        let error = |src| Ast::from_rpn(src).unwrap_err().to_string();
+        assert_eq!(error("1 +"), "Parse error: Expected operand, found '+'");
+        assert_eq!(
+            error("1 2"),
+            "Parse error: Expected operator, found end of input"
+        );
+        assert_eq!(
+            error(""),
+            "Parse error: Expected operand, found end of input"
+        );
+        assert_eq!(
+            error("1 2 **"),
+            "Parse error: Expected operand or operator, found '**'"
+        );
+        assert_eq!(
+            error("1 in/1"),
+            "Parse error: Expected in/N with N of 2 or more, found 'in/1'"
+        );
+        assert_eq!(
+            Ast::from_rpn("1 2 f/3").unwrap_err().span(),
+            Some(Span { start: 4, end: 7 })
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use std::fmt::Display;
+
+use crate::{parse, split_assignment, Ast, Context, EvalOptions, ExprError, Value};
+
+/// A multi-step calculation, such as a `.calc` file: one statement per line,
+/// with `#` starting a comment.
+///
+/// ```text
+/// fn area(w, h) = w * h          # a function
+/// total = 0                      # an assignment
+/// for w in 1..4 {                # 1, 2 and 3; or a list such as (2, 5, 7)
+///     if w % 2 {
+///         total = total + area(w, 10)
+///     } else if w in (2) {
+///         total = total - 1
+///     } else {
+///         total = 0
+///     }
+/// }
+/// total * 2                      # an expression, whose value is the result
+/// ```
+///
+/// Conditions are true when nonzero. Variables are global, including loop
+/// variables, which keep their last value. The value of a script is that of
+/// the last expression statement it ran.
+#[derive(Debug, Clone)]
+pub struct Script {
+    body: Vec<Line>,
+}
+
+/// An error in a [`Script`], and the 1-based line it is on. Spans in the
+/// error are relative to the statement's expression, not the whole script.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    line: usize,
+    error: ExprError,
+}
+
+impl ScriptError {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn error(&self) -> &ExprError {
+        &self.error
+    }
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+type Result<T> = std::result::Result<T, ScriptError>;
+
+#[derive(Debug, Clone)]
+struct Line {
+    number: usize,
+    stmt: Stmt,
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Expr(Ast),
+    Assign(String, Ast),
+    Function {
+        name: String,
+        params: Vec<String>,
+        body: String,
+    },
+    If {
+        cond: Ast,
+        then: Vec<Line>,
+        otherwise: Vec<Line>,
+    },
+    For {
+        var: String,
+        items: Items,
+        body: Vec<Line>,
+    },
+}
+
+// What a `for` loop runs over.
+#[derive(Debug, Clone)]
+enum Items {
+    // `lo..hi`, excluding `hi`.
+    Range(Ast, Ast),
+    List(Vec<Ast>),
+}
+
+impl Script {
+    pub fn parse(src: &str) -> Result<Self> {
+        let lines = src
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.split('#').next().unwrap_or("").trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect::<Vec<_>>();
+        let mut parser = Parser { lines, pos: 0 };
+        match parser.block()? {
+            (body, None) => Ok(Script { body }),
+            (_, Some((number, close))) => Err(unexpected(number, close, "statement")),
+        }
+    }
+
+    /// Runs the script, reading and assigning variables and defining
+    /// functions in `context`, and returns its value, if any.
+    pub fn run(&self, options: &EvalOptions, context: &mut Context) -> Result<Option<Value>> {
+        let mut value = None;
+        exec(&self.body, options, context, &mut value)?;
+        Ok(value)
+    }
+}
+
+// A syntax error in the line `number`, at `found`.
+fn unexpected(number: usize, found: &str, expected: &'static str) -> ScriptError {
+    ScriptError {
+        line: number,
+        error: ExprError::UnexpectedToken {
+            found: Some(found.to_string()).filter(|found| !found.is_empty()),
+            expected,
+            span: None,
+        },
+    }
+}
+
+// `text` with the keyword `word` taken off its start, if it has it.
+fn keyword<'a>(text: &'a str, word: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(word)?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim())
+}
+
+fn is_ident(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Splits `a, (b, c), d` at the commas outside brackets.
+fn split_items(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(text[start..].trim());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+// A line starting with `}` and its number.
+type Close<'a> = (usize, &'a str);
+
+struct Parser<'a> {
+    // The non-blank lines, without comments, and their numbers.
+    lines: Vec<(usize, &'a str)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    // Parses statements up to a line starting with `}`, which it returns,
+    // or to the end of the input.
+    fn block(&mut self) -> Result<(Vec<Line>, Option<Close<'a>>)> {
+        let mut body = Vec::new();
+        while let Some(&(number, text)) = self.lines.get(self.pos) {
+            self.pos += 1;
+            if text.starts_with('}') {
+                return Ok((body, Some((number, text))));
+            }
+            let stmt = self.statement(number, text)?;
+            body.push(Line { number, stmt });
+        }
+        Ok((body, None))
+    }
+
+    // Parses the body of a block opened on line `number`, returning the
+    // closing line.
+    fn nested(&mut self, number: usize) -> Result<(Vec<Line>, Close<'a>)> {
+        match self.block()? {
+            (body, Some(close)) => Ok((body, close)),
+            (_, None) => Err(unexpected(number, "", "'}' closing this block")),
+        }
+    }
+
+    fn statement(&mut self, number: usize, text: &'a str) -> Result<Stmt> {
+        let expr = |src: &str| {
+            parse(src).map_err(|error| ScriptError {
+                line: number,
+                error,
+            })
+        };
+        // The part of a block's first line between the keyword and `{`.
+        let header = |rest: &'a str| {
+            rest.strip_suffix('{')
+                .map(str::trim)
+                .ok_or_else(|| unexpected(number, text, "'{' at the end of the line"))
+        };
+
+        if let Some(rest) = keyword(text, "if") {
+            let cond = expr(header(rest)?)?;
+            return self.if_chain(number, cond);
+        }
+
+        if let Some(rest) = keyword(text, "for") {
+            let (var, items) = header(rest)?
+                .split_once(" in ")
+                .filter(|(var, _)| is_ident(var.trim()))
+                .ok_or_else(|| unexpected(number, text, "'for NAME in ITEMS {'"))?;
+            let items = match items.split_once("..") {
+                Some((lo, hi)) => Items::Range(expr(lo)?, expr(hi)?),
+                None => {
+                    let list = items
+                        .trim()
+                        .strip_prefix('(')
+                        .and_then(|items| items.strip_suffix(')'))
+                        .ok_or_else(|| unexpected(number, items, "range or list"))?;
+                    let items = split_items(list).into_iter().map(expr);
+                    Items::List(items.collect::<Result<_>>()?)
+                }
+            };
+            let (body, (end, close)) = self.nested(number)?;
+            if close != "}" {
+                return Err(unexpected(end, close, "'}'"));
+            }
+            return Ok(Stmt::For {
+                var: var.trim().to_string(),
+                items,
+                body,
+            });
+        }
+
+        if let Some(rest) = keyword(text, "fn") {
+            let signature = || unexpected(number, text, "'fn NAME(PARAMS) = BODY'");
+            let (head, body) = rest.split_once('=').ok_or_else(signature)?;
+            let (name, params) = head
+                .trim()
+                .strip_suffix(')')
+                .and_then(|head| head.split_once('('))
+                .ok_or_else(signature)?;
+            let params = split_items(params);
+            if !is_ident(name.trim()) || !params.iter().all(|param| is_ident(param)) {
+                return Err(signature());
+            }
+            expr(body)?;
+            return Ok(Stmt::Function {
+                name: name.trim().to_string(),
+                params: params.into_iter().map(str::to_string).collect(),
+                body: body.trim().to_string(),
+            });
+        }
+
+        Ok(match split_assignment(text) {
+            Some((name, src)) => Stmt::Assign(name.to_string(), expr(src)?),
+            None => Stmt::Expr(expr(text)?),
+        })
+    }
+
+    // Parses the rest of `if cond {` on line `number`, along with any
+    // `else` branches.
+    fn if_chain(&mut self, number: usize, cond: Ast) -> Result<Stmt> {
+        let (then, (end, close)) = self.nested(number)?;
+        let rest = close[1..].trim();
+        let otherwise = match keyword(rest, "else") {
+            _ if rest.is_empty() => Vec::new(),
+            Some("{") => {
+                let (body, (end, close)) = self.nested(end)?;
+                if close != "}" {
+                    return Err(unexpected(end, close, "'}'"));
+                }
+                body
+            }
+            Some(rest) => match keyword(rest, "if").and_then(|rest| rest.strip_suffix('{')) {
+                Some(cond) => {
+                    let cond = parse(cond).map_err(|error| ScriptError { line: end, error })?;
+                    let stmt = self.if_chain(end, cond)?;
+                    vec![Line { number: end, stmt }]
+                }
+                None => return Err(unexpected(end, close, "'} else {' or '} else if COND {'")),
+            },
+            None => return Err(unexpected(end, close, "'}' or '} else {'")),
+        };
+        Ok(Stmt::If {
+            cond,
+            then,
+            otherwise,
+        })
+    }
+}
+
+fn exec(
+    body: &[Line],
+    options: &EvalOptions,
+    context: &mut Context,
+    value: &mut Option<Value>,
+) -> Result<()> {
+    for line in body {
+        let at = |error| ScriptError {
+            line: line.number,
+            error,
+        };
+        match &line.stmt {
+            Stmt::Expr(ast) => *value = Some(ast.eval_with(options, context).map_err(at)?),
+            Stmt::Assign(name, ast) => {
+                let value = ast.eval_with(options, context).map_err(at)?;
+                context.set(name.as_str(), value);
+            }
+            Stmt::Function { name, params, body } => {
+                let params = params.iter().map(String::as_str).collect::<Vec<_>>();
+                context.define_function(name, &params, body).map_err(at)?;
+            }
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let cond = ast_int(cond, "if", options, context).map_err(at)?;
+                exec(
+                    if cond != 0 { then } else { otherwise },
+                    options,
+                    context,
+                    value,
+                )?;
+            }
+            Stmt::For {
+                var,
+                items: Items::Range(lo, hi),
+                body,
+            } => {
+                let lo = ast_int(lo, "for", options, context).map_err(at)?;
+                let hi = ast_int(hi, "for", options, context).map_err(at)?;
+                for n in lo..hi {
+                    context.set(var.as_str(), n);
+                    exec(body, options, context, value)?;
+                }
+            }
+            Stmt::For {
+                var,
+                items: Items::List(items),
+                body,
+            } => {
+                // The list is evaluated once, before the first iteration.
+                let items = items
+                    .iter()
+                    .map(|item| item.eval_with(options, context))
+                    .collect::<crate::Result<Vec<_>>>()
+                    .map_err(at)?;
+                for item in items {
+                    context.set(var.as_str(), item);
+                    exec(body, options, context, value)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Evaluates `ast`, which must give an integer, for the statement `what`.
+fn ast_int(ast: &Ast, what: &str, options: &EvalOptions, context: &Context) -> crate::Result<i32> {
+    ast.eval_with(options, context)?.expect_int(what)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(src: &str) -> Result<Option<Value>> {
+        Script::parse(src)?.run(&EvalOptions::default(), &mut Context::new())
+    }
+
+    #[test]
+    fn test_script() {
+        let src = "
+            # Sums areas of odd widths and counts even ones.
+            fn area(w, h) = w * h
+            total = 0
+            evens = 0
+            for w in 1..5 {
+                if w % 2 {
+                    total = total + area(w, 10)  # 10 and 30
+                } else if w in (4) {
+                    evens = evens + 100
+                } else {
+                    evens = evens + 1
+                }
+            }
+            for k in (1, 2 * 3, total) {
+                total = total + k
+            }
+            total + evens
+        ";
+        assert_eq!(run(src), Ok(Some(Value::Int(40 + 47 + 101))));
+        assert_eq!(run("x = 1"), Ok(None));
+
+        let mut context = Context::new();
+        context.set("n", 3);
+        let script = Script::parse("for i in 0..n {\n  n = n + i\n}").unwrap();
+        assert_eq!(script.run(&EvalOptions::default(), &mut context), Ok(None));
+        assert_eq!(context.get("n"), Some(Value::Int(6)));
+        assert_eq!(context.get("i"), Some(Value::Int(2)));
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let err = run("x = 1\n\nif x {\n  y\n}").unwrap_err();
+        assert_eq!(
+            (err.line(), err.error()),
+            (4, &ExprError::UnknownVariable("y".into()))
+        );
+        assert_eq!(err.to_string(), "line 4: Unknown variable: y");
+
+        let err = run("for i in 0..3 {\n  i").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: Parse error: Expected '}' closing this block, found end of input"
+        );
+        assert_eq!(run("1\n}").unwrap_err().line(), 2);
+        assert_eq!(run("if 1\n2\n}").unwrap_err().line(), 1);
+        assert_eq!(run("fn f(1) = 2").unwrap_err().line(), 1);
+        assert_eq!(run("x = 1 +").unwrap_err().line(), 1);
+    }
+}
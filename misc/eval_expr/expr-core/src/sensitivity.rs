@@ -0,0 +1,149 @@
+use std::fmt::Display;
+
+use crate::{Ast, Context, EvalOptions, ExprError, Result};
+
+impl Ast {
+    /// Evaluates the expression with the variable `var` as bound in `context`,
+    /// then with it `delta` lower and `delta` higher, to show how much the
+    /// result depends on it: a what-if for "what if the rate were 1 higher".
+    ///
+    /// Fails if `var` is unbound or an interval, or if any of the three
+    /// evaluations fails or gives an interval.
+    pub fn sensitivity(&self, context: &Context, var: &str, delta: i32) -> Result<Sensitivity> {
+        let value = context
+            .get(var)
+            .ok_or_else(|| ExprError::UnknownVariable(var.to_string()))?
+            .expect_int("sensitivity")?;
+        let shifted = |op, n: Option<i32>| {
+            n.ok_or(ExprError::Overflow {
+                op,
+                lhs: value,
+                rhs: delta,
+            })
+        };
+        let lower = shifted("-", value.checked_sub(delta))?;
+        let upper = shifted("+", value.checked_add(delta))?;
+
+        let options = EvalOptions::default();
+        let mut scope = context.clone();
+        let mut eval_at = |n: i32| {
+            scope.set(var, n);
+            self.eval_with(&options, &scope)?.expect_int("sensitivity")
+        };
+
+        Ok(Sensitivity {
+            var: var.to_string(),
+            delta,
+            below: eval_at(lower)?,
+            base: eval_at(value)?,
+            above: eval_at(upper)?,
+        })
+    }
+}
+
+/// The result of [`Ast::sensitivity`]: the expression's value at the
+/// variable's current binding and `delta` either side of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sensitivity {
+    var: String,
+    delta: i32,
+    below: i32,
+    base: i32,
+    above: i32,
+}
+
+impl Sensitivity {
+    /// The value with the variable as bound.
+    pub fn base(&self) -> i32 {
+        self.base
+    }
+
+    /// The value with the variable `delta` lower.
+    pub fn below(&self) -> i32 {
+        self.below
+    }
+
+    /// The value with the variable `delta` higher.
+    pub fn above(&self) -> i32 {
+        self.above
+    }
+
+    /// How much the result changes when the variable drops by `delta`.
+    pub fn impact_below(&self) -> i64 {
+        self.below as i64 - self.base as i64
+    }
+
+    /// How much the result changes when the variable rises by `delta`.
+    pub fn impact_above(&self) -> i64 {
+        self.above as i64 - self.base as i64
+    }
+}
+
+impl Display for Sensitivity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}: {} ({:+}), {}: {}, {}+{}: {} ({:+})",
+            self.var,
+            self.delta,
+            self.below,
+            self.impact_below(),
+            self.var,
+            self.base,
+            self.var,
+            self.delta,
+            self.above,
+            self.impact_above()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_sensitivity() {
+        let mut ctx = Context::new();
+        ctx.set("base", 1000);
+        ctx.set("rate", 7);
+
+        let total = parse("base + base * rate / 100").unwrap();
+        let rate = total.sensitivity(&ctx, "rate", 1).unwrap();
+        assert_eq!(
+            (rate.below(), rate.base(), rate.above()),
+            (1060, 1070, 1080)
+        );
+        assert_eq!((rate.impact_below(), rate.impact_above()), (-10, 10));
+        assert_eq!(
+            rate.to_string(),
+            "rate-1: 1060 (-10), rate: 1070, rate+1: 1080 (+10)"
+        );
+        assert_eq!(ctx.get("rate"), Some(7.into()));
+
+        let square = parse("x * x").unwrap();
+        ctx.set("x", 0);
+        let x = square.sensitivity(&ctx, "x", 3).unwrap();
+        assert_eq!((x.impact_below(), x.impact_above()), (9, 9));
+
+        assert_eq!(
+            total.sensitivity(&ctx, "y", 1),
+            Err(ExprError::UnknownVariable("y".into()))
+        );
+        ctx.set("x", i32::MAX);
+        assert_eq!(
+            square.sensitivity(&ctx, "x", 1),
+            Err(ExprError::Overflow {
+                op: "+",
+                lhs: i32::MAX,
+                rhs: 1
+            })
+        );
+        ctx.set("rate", 0);
+        assert!(parse("base / rate")
+            .unwrap()
+            .sensitivity(&ctx, "rate", 1)
+            .is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{Ast, BinaryOp, ExprError, Result, Span, UnaryOp};
+
+impl Ast {
+    /// Writes the expression as an S-expression, e.g. `(+ 1 (* 2 3))`, a
+    /// compact form that is easy to diff. Operators are spelled as in
+    /// [`UnaryOp::symbol`] and [`BinaryOp::symbol`]; the other nodes are
+    /// `(interval lo hi)`, `(in needle item ...)`, `(call name arg ...)` and
+    /// `(paren inner)`. [`Ast::from_sexpr`] reads it back.
+    pub fn to_sexpr(&self) -> String {
+        match self {
+            Ast::Number(n) => n.to_string(),
+            Ast::Var(name) => name.clone(),
+            Ast::Interval { lo, hi } => list("interval", [&**lo, &**hi]),
+            Ast::Unary { op, operand } => list(op.symbol(), [&**operand]),
+            Ast::Binary { op, lhs, rhs } => list(op.symbol(), [&**lhs, &**rhs]),
+            Ast::In {
+                needle,
+                list: items,
+            } => list("in", std::iter::once(&**needle).chain(items)),
+            Ast::Call { name, args } => list(&format!("call {}", name), args),
+            Ast::Paren(inner) => list("paren", [&**inner]),
+        }
+    }
+
+    /// Reads an S-expression written by [`Ast::to_sexpr`]. Errors are
+    /// [`ExprError::UnexpectedToken`] or [`ExprError::UnclosedParen`], with
+    /// spans into `src`.
+    pub fn from_sexpr(src: &str) -> Result<Ast> {
+        let mut reader = Reader {
+            src,
+            tokens: lex(src).into_iter().peekable(),
+        };
+        let ast = reader.expr()?;
+        match reader.tokens.next() {
+            Some(span) => Err(ExprError::unexpected(src, span, "end of input")),
+            None => Ok(ast),
+        }
+    }
+}
+
+// `(head item ...)`.
+fn list<'a>(head: &str, items: impl IntoIterator<Item = &'a Ast>) -> String {
+    let mut out = format!("({}", head);
+    for item in items {
+        out.push(' ');
+        out.push_str(&item.to_sexpr());
+    }
+    out.push(')');
+    out
+}
+
+// The spans of the parentheses and atoms in `src`.
+fn lex(src: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        if c != '(' && c != ')' {
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+        }
+        spans.push(Span { start, end });
+    }
+    spans
+}
+
+struct Reader<'a> {
+    src: &'a str,
+    tokens: std::iter::Peekable<std::vec::IntoIter<Span>>,
+}
+
+impl<'a> Reader<'a> {
+    fn text(&self, span: Span) -> &'a str {
+        &self.src[span.start..span.end]
+    }
+
+    // The span where input ran out.
+    fn end(&self) -> Span {
+        Span {
+            start: self.src.len(),
+            end: self.src.len(),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Ast> {
+        let Some(span) = self.tokens.next() else {
+            return Err(ExprError::unexpected(self.src, self.end(), "expression"));
+        };
+        match self.text(span) {
+            "(" => self.list(),
+            ")" => Err(ExprError::unexpected(self.src, span, "expression")),
+            atom => {
+                atom_ast(atom).ok_or_else(|| ExprError::unexpected(self.src, span, "expression"))
+            }
+        }
+    }
+
+    // The rest of a list whose `(` has been read.
+    fn list(&mut self) -> Result<Ast> {
+        let Some(span) = self.tokens.next() else {
+            return Err(ExprError::unexpected(self.src, self.end(), "operator"));
+        };
+        let head = self.text(span);
+        let ast = if let Some(op) = UnaryOp::from_symbol(head) {
+            Ast::Unary {
+                op,
+                operand: Box::new(self.expr()?),
+            }
+        } else if let Some(op) = BinaryOp::from_symbol(head) {
+            Ast::Binary {
+                op,
+                lhs: Box::new(self.expr()?),
+                rhs: Box::new(self.expr()?),
+            }
+        } else {
+            match head {
+                "interval" => Ast::Interval {
+                    lo: Box::new(self.expr()?),
+                    hi: Box::new(self.expr()?),
+                },
+                "paren" => Ast::Paren(Box::new(self.expr()?)),
+                "in" => {
+                    let needle = Box::new(self.expr()?);
+                    let list = self.rest()?;
+                    return Ok(Ast::In { needle, list });
+                }
+                "call" => {
+                    let name = self.tokens.next().unwrap_or(self.end());
+                    let Some(Ast::Var(name)) = atom_ast(self.text(name)) else {
+                        return Err(ExprError::unexpected(self.src, name, "function name"));
+                    };
+                    let args = self.rest()?;
+                    return Ok(Ast::Call { name, args });
+                }
+                _ => return Err(ExprError::unexpected(self.src, span, "operator")),
+            }
+        };
+        match self.tokens.next() {
+            Some(span) if self.text(span) == ")" => Ok(ast),
+            Some(span) => Err(ExprError::unexpected(self.src, span, "')'")),
+            None => Err(ExprError::unclosed(self.end())),
+        }
+    }
+
+    // Expressions up to and including the `)` that closes the list.
+    fn rest(&mut self) -> Result<Vec<Ast>> {
+        let mut items = Vec::new();
+        loop {
+            match self.tokens.peek().copied() {
+                Some(span) if self.text(span) == ")" => {
+                    self.tokens.next();
+                    return Ok(items);
+                }
+                Some(_) => items.push(self.expr()?),
+                None => return Err(ExprError::unclosed(self.end())),
+            }
+        }
+    }
+}
+
+// The number or variable an atom stands for; `None` for text that looks
+// like a number but does not fit in an `i32`.
+fn atom_ast(atom: &str) -> Option<Ast> {
+    let numeric = atom
+        .strip_prefix('-')
+        .unwrap_or(atom)
+        .starts_with(|c: char| c.is_ascii_digit());
+    if numeric {
+        atom.parse().ok().map(Ast::Number)
+    } else {
+        Some(Ast::Var(atom.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_sexpr() {
+        assert_eq!(parse("1 + 2 * 3").unwrap().to_sexpr(), "(+ 1 (* 2 3))");
+        assert_eq!(
+            parse("-(x ** 2) in ([0, 4], f(y, 3), g(), not z)")
+                .unwrap()
+                .to_sexpr(),
+            "(in (neg (paren (^ x 2))) (interval 0 4) (call f y 3) (call g) (not z))"
+        );
+        for src in [
+            "1 + 2 * 3",
+            "-(x ^ 2) in ([0, 4], f(y, 3), g(), not z)",
+            "(a // b) % 7 or c and 1",
+        ] {
+            let ast = parse(src).unwrap();
+            assert_eq!(Ast::from_sexpr(&ast.to_sexpr()), Ok(ast), "{}", src);
+        }
+        assert_eq!(
+            Ast::from_sexpr(" (-  -5\n neg) "),
+            Ok(Ast::Binary {
+                op: BinaryOp::Sub,
+                lhs: Box::new(Ast::Number(-5)),
+                rhs: Box::new(Ast::Var("neg".into())),
+            })
+        );
+    }
+
+    #[test]
+    fn test_sexpr_errors() {
+        let error = |src| Ast::from_sexpr(src).unwrap_err().to_string();
+        assert_eq!(error("(+ 1 2 3)"), "Parse error: Expected ')', found '3'");
+        assert_eq!(
+            error("(+ 1"),
+            "Parse error: Expected expression, found end of input"
+        );
+        assert_eq!(error("(in x 1"), "Parse error: Unclosed parenthesis");
+        assert_eq!(
+            error("(** 1 2)"),
+            "Parse error: Expected operator, found '**'"
+        );
+        assert_eq!(
+            error("(call 2)"),
+            "Parse error: Expected function name, found '2'"
+        );
+        assert_eq!(
+            error("99999999999"),
+            "Parse error: Expected expression, found '99999999999'"
+        );
+        assert_eq!(
+            error("x y"),
+            "Parse error: Expected end of input, found 'y'"
+        );
+        assert_eq!(
+            Ast::from_sexpr("(not )").unwrap_err().span(),
+            Some(Span { start: 5, end: 6 })
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{Ast, CompiledExpr, Context, EvalOptions, Result};
+
+impl Ast {
+    /// Finds the value of `var` in `lo..=hi` that makes the expression equal
+    /// `target`, like a spreadsheet's Goal Seek, with the other variables as
+    /// bound in `context`.
+    ///
+    /// The search bisects, so the expression should be monotonic over the
+    /// range: it needs to be on opposite sides of `target` at the two ends,
+    /// and returns `None` otherwise. Since values are integers the target may
+    /// fall between two results, e.g. for `x / 3 == 5` any of `15..=17` hits
+    /// it and for `2 * x == 5` none does; the value whose result is closest
+    /// to `target` is returned, the lower one on a tie.
+    pub fn goal_seek(
+        &self,
+        context: &Context,
+        var: &str,
+        target: i32,
+        lo: i32,
+        hi: i32,
+    ) -> Result<Option<i32>> {
+        let compiled = self.compile(&EvalOptions::default());
+        let mut scope = context.clone();
+        let mut error = |x: i32| distance(&compiled, &mut scope, var, x, target);
+
+        let (mut lo, mut hi) = (lo.min(hi), lo.max(hi));
+        let (mut lo_error, mut hi_error) = (error(lo)?, error(hi)?);
+        if lo_error == 0 {
+            return Ok(Some(lo));
+        }
+        if hi_error == 0 {
+            return Ok(Some(hi));
+        }
+        if lo_error.signum() == hi_error.signum() {
+            return Ok(None);
+        }
+
+        while hi as i64 - lo as i64 > 1 {
+            let mid = ((lo as i64 + hi as i64) / 2) as i32;
+            let mid_error = error(mid)?;
+            if mid_error == 0 {
+                return Ok(Some(mid));
+            }
+            if mid_error.signum() == lo_error.signum() {
+                (lo, lo_error) = (mid, mid_error);
+            } else {
+                (hi, hi_error) = (mid, mid_error);
+            }
+        }
+
+        Ok(Some(if hi_error.abs() < lo_error.abs() {
+            hi
+        } else {
+            lo
+        }))
+    }
+}
+
+// How far the expression is above `target` with `var` set to `x`.
+fn distance(
+    compiled: &CompiledExpr,
+    scope: &mut Context,
+    var: &str,
+    x: i32,
+    target: i32,
+) -> Result<i64> {
+    scope.set(var, x);
+    let value = compiled.eval(scope)?.expect_int("goal seek")?;
+    Ok(value as i64 - target as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, ExprError};
+
+    #[test]
+    fn test_goal_seek() {
+        let mut ctx = Context::new();
+        ctx.set("base", 1000);
+
+        // What rate brings the total to 1070?
+        let total = parse("base + base * rate / 100").unwrap();
+        assert_eq!(total.goal_seek(&ctx, "rate", 1070, 0, 100), Ok(Some(7)));
+        assert_eq!(total.goal_seek(&ctx, "rate", 1000, 0, 100), Ok(Some(0)));
+        assert_eq!(total.goal_seek(&ctx, "rate", 1073, 100, 0), Ok(Some(7)));
+        assert_eq!(total.goal_seek(&ctx, "rate", 999, 0, 100), Ok(None));
+
+        let falling = parse("100 - x * x").unwrap();
+        assert_eq!(falling.goal_seek(&ctx, "x", 19, 0, 50), Ok(Some(9)));
+        assert_eq!(falling.goal_seek(&ctx, "x", 50, 0, 50), Ok(Some(7)));
+
+        let wide = parse("x / 1000").unwrap();
+        let x = wide.goal_seek(&ctx, "x", 5, i32::MIN, i32::MAX).unwrap();
+        assert!((5000..6000).contains(&x.unwrap()), "{:?}", x);
+
+        assert_eq!(
+            total.goal_seek(&ctx, "base", 1, 0, 10),
+            Err(ExprError::UnknownVariable("rate".into()))
+        );
+    }
+}
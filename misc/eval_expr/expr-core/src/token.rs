@@ -0,0 +1,743 @@
+use std::{borrow::Cow, fmt::Debug, sync::Arc};
+
+use crate::{ExprError, Result, Value};
+
+pub(crate) const ASSOC_LEFT: i32 = 0;
+pub(crate) const ASSOC_RIGHT: i32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Token {
+    Number(i32),
+    Ident(String),
+    Plus,
+    Minus,
+    Divide,
+    Multiply,
+    FloorDivide,
+    Modulo,
+    Power,
+    And,
+    Or,
+    Not,
+    In,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Assign,
+    /// An operator added to an [`OperatorTable`], its `index`th.
+    ///
+    /// Neither this nor `Malformed` is serialized. Their `&'static str`
+    /// fields are skipped as well, as otherwise serde would only deserialize
+    /// tokens from `'static` input.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom {
+        #[cfg_attr(feature = "serde", serde(skip))]
+        spelling: &'static str,
+        index: usize,
+        precedence: i32,
+        fixity: Fixity,
+    },
+    /// A number the [`NumberFormat`] rejects, with what was expected.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Malformed(#[cfg_attr(feature = "serde", serde(skip))] &'static str),
+    /// A number beyond the range of `i32`.
+    OutOfRange,
+    Unknown(char),
+}
+
+/// Where a custom operator goes relative to its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Fixity {
+    Infix(Assoc),
+    Prefix,
+    Postfix,
+}
+
+impl Token {
+    pub(crate) fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            Token::Plus
+                | Token::Minus
+                | Token::Multiply
+                | Token::Divide
+                | Token::FloorDivide
+                | Token::Modulo
+                | Token::Power
+                | Token::And
+                | Token::Or
+                | Token::In
+                | Token::Custom {
+                    fixity: Fixity::Infix(_) | Fixity::Postfix,
+                    ..
+                }
+        )
+    }
+
+    /// Whether the token can begin an operand.
+    pub(crate) fn starts_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Minus
+                | Token::Not
+                | Token::Number(_)
+                | Token::Ident(_)
+                | Token::LeftBracket
+                | Token::LeftParen
+                | Token::Custom {
+                    fixity: Fixity::Prefix,
+                    ..
+                }
+        )
+    }
+
+    /// The error for `token`, which cannot start one, standing at `span` in
+    /// `src` in place of an operand.
+    pub(crate) fn operand_error(token: Option<&Token>, src: &str, span: Span) -> ExprError {
+        let expected = match token {
+            Some(Token::OutOfRange) => return ExprError::InvalidNumber { span: Some(span) },
+            Some(Token::Malformed(expected)) => expected,
+            _ => "number or parenthesis",
+        };
+        ExprError::unexpected(src, span, expected)
+    }
+
+    /// Whether the token ends an operand, so that a `-` after it subtracts
+    /// rather than negates.
+    fn ends_operand_before_minus(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_)
+                | Token::OutOfRange
+                | Token::Ident(_)
+                | Token::RightParen
+                | Token::RightBracket
+                | Token::Custom {
+                    fixity: Fixity::Postfix,
+                    ..
+                }
+        )
+    }
+
+    /// Whether the token, straight after an operand, starts another to
+    /// multiply it by under implicit multiplication, as in `2x` or
+    /// `(a + b)(a - b)`. Numbers do not, so `2 3` is still an error.
+    pub(crate) fn follows_implicitly(&self) -> bool {
+        matches!(self, Token::Ident(_) | Token::LeftParen)
+    }
+
+    /// Whether the token ends an enclosing operand, so that recovering from
+    /// an error should stop at it.
+    pub(crate) fn ends_operand(&self) -> bool {
+        matches!(self, Token::RightParen | Token::RightBracket | Token::Comma)
+    }
+
+    pub(crate) fn symbol(&self) -> &'static str {
+        match self {
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Multiply => "*",
+            Token::Divide => "/",
+            Token::FloorDivide => "//",
+            Token::Modulo => "%",
+            Token::Power => "^",
+            Token::And => "and",
+            Token::Or => "or",
+            Token::Not => "not",
+            Token::In => "in",
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::LeftBracket => "[",
+            Token::RightBracket => "]",
+            Token::Comma => ",",
+            Token::Assign => "=",
+            Token::Custom { spelling, .. } => spelling,
+            Token::Number(_) | Token::OutOfRange => "number",
+            Token::Ident(_) => "identifier",
+            Token::Malformed(_) => "malformed",
+            Token::Unknown(_) => "unknown",
+        }
+    }
+
+    pub(crate) fn precedence(op: &Token) -> i32 {
+        match op {
+            Token::Or => 1,
+            Token::And => 2,
+            Token::Not => 3,
+            Token::In => 4,
+            Token::Plus | Token::Minus => 5,
+            Token::Multiply | Token::Divide | Token::FloorDivide | Token::Modulo => 6,
+            Token::Power => 7,
+            Token::Custom { precedence, .. } => *precedence,
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn assoc(&self) -> i32 {
+        match self {
+            Token::Power
+            | Token::Custom {
+                fixity: Fixity::Infix(Assoc::Right),
+                ..
+            } => ASSOC_RIGHT,
+            _ => ASSOC_LEFT,
+        }
+    }
+}
+
+/// Operator spellings recognised by the tokenizer. The longest spelling that
+/// matches the input wins, so `**` is read as one token rather than two `*`.
+const OPERATORS: &[(&str, Token)] = &[
+    ("+", Token::Plus),
+    ("-", Token::Minus),
+    ("*", Token::Multiply),
+    ("/", Token::Divide),
+    ("%", Token::Modulo),
+    ("^", Token::Power),
+    ("(", Token::LeftParen),
+    (")", Token::RightParen),
+    ("[", Token::LeftBracket),
+    ("]", Token::RightBracket),
+    (",", Token::Comma),
+    ("=", Token::Assign),
+    ("and", Token::And),
+    ("or", Token::Or),
+    ("not", Token::Not),
+    ("in", Token::In),
+];
+
+/// Familiar spellings borrowed from other languages, enabled by default.
+const ALIASES: &[(&str, Token)] = &[
+    ("**", Token::Power),
+    ("//", Token::FloorDivide),
+    ("mod", Token::Modulo),
+];
+
+/// The set of operator spellings the tokenizer accepts. Word spellings such
+/// as `and` are keywords; any other word is read as an identifier.
+///
+/// The default table contains the core symbols plus the `**`, `//` and `mod`
+/// aliases; use [`OperatorTable::without`] to disable the ones you dislike,
+/// and [`OperatorTable::infix`], [`OperatorTable::prefix`] and
+/// [`OperatorTable::postfix`] to add operators of your own.
+#[derive(Debug, Clone)]
+pub struct OperatorTable {
+    spellings: Vec<(&'static str, Token)>,
+    custom: Vec<CustomOp>,
+}
+
+impl Default for OperatorTable {
+    fn default() -> Self {
+        Self {
+            spellings: OPERATORS.iter().chain(ALIASES).cloned().collect(),
+            custom: Vec::new(),
+        }
+    }
+}
+
+type InfixFn = dyn Fn(Value, Value) -> Result<Value> + Send + Sync;
+type UnaryFn = dyn Fn(Value) -> Result<Value> + Send + Sync;
+
+/// The implementation of an operator added to an [`OperatorTable`].
+#[derive(Clone)]
+pub(crate) enum CustomOp {
+    Infix(Arc<InfixFn>),
+    Unary(Arc<UnaryFn>),
+}
+
+impl Debug for CustomOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CustomOp")
+    }
+}
+
+impl OperatorTable {
+    /// Removes `spelling` from the table, e.g. `without("//")`.
+    pub fn without(mut self, spelling: &str) -> Self {
+        self.spellings.retain(|(s, _)| *s != spelling);
+        self
+    }
+
+    /// Adds the infix operator `spelling`, e.g. `@` for a dot product or
+    /// `..` for ranges, binding as tightly as `precedence` says on the scale
+    /// of [`PrecedenceTable`] and computing its value with `apply`. A word
+    /// spelling becomes a keyword, like `and`; an existing operator spelled
+    /// the same is replaced.
+    ///
+    /// Only [`Expr::eval`](crate::Expr::eval) evaluates custom operators:
+    /// syntax trees have no node for them, so
+    /// [`parse_with`](crate::parse_with), [`Expr::compile`](crate::Expr::compile),
+    /// [`ExprCache`](crate::ExprCache) and everything else built on an
+    /// [`Ast`](crate::Ast) fail with [`ExprError::Unsupported`](crate::ExprError::Unsupported)
+    /// on input that uses one, as does the
+    /// [`FixedEvaluator`](crate::FixedEvaluator).
+    pub fn infix(
+        self,
+        spelling: &'static str,
+        precedence: i32,
+        assoc: Assoc,
+        apply: impl Fn(Value, Value) -> Result<Value> + Send + Sync + 'static,
+    ) -> Self {
+        let fixity = Fixity::Infix(assoc);
+        self.custom(
+            spelling,
+            precedence,
+            fixity,
+            CustomOp::Infix(Arc::new(apply)),
+        )
+    }
+
+    /// Adds the prefix operator `spelling`, e.g. `~` for bitwise not, whose
+    /// operand is read at `precedence`, as for `-` in
+    /// [`PrecedenceTable::prefix`]. Otherwise like [`OperatorTable::infix`].
+    pub fn prefix(
+        self,
+        spelling: &'static str,
+        precedence: i32,
+        apply: impl Fn(Value) -> Result<Value> + Send + Sync + 'static,
+    ) -> Self {
+        let apply = CustomOp::Unary(Arc::new(apply));
+        self.custom(spelling, precedence, Fixity::Prefix, apply)
+    }
+
+    /// Adds the postfix operator `spelling`, e.g. `!` for factorial or `km`
+    /// for a unit, which applies to the operand before it when it binds at
+    /// least as tightly as that operand's operators: at precedence 8, `2 ^
+    /// 3!` is `2 ^ (3!)`, and at 1, `2 + 3 km` is `(2 + 3) km`. Otherwise
+    /// like [`OperatorTable::infix`].
+    pub fn postfix(
+        self,
+        spelling: &'static str,
+        precedence: i32,
+        apply: impl Fn(Value) -> Result<Value> + Send + Sync + 'static,
+    ) -> Self {
+        let apply = CustomOp::Unary(Arc::new(apply));
+        self.custom(spelling, precedence, Fixity::Postfix, apply)
+    }
+
+    fn custom(
+        mut self,
+        spelling: &'static str,
+        precedence: i32,
+        fixity: Fixity,
+        apply: CustomOp,
+    ) -> Self {
+        let op = Token::Custom {
+            spelling,
+            index: self.custom.len(),
+            precedence: precedence.max(1),
+            fixity,
+        };
+        self.custom.push(apply);
+        self.spellings.retain(|(s, _)| *s != spelling);
+        self.spellings.push((spelling, op));
+        self
+    }
+
+    /// Applies the custom operator `index` to `args`, one operand or two.
+    pub(crate) fn apply_custom(&self, index: usize, args: &[Value]) -> Result<Value> {
+        match (&self.custom[index], args) {
+            (CustomOp::Infix(apply), [l, r]) => apply(*l, *r),
+            (CustomOp::Unary(apply), [operand]) => apply(*operand),
+            _ => unreachable!("custom operator applied to the wrong number of operands"),
+        }
+    }
+
+    fn longest_symbol(&self, rest: &str) -> Option<(&'static str, Token)> {
+        self.spellings
+            .iter()
+            .filter(|(s, _)| !s.starts_with(char::is_alphabetic) && rest.starts_with(s))
+            .max_by_key(|(s, _)| s.len())
+            .cloned()
+    }
+
+    fn word(&self, word: &str) -> Option<Token> {
+        self.spellings
+            .iter()
+            .find(|(s, _)| *s == word)
+            .map(|(_, op)| op.clone())
+    }
+}
+
+/// Which way a chain of operators of equal precedence groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`.
+    Right,
+}
+
+/// How tightly each operator binds, for the parser to consult instead of
+/// the built-in precedences, e.g. to make `^` left-associative or to match
+/// a legacy language.
+///
+/// Operators are named by any spelling of theirs, so `**` and `^` are the
+/// same entry. Precedences are 1 or more, higher binding tighter; the
+/// defaults run from 1 for `or` to 7 for `^`:
+///
+/// ```text
+/// or 1   and 2   not 3   in 4   + - 5   * / // % 6   ^ 7
+/// ```
+///
+/// Prefix `-` binds the operand after it at the precedence of `^`, so that
+/// `-2 ^ 2` is `-(2 ^ 2)`, and `not` at that of `in`; see
+/// [`PrecedenceTable::prefix`]. The table only changes how input is read:
+/// printing an [`Ast`](crate::Ast) always uses the default precedences.
+#[derive(Debug, Clone, Default)]
+pub struct PrecedenceTable {
+    infix: Vec<(Token, i32, Assoc)>,
+    prefix: Vec<(Token, i32)>,
+}
+
+impl PrecedenceTable {
+    /// Sets the precedence and associativity of the infix operator
+    /// `spelling`, e.g. `infix("^", 7, Assoc::Left)`. Spellings of no
+    /// operator are ignored.
+    pub fn infix(mut self, spelling: &str, precedence: i32, assoc: Assoc) -> Self {
+        if let Some(op) = operator(spelling).filter(Token::is_operator) {
+            self.infix.retain(|(token, ..)| *token != op);
+            self.infix.push((op, precedence.max(1), assoc));
+        }
+        self
+    }
+
+    /// Sets the precedence the operand of the prefix operator `spelling`,
+    /// `-` or `not`, is read at: only operators binding at least this
+    /// tightly belong to it.
+    pub fn prefix(mut self, spelling: &str, precedence: i32) -> Self {
+        if let Some(op) = operator(spelling).filter(|op| matches!(op, Token::Minus | Token::Not)) {
+            self.prefix.retain(|(token, _)| *token != op);
+            self.prefix.push((op, precedence.max(1)));
+        }
+        self
+    }
+
+    pub(crate) fn precedence(&self, op: &Token) -> i32 {
+        match self.infix.iter().find(|(token, ..)| token == op) {
+            Some((_, precedence, _)) => *precedence,
+            None => Token::precedence(op),
+        }
+    }
+
+    /// The precedence the right operand of the infix operator `op` is read
+    /// at, one above its own unless it groups to the right.
+    pub(crate) fn rhs_precedence(&self, op: &Token) -> i32 {
+        let assoc = match self.infix.iter().find(|(token, ..)| token == op) {
+            Some((_, _, assoc)) => *assoc,
+            None if op.assoc() == ASSOC_RIGHT => Assoc::Right,
+            None => Assoc::Left,
+        };
+        match assoc {
+            Assoc::Left => self.precedence(op) + 1,
+            Assoc::Right => self.precedence(op),
+        }
+    }
+
+    /// The precedence the operand of the prefix operator `op` is read at.
+    pub(crate) fn operand_precedence(&self, op: &Token) -> i32 {
+        match self.prefix.iter().find(|(token, _)| token == op) {
+            Some((_, precedence)) => *precedence,
+            None if *op == Token::Minus => Token::precedence(&Token::Power),
+            None => Token::precedence(&Token::In),
+        }
+    }
+}
+
+// The operator `spelling` denotes in the default table.
+fn operator(spelling: &str) -> Option<Token> {
+    OPERATORS
+        .iter()
+        .chain(ALIASES)
+        .find(|(s, _)| *s == spelling)
+        .map(|(_, op)| op.clone())
+}
+
+/// How numbers are written in the input, so that numbers formatted for a
+/// locale can be pasted as they are: `1.234.567` in German or `1 234 567`
+/// in French.
+///
+/// Values are integers, so a decimal part must be zeros, as in `1.234,00`.
+/// Input that could mean two things is rejected rather than guessed at: a
+/// group of other than three digits after a grouping separator, or more
+/// than three before one. A comma that could separate arguments only
+/// belongs to the number if digits follow it straight away, so with a
+/// comma decimal separator `f(1,5)` is an error and `f(1, 5)` a call with
+/// two arguments; with a comma or a space grouping separator, one not
+/// followed by exactly three digits is left to mean what it otherwise
+/// would.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    decimal: char,
+    grouping: Option<char>,
+}
+
+impl NumberFormat {
+    /// Numbers with a `decimal` separator and, if any, a `grouping` one.
+    ///
+    /// # Panics
+    ///
+    /// If the separators are the same, or either is a digit.
+    pub fn new(decimal: char, grouping: Option<char>) -> Self {
+        assert!(
+            grouping != Some(decimal),
+            "decimal and grouping separators must differ"
+        );
+        assert!(
+            !decimal.is_ascii_digit() && !grouping.is_some_and(|c| c.is_ascii_digit()),
+            "separators cannot be digits"
+        );
+        Self { decimal, grouping }
+    }
+
+    /// The format of a locale such as `de-DE` or `fr`, by its language and,
+    /// for Switzerland, region; `None` for a locale it does not know.
+    pub fn locale(tag: &str) -> Option<Self> {
+        let tag = tag.split(['.', '@']).next()?.replace('_', "-");
+        let mut parts = tag.split('-');
+        let language = parts.next()?.to_ascii_lowercase();
+        if parts.any(|region| region.eq_ignore_ascii_case("ch")) {
+            return Some(Self::new('.', Some('\'')));
+        }
+        let (decimal, grouping) = match language.as_str() {
+            "c" | "posix" | "en" | "ja" | "ko" | "zh" | "he" | "th" => ('.', ','),
+            "de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" | "ro" | "hr" | "sl" => {
+                (',', '.')
+            }
+            "fr" | "sv" | "nb" | "nn" | "no" | "fi" | "cs" | "sk" | "pl" | "ru" | "uk" | "hu"
+            | "bg" | "et" | "lv" | "lt" => (',', ' '),
+            _ => return None,
+        };
+        Some(Self::new(decimal, Some(grouping)))
+    }
+
+    fn is_grouping(&self, c: char) -> bool {
+        match self.grouping {
+            // Typeset numbers group with no-break spaces.
+            Some(' ') => matches!(c, ' ' | '\u{a0}' | '\u{202f}'),
+            grouping => grouping == Some(c),
+        }
+    }
+
+    // Whether the separator `c` has a meaning of its own when not part of
+    // a number.
+    fn stands_alone(c: char) -> bool {
+        c == ',' || c.is_whitespace()
+    }
+}
+
+/// A range of byte offsets into the source, `start..end`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A token along with where in the source it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Spanned<T> {
+    pub(crate) value: T,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug)]
+pub(crate) struct Tokenizer<'a> {
+    src: &'a str,
+    pos: usize,
+    operators: Cow<'a, OperatorTable>,
+    numbers: Option<NumberFormat>,
+    // Whether the last token ended an operand.
+    after_operand: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub(crate) fn new(src: &'a str) -> Self {
+        Self::with_operators(src, OperatorTable::default())
+    }
+
+    pub(crate) fn with_operators(src: &'a str, operators: OperatorTable) -> Self {
+        Self {
+            src,
+            pos: 0,
+            operators: Cow::Owned(operators),
+            numbers: None,
+            after_operand: false,
+        }
+    }
+
+    /// A tokenizer that borrows `operators` rather than owning a copy.
+    pub(crate) fn borrowing(src: &'a str, operators: &'a OperatorTable) -> Self {
+        Self {
+            src,
+            pos: 0,
+            operators: Cow::Borrowed(operators),
+            numbers: None,
+            after_operand: false,
+        }
+    }
+
+    /// Reads numbers written in `format`, rather than as plain digits.
+    pub(crate) fn with_numbers(mut self, format: Option<NumberFormat>) -> Self {
+        self.numbers = format;
+        self
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn consume_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn scan_number(&mut self) -> Option<Token> {
+        let len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.rest().len());
+        let digits = &self.rest()[..len];
+        self.pos += len;
+        Some(parse_digits(digits.chars()))
+    }
+
+    // Reads `-2147483648`, whose digits alone are out of range, as one
+    // number, where the `-` would negate them and no `^` follows to bind
+    // to them first.
+    fn scan_min(&mut self) -> Option<Token> {
+        let rest = self.rest().strip_prefix('-')?;
+        let digits = rest.trim_start();
+        let after = digits.strip_prefix("2147483648")?;
+        let ends = !after.starts_with(|c: char| c.is_ascii_digit());
+        let power = matches!(
+            self.operators.longest_symbol(after.trim_start()),
+            Some((_, Token::Power))
+        );
+        if self.after_operand || !ends || power {
+            return None;
+        }
+        self.pos = self.src.len() - after.len();
+        Some(Token::Number(i32::MIN))
+    }
+
+    // Reads a number in `format`: digits in groups, and any decimal part,
+    // which must be zeros.
+    fn scan_formatted(&mut self, format: NumberFormat) -> Token {
+        let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let start = self.pos;
+        let mut first = digits(self.rest());
+        self.pos += first;
+
+        while let Some(c) = self.peek_char().filter(|c| format.is_grouping(*c)) {
+            let group = digits(&self.rest()[c.len_utf8()..]);
+            if group == 3 && first <= 3 {
+                self.pos += c.len_utf8() + group;
+                first = 0;
+            } else if group == 0 || NumberFormat::stands_alone(c) {
+                break;
+            } else {
+                self.pos += c.len_utf8() + group;
+                return Token::Malformed("groups of three digits between separators");
+            }
+        }
+
+        let num = parse_digits(
+            self.src[start..self.pos]
+                .chars()
+                .filter(char::is_ascii_digit),
+        );
+
+        if self.peek_char() == Some(format.decimal) {
+            let fraction = digits(&self.rest()[format.decimal.len_utf8()..]);
+            if fraction > 0 {
+                let end = self.pos + format.decimal.len_utf8() + fraction;
+                let fraction = &self.src[end - fraction..end];
+                let zeros = fraction.bytes().all(|b| b == b'0');
+                self.pos = end;
+                if !zeros {
+                    return Token::Malformed(match format.decimal {
+                        ',' => "whole number (a space after ',' separates arguments)",
+                        _ => "whole number",
+                    });
+                }
+            }
+        }
+        num
+    }
+
+    fn scan_word(&mut self) -> Option<Token> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+
+        let word = &rest[..len];
+        self.pos += len;
+        Some(
+            self.operators
+                .word(word)
+                .unwrap_or_else(|| Token::Ident(word.to_string())),
+        )
+    }
+
+    fn scan_operator(&mut self) -> Option<Token> {
+        match self.operators.longest_symbol(self.rest()) {
+            Some((spelling, op)) => {
+                self.pos += spelling.len();
+                Some(op)
+            }
+            None => {
+                let c = self.peek_char()?;
+                self.pos += c.len_utf8();
+                Some(Token::Unknown(c))
+            }
+        }
+    }
+}
+
+// The number `digits` spell, or `OutOfRange` if it is beyond `i32`.
+fn parse_digits(digits: impl IntoIterator<Item = char>) -> Token {
+    let mut num = Some(0i32);
+    for c in digits {
+        let digit = c.to_digit(10).unwrap() as i32;
+        num = num.and_then(|n| n.checked_mul(10)?.checked_add(digit));
+    }
+    num.map_or(Token::OutOfRange, Token::Number)
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Spanned<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.consume_whitespace();
+
+        let start = self.pos;
+        let value = match self.peek_char() {
+            Some('-') => self.scan_min().or_else(|| self.scan_operator()),
+            Some(c) if c.is_ascii_digit() => match self.numbers {
+                Some(format) => Some(self.scan_formatted(format)),
+                None => self.scan_number(),
+            },
+            Some(c) if c.is_alphabetic() || c == '_' => self.scan_word(),
+            Some(_) => self.scan_operator(),
+            None => None,
+        }?;
+        self.after_operand = value.ends_operand_before_minus();
+        Some(Spanned {
+            value,
+            span: Span {
+                start,
+                end: self.pos,
+            },
+        })
+    }
+}
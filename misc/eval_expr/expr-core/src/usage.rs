@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use crate::{DivisionMode, EvalOptions, OverflowPolicy};
+
+/// Which features evaluations used, from [`Expr::usage`](crate::Expr::usage):
+/// how often each operator was applied and each function called, and which
+/// non-default modes were on. [`UsageStats::merge`] adds up the stats of many
+/// evaluations, e.g. to see how much of a service's traffic uses intervals.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageStats {
+    evaluations: usize,
+    operators: BTreeMap<&'static str, usize>,
+    functions: BTreeMap<String, usize>,
+    modes: BTreeMap<&'static str, usize>,
+}
+
+impl UsageStats {
+    /// The stats of a single evaluation under `options`.
+    pub(crate) fn new(
+        options: &EvalOptions,
+        operators: &BTreeMap<&'static str, usize>,
+        functions: &BTreeMap<String, usize>,
+    ) -> Self {
+        let modes = [
+            (options.intervals, "intervals"),
+            (options.modulus.is_some(), "modulus"),
+            (options.overflow == OverflowPolicy::Wrapping, "wrapping"),
+            (options.overflow == OverflowPolicy::Saturating, "saturating"),
+            (options.division == DivisionMode::Floor, "floor division"),
+            (
+                options.division == DivisionMode::Euclidean,
+                "euclidean division",
+            ),
+        ];
+
+        Self {
+            evaluations: 1,
+            operators: operators.clone(),
+            functions: functions.clone(),
+            modes: modes
+                .into_iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, mode)| (mode, 1))
+                .collect(),
+        }
+    }
+
+    /// How many evaluations these stats cover.
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    /// How many times each operator was applied, keyed by its symbol (`neg`
+    /// for unary minus).
+    pub fn operators(&self) -> &BTreeMap<&'static str, usize> {
+        &self.operators
+    }
+
+    /// How many times each user-defined function was called.
+    pub fn functions(&self) -> &BTreeMap<String, usize> {
+        &self.functions
+    }
+
+    /// How many evaluations ran with each non-default mode: `intervals`,
+    /// `modulus`, `wrapping`, `saturating`, `floor division` or
+    /// `euclidean division`.
+    pub fn modes(&self) -> &BTreeMap<&'static str, usize> {
+        &self.modes
+    }
+
+    /// Adds the counts in `other` to these.
+    pub fn merge(&mut self, other: &UsageStats) {
+        self.evaluations += other.evaluations;
+        for (op, count) in &other.operators {
+            *self.operators.entry(op).or_default() += count;
+        }
+        for (name, count) in &other.functions {
+            *self.functions.entry(name.clone()).or_default() += count;
+        }
+        for (mode, count) in &other.modes {
+            *self.modes.entry(mode).or_default() += count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Expr};
+
+    #[test]
+    fn test_usage_stats() {
+        let mut ctx = Context::new();
+        ctx.define_function("sq", &["n"], "n * n").unwrap();
+
+        let mut total = UsageStats::default();
+        for (src, options) in [
+            ("sq(2) + sq(3) * 2", EvalOptions::default()),
+            ("-[1, 2] + 1", EvalOptions::default().intervals(true)),
+            (
+                "7 / -2",
+                EvalOptions::default()
+                    .modulus(11)
+                    .division(DivisionMode::Floor),
+            ),
+        ] {
+            let mut expr = Expr::with_options(src, options).with_context(&ctx);
+            expr.eval().unwrap();
+            total.merge(&expr.usage());
+        }
+
+        assert_eq!(total.evaluations(), 3);
+        let operators = total.operators().clone().into_iter().collect::<Vec<_>>();
+        assert_eq!(operators, [("*", 1), ("+", 2), ("/", 1), ("neg", 2)]);
+        let functions = total.functions().clone().into_iter().collect::<Vec<_>>();
+        assert_eq!(functions, [("sq".to_string(), 2)]);
+        let modes = total.modes().clone().into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            modes,
+            [("floor division", 1), ("intervals", 1), ("modulus", 1)]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt::Display;
+
+use crate::{token::Token, DivisionMode, EvalOptions, ExprError, Result};
+
+/// The result of evaluating an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Value {
+    Int(i32),
+    Interval(Interval),
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Self {
+        Value::Int(n)
+    }
+}
+
+impl PartialEq<i32> for Value {
+    fn eq(&self, other: &i32) -> bool {
+        *self == Value::Int(*other)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Interval(i) => write!(f, "{}", i),
+        }
+    }
+}
+
+/// A closed range of integers `[lo, hi]` that is guaranteed to contain the
+/// true result of a calculation.
+///
+/// Interval bounds always use checked arithmetic whatever the configured
+/// [`OverflowPolicy`](crate::OverflowPolicy): a wrapped or clamped bound would
+/// no longer enclose the true result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Bounds"))]
+pub struct Interval {
+    lo: i32,
+    hi: i32,
+}
+
+// What an `Interval` is deserialized from, so that `lo <= hi` is checked.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Bounds {
+    lo: i32,
+    hi: i32,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<Bounds> for Interval {
+    type Error = ExprError;
+
+    fn try_from(bounds: Bounds) -> Result<Self> {
+        Self::new(bounds.lo, bounds.hi)
+    }
+}
+
+impl From<i32> for Interval {
+    fn from(n: i32) -> Self {
+        Self { lo: n, hi: n }
+    }
+}
+
+impl From<Value> for Interval {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Int(n) => n.into(),
+            Value::Interval(i) => i,
+        }
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+impl Interval {
+    pub fn new(lo: i32, hi: i32) -> Result<Self> {
+        if lo > hi {
+            return Err(ExprError::InvalidInterval { lo, hi });
+        }
+        Ok(Self { lo, hi })
+    }
+
+    pub fn lo(&self) -> i32 {
+        self.lo
+    }
+
+    pub fn hi(&self) -> i32 {
+        self.hi
+    }
+
+    fn contains(&self, n: i32) -> bool {
+        self.lo <= n && n <= self.hi
+    }
+
+    // Smallest interval containing all of `values`
+    fn hull(values: impl IntoIterator<Item = i32>) -> Self {
+        let mut values = values.into_iter();
+        let first = values.next().expect("hull of no values");
+        values.fold(first.into(), |acc: Interval, n| Self {
+            lo: acc.lo.min(n),
+            hi: acc.hi.max(n),
+        })
+    }
+
+    pub(crate) fn negate(self) -> Result<Self> {
+        let neg = |n: i32| {
+            n.checked_neg().ok_or(ExprError::Overflow {
+                op: Token::Minus.symbol(),
+                lhs: 0,
+                rhs: n,
+            })
+        };
+        Ok(Self {
+            lo: neg(self.hi)?,
+            hi: neg(self.lo)?,
+        })
+    }
+
+    pub(crate) fn compute(self, op: &Token, rhs: Interval, options: &EvalOptions) -> Result<Self> {
+        let overflowed = |lhs, rhs| ExprError::Overflow {
+            op: op.symbol(),
+            lhs,
+            rhs,
+        };
+        let corners = [
+            (self.lo, rhs.lo),
+            (self.lo, rhs.hi),
+            (self.hi, rhs.lo),
+            (self.hi, rhs.hi),
+        ];
+        // Applies `f` to every pair of bounds; enough for operations that are
+        // monotonic in each argument.
+        let at_corners = |f: &dyn Fn(i32, i32) -> Option<i32>| -> Result<Self> {
+            let values = corners
+                .iter()
+                .map(|&(l, r)| f(l, r).ok_or_else(|| overflowed(l, r)))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Self::hull(values))
+        };
+
+        if matches!(op, Token::Divide | Token::FloorDivide | Token::Modulo) && rhs.contains(0) {
+            return Err(ExprError::DivisionByZero);
+        }
+
+        match op {
+            Token::Plus => at_corners(&|l, r| l.checked_add(r)),
+            Token::Minus => at_corners(&|l, r| l.checked_sub(r)),
+            Token::Multiply => at_corners(&|l, r| l.checked_mul(r)),
+            Token::Divide => at_corners(&|l, r| options.division.quotient(l, r)),
+            Token::FloorDivide => at_corners(&|l, r| DivisionMode::Floor.quotient(l, r)),
+            Token::Modulo => Ok(self.remainder(rhs, options.remainder())),
+            Token::Power => self.pow(rhs),
+            _ => Err(ExprError::Unsupported(format!(
+                "'{}' on intervals",
+                op.symbol()
+            ))),
+        }
+    }
+
+    fn remainder(self, rhs: Interval, division: DivisionMode) -> Self {
+        if self.lo == self.hi && rhs.lo == rhs.hi {
+            return division.remainder(self.lo, rhs.lo).into();
+        }
+
+        // |remainder| is always less than |divisor|; which signs it can take
+        // depends on the rounding mode.
+        let max = (rhs.lo.unsigned_abs().max(rhs.hi.unsigned_abs()) - 1) as i32;
+        match division {
+            DivisionMode::Truncating => Self {
+                lo: if self.lo < 0 { self.lo.max(-max) } else { 0 },
+                hi: if self.hi > 0 { self.hi.min(max) } else { 0 },
+            },
+            DivisionMode::Floor if rhs.lo > 0 => Self { lo: 0, hi: max },
+            DivisionMode::Floor => Self { lo: -max, hi: 0 },
+            DivisionMode::Euclidean => Self { lo: 0, hi: max },
+        }
+    }
+
+    fn pow(self, exponent: Interval) -> Result<Self> {
+        if exponent.lo < 0 {
+            return Err(ExprError::NegativeExponent {
+                base: self.lo,
+                exponent: exponent.lo,
+            });
+        }
+
+        // Magnitudes only grow with the exponent, so the extremes of each sign
+        // are reached at the smallest and largest exponents of each parity.
+        let mut exponents = vec![exponent.lo, exponent.hi];
+        if exponent.lo < exponent.hi {
+            exponents.extend([exponent.lo + 1, exponent.hi - 1]);
+        }
+
+        let mut bounds = Vec::new();
+        for exp in exponents {
+            let pow = |base: i32| {
+                base.checked_pow(exp as u32).ok_or(ExprError::Overflow {
+                    op: Token::Power.symbol(),
+                    lhs: base,
+                    rhs: exp,
+                })
+            };
+            bounds.extend([pow(self.lo)?, pow(self.hi)?]);
+            // An even power of an interval spanning zero bottoms out at zero.
+            if exp % 2 == 0 && exp > 0 && self.contains(0) {
+                bounds.push(0);
+            }
+        }
+
+        Ok(Self::hull(bounds))
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{ast::Parser, Ast, BinaryOp, OperatorTable, Result, UnaryOp};
+
+/// A parsed expression that borrows its literals and names from the source
+/// instead of copying them, for tools that parse many formulas only to
+/// inspect them. Its shape is that of [`Ast`], which
+/// [`AstView::to_ast`] converts it to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AstView<'src> {
+    /// A number literal as written, and its value.
+    Number {
+        text: &'src str,
+        value: i32,
+    },
+    Var(&'src str),
+    Interval {
+        lo: Box<AstView<'src>>,
+        hi: Box<AstView<'src>>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<AstView<'src>>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<AstView<'src>>,
+        rhs: Box<AstView<'src>>,
+    },
+    In {
+        needle: Box<AstView<'src>>,
+        list: Vec<AstView<'src>>,
+    },
+    Call {
+        name: &'src str,
+        args: Vec<AstView<'src>>,
+    },
+    Paren(Box<AstView<'src>>),
+}
+
+impl<'src> AstView<'src> {
+    /// Parses `src` with the default operator table, like
+    /// [`parse`](crate::parse).
+    pub fn parse(src: &'src str) -> Result<Self> {
+        Parser::new(src, OperatorTable::default()).parse_view()
+    }
+
+    /// Copies the tree into an [`Ast`] that does not borrow the source.
+    pub fn to_ast(&self) -> Ast {
+        let boxed = |view: &AstView| Box::new(view.to_ast());
+        let list = |views: &[AstView]| views.iter().map(AstView::to_ast).collect();
+        match self {
+            AstView::Number { value, .. } => Ast::Number(*value),
+            AstView::Var(name) => Ast::Var(name.to_string()),
+            AstView::Interval { lo, hi } => Ast::Interval {
+                lo: boxed(lo),
+                hi: boxed(hi),
+            },
+            AstView::Unary { op, operand } => Ast::Unary {
+                op: *op,
+                operand: boxed(operand),
+            },
+            AstView::Binary { op, lhs, rhs } => Ast::Binary {
+                op: *op,
+                lhs: boxed(lhs),
+                rhs: boxed(rhs),
+            },
+            AstView::In {
+                needle,
+                list: items,
+            } => Ast::In {
+                needle: boxed(needle),
+                list: list(items),
+            },
+            AstView::Call { name, args } => Ast::Call {
+                name: name.to_string(),
+                args: list(args),
+            },
+            AstView::Paren(inner) => Ast::Paren(boxed(inner)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_view() {
+        let src = "f(rate, 007) * -[x, 2] in (1, y ** 2)";
+        let view = AstView::parse(src).unwrap();
+        assert_eq!(view.to_ast(), parse(src).unwrap());
+
+        let AstView::In { needle, .. } = &view else {
+            panic!("{:?}", view);
+        };
+        let AstView::Binary { lhs, .. } = &**needle else {
+            panic!("{:?}", needle);
+        };
+        let AstView::Call { name, args } = &**lhs else {
+            panic!("{:?}", lhs);
+        };
+        assert_eq!(*name, "f");
+        assert!(src.as_bytes().as_ptr_range().contains(&name.as_ptr()));
+        assert_eq!(
+            args[1],
+            AstView::Number {
+                text: "007",
+                value: 7
+            }
+        );
+
+        assert_eq!(
+            AstView::parse("1 +").unwrap_err(),
+            parse("1 +").unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Traversals over an [`Ast`] that spare implementors from matching every
+//! node kind themselves.
+//!
+//! A [`Visitor`] inspects a tree and a [`Folder`] rebuilds one. Both come with
+//! a default for every method, so an implementation only overrides the nodes
+//! it cares about.
+
+use crate::{Ast, BinaryOp, UnaryOp};
+
+/// Read-only traversal of an [`Ast`], e.g. to count operators or collect
+/// variable names.
+///
+/// [`Visitor::visit`] is called for every node and by default hands it to
+/// [`walk`], which calls the matching `visit_*` hook and then visits the
+/// children. Override `visit` itself to skip subtrees or change the order.
+pub trait Visitor {
+    fn visit(&mut self, ast: &Ast) {
+        walk(self, ast);
+    }
+
+    fn visit_number(&mut self, _n: i32) {}
+
+    fn visit_var(&mut self, _name: &str) {}
+
+    fn visit_unary(&mut self, _op: UnaryOp) {}
+
+    fn visit_binary(&mut self, _op: BinaryOp) {}
+
+    /// Called for an `in` test whose list has `len` items.
+    fn visit_in(&mut self, _len: usize) {}
+
+    /// Called for a call to `name` with `args` arguments.
+    fn visit_call(&mut self, _name: &str, _args: usize) {}
+}
+
+/// Calls the `visit_*` hook for `ast`, then [`Visitor::visit`]s its children
+/// from left to right.
+pub fn walk<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast) {
+    match ast {
+        Ast::Number(n) => visitor.visit_number(*n),
+        Ast::Var(name) => visitor.visit_var(name),
+        Ast::Interval { lo, hi } => {
+            visitor.visit(lo);
+            visitor.visit(hi);
+        }
+        Ast::Unary { op, operand } => {
+            visitor.visit_unary(*op);
+            visitor.visit(operand);
+        }
+        Ast::Binary { op, lhs, rhs } => {
+            visitor.visit_binary(*op);
+            visitor.visit(lhs);
+            visitor.visit(rhs);
+        }
+        Ast::In { needle, list } => {
+            visitor.visit_in(list.len());
+            visitor.visit(needle);
+            for item in list {
+                visitor.visit(item);
+            }
+        }
+        Ast::Call { name, args } => {
+            visitor.visit_call(name, args.len());
+            for arg in args {
+                visitor.visit(arg);
+            }
+        }
+        Ast::Paren(inner) => visitor.visit(inner),
+    }
+}
+
+/// Bottom-up rewrite of an [`Ast`], e.g. to rename variables or replace
+/// `x * 2` with `x + x`.
+///
+/// [`Folder::fold`] by default hands each node to [`fold_children`], which
+/// folds the children first and passes them to the matching `fold_*` hook.
+/// The hooks rebuild the node unchanged unless overridden.
+pub trait Folder {
+    fn fold(&mut self, ast: Ast) -> Ast {
+        fold_children(self, ast)
+    }
+
+    fn fold_number(&mut self, n: i32) -> Ast {
+        Ast::Number(n)
+    }
+
+    fn fold_var(&mut self, name: String) -> Ast {
+        Ast::Var(name)
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, operand: Ast) -> Ast {
+        Ast::Unary {
+            op,
+            operand: Box::new(operand),
+        }
+    }
+
+    fn fold_binary(&mut self, op: BinaryOp, lhs: Ast, rhs: Ast) -> Ast {
+        Ast::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+}
+
+/// [`Folder::fold`]s the children of `ast`, then rebuilds it with the
+/// matching `fold_*` hook. Intervals, `in` tests, calls and parentheses have
+/// no hook and are rebuilt as they were.
+pub fn fold_children<F: Folder + ?Sized>(folder: &mut F, ast: Ast) -> Ast {
+    match ast {
+        Ast::Number(n) => folder.fold_number(n),
+        Ast::Var(name) => folder.fold_var(name),
+        Ast::Interval { lo, hi } => Ast::Interval {
+            lo: Box::new(folder.fold(*lo)),
+            hi: Box::new(folder.fold(*hi)),
+        },
+        Ast::Unary { op, operand } => {
+            let operand = folder.fold(*operand);
+            folder.fold_unary(op, operand)
+        }
+        Ast::Binary { op, lhs, rhs } => {
+            let lhs = folder.fold(*lhs);
+            let rhs = folder.fold(*rhs);
+            folder.fold_binary(op, lhs, rhs)
+        }
+        Ast::In { needle, list } => Ast::In {
+            needle: Box::new(folder.fold(*needle)),
+            list: list.into_iter().map(|item| folder.fold(item)).collect(),
+        },
+        Ast::Call { name, args } => Ast::Call {
+            name,
+            args: args.into_iter().map(|arg| folder.fold(arg)).collect(),
+        },
+        Ast::Paren(inner) => Ast::Paren(Box::new(folder.fold(*inner))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::parse;
+
+    #[derive(Default)]
+    struct OpCounter {
+        counts: BTreeMap<&'static str, usize>,
+        vars: Vec<String>,
+    }
+
+    impl Visitor for OpCounter {
+        fn visit_var(&mut self, name: &str) {
+            self.vars.push(name.to_string());
+        }
+
+        fn visit_unary(&mut self, _op: UnaryOp) {
+            *self.counts.entry("unary").or_default() += 1;
+        }
+
+        fn visit_binary(&mut self, op: BinaryOp) {
+            *self.counts.entry(op.symbol()).or_default() += 1;
+        }
+    }
+
+    struct DoubleToAdd;
+
+    impl Folder for DoubleToAdd {
+        fn fold_binary(&mut self, op: BinaryOp, lhs: Ast, rhs: Ast) -> Ast {
+            match (op, rhs) {
+                (BinaryOp::Mul, Ast::Number(2)) => Ast::Binary {
+                    op: BinaryOp::Add,
+                    lhs: Box::new(lhs.clone()),
+                    rhs: Box::new(lhs),
+                },
+                (op, rhs) => Ast::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut counter = OpCounter::default();
+        counter.visit(&parse("-x * 2 + y * (x - 1) in (z)").unwrap());
+
+        let counts = counter.counts.into_iter().collect::<Vec<_>>();
+        assert_eq!(counts, [("*", 2), ("+", 1), ("-", 1), ("unary", 1)]);
+        assert_eq!(counter.vars, ["x", "y", "x", "z"]);
+    }
+
+    #[test]
+    fn test_folder() {
+        let ast = DoubleToAdd.fold(parse("x * 2 - (y * 2) * 3").unwrap());
+        assert_eq!(ast.to_string(), "x + x - (y + y) * 3");
+        assert_eq!(
+            DoubleToAdd.fold(parse("2 * x").unwrap()),
+            parse("2 * x").unwrap()
+        );
+    }
+}
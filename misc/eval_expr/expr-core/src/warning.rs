@@ -0,0 +1,60 @@
+use std::fmt::Display;
+
+use crate::{error::render_at, Span};
+
+/// Something in an expression that is valid but likely not what was meant,
+/// such as a chain of `^` or a division that discards a remainder. Collected
+/// by [`Expr::warnings`](crate::Expr::warnings) and passed to the
+/// [`EvalOptions::on_warning`](crate::EvalOptions::on_warning) hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    kind: WarningKind,
+    message: String,
+    span: Span,
+}
+
+/// What a [`Warning`] is about, for branching on it rather than on its
+/// message or the source under its span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A chain such as `a ^ b ^ c`, which groups to the right.
+    PowerChain,
+    /// An integer division that discards a remainder, such as `7 / 2`.
+    InexactDivision,
+}
+
+impl Warning {
+    pub(crate) fn new(kind: WarningKind, message: String, span: Span) -> Self {
+        Self {
+            kind,
+            message,
+            span,
+        }
+    }
+
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The operator the warning is about.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Describes the warning with the line of `source` it is on, like
+    /// [`ExprError::render`](crate::ExprError::render).
+    pub fn render(&self, source: &str) -> String {
+        render_at(&self.to_string(), self.span, source)
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Warning: {}", self.message)
+    }
+}